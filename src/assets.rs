@@ -0,0 +1,535 @@
+//! Loading and sharing textures, shader programs, meshes and materials.
+//!
+//! Loads are deduplicated by path, so loading the same file twice hands out the same asset.
+//! Every handle counts as a reference until `AssetManager::release`, and assets nobody
+//! references are only unloaded by `AssetManager::unload_unused`. Assets are looked up with
+//! `AssetManager::get`, which returns `None` once they are unloaded.
+
+use std::{fmt, hash, marker::PhantomData, ops};
+
+use crate::{
+    material::Material,
+    modelling::model::Mesh,
+    shader_program::ShaderProgram,
+    loading::Loader,
+    texture::{self, Texture},
+    Result,
+};
+
+/// Refers to an asset of type `T` in an `AssetManager`. Handles to unloaded assets never refer
+/// to anything again, even once their slot is reused.
+pub struct Handle<T> {
+    index: usize,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    const fn new(index: usize, generation: u32) -> Self {
+        Self {
+            index,
+            generation,
+            marker: PhantomData,
+        }
+    }
+}
+
+// Derives would needlessly require `T` to implement each trait
+impl<T> Clone for Handle<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> hash::Hash for Handle<T> {
+    #[inline]
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+mod sealed {
+    use std::collections::HashMap;
+
+    use super::{AssetManager, Handle};
+
+    pub struct Slot<T> {
+        pub asset: Option<T>,
+        pub generation: u32,
+        pub references: usize,
+        pub key: Option<String>,
+    }
+
+    /// Every asset of one type, slots of unloaded assets are reused
+    pub struct Storage<T> {
+        pub slots: Vec<Slot<T>>,
+        pub free: Vec<usize>,
+        pub keys: HashMap<String, usize>,
+    }
+
+    impl<T> Default for Storage<T> {
+        fn default() -> Self {
+            Self {
+                slots: Vec::new(),
+                free: Vec::new(),
+                keys: HashMap::new(),
+            }
+        }
+    }
+
+    impl<T> Storage<T> {
+        pub fn slot(&self, handle: Handle<T>) -> Option<&Slot<T>> {
+            self.slots
+                .get(handle.index)
+                .filter(|slot| slot.generation == handle.generation && slot.asset.is_some())
+        }
+
+        pub fn slot_mut(&mut self, handle: Handle<T>) -> Option<&mut Slot<T>> {
+            self.slots
+                .get_mut(handle.index)
+                .filter(|slot| slot.generation == handle.generation && slot.asset.is_some())
+        }
+
+        /// Another reference to the asset loaded under `key`, if any
+        pub fn find(&mut self, key: &str) -> Option<Handle<T>> {
+            let index = *self.keys.get(key)?;
+            let slot = &mut self.slots[index];
+            slot.references += 1;
+            Some(Handle::new(index, slot.generation))
+        }
+
+        pub fn insert(&mut self, asset: T, key: Option<String>) -> Handle<T> {
+            let index = self.free.pop().unwrap_or(self.slots.len());
+            if let Some(key) = &key {
+                self.keys.insert(key.clone(), index);
+            }
+
+            let slot = Slot {
+                asset: Some(asset),
+                generation: self.slots.get(index).map_or(0, |slot| slot.generation + 1),
+                references: 1,
+                key,
+            };
+            let handle = Handle::new(index, slot.generation);
+            if index == self.slots.len() {
+                self.slots.push(slot);
+            } else {
+                self.slots[index] = slot;
+            }
+            handle
+        }
+
+        pub fn unload(&mut self, index: usize) {
+            let slot = &mut self.slots[index];
+            if slot.asset.take().is_some() {
+                if let Some(key) = slot.key.take() {
+                    self.keys.remove(&key);
+                }
+                slot.references = 0;
+                self.free.push(index);
+            }
+        }
+
+        /// Unload every asset without references, returning how many there were
+        pub fn unload_unused(&mut self) -> usize {
+            let unused: Vec<usize> = self
+                .slots
+                .iter()
+                .enumerate()
+                .filter(|(_, slot)| slot.asset.is_some() && slot.references == 0)
+                .map(|(index, _)| index)
+                .collect();
+            for &index in &unused {
+                self.unload(index);
+            }
+            unused.len()
+        }
+    }
+
+    impl<T: Stored> Storage<T> {
+        /// How many assets are loaded and their memory, measured now as textures may have
+        /// finished loading since they were added
+        pub fn usage(&self) -> (usize, usize) {
+            self.slots
+                .iter()
+                .filter_map(|slot| slot.asset.as_ref())
+                .fold((0, 0), |(count, memory), asset| {
+                    (count + 1, memory + asset.memory())
+                })
+        }
+    }
+
+    pub trait Stored: Sized {
+        fn storage(assets: &AssetManager) -> &Storage<Self>;
+        fn storage_mut(assets: &mut AssetManager) -> &mut Storage<Self>;
+        /// Estimated bytes of GPU memory, not counting other assets it refers to
+        fn memory(&self) -> usize;
+    }
+}
+
+use sealed::{Storage, Stored};
+
+/// Anything an `AssetManager` can hold: `Texture`, `ShaderProgram`, `Mesh` and `Material`
+pub trait Asset: Stored {}
+
+impl Asset for Texture {}
+impl Asset for ShaderProgram {}
+impl Asset for Mesh {}
+impl Asset for Material {}
+
+impl Stored for Texture {
+    fn storage(assets: &AssetManager) -> &Storage<Self> {
+        &assets.textures
+    }
+
+    fn storage_mut(assets: &mut AssetManager) -> &mut Storage<Self> {
+        &mut assets.textures
+    }
+
+    fn memory(&self) -> usize {
+        Self::memory(self)
+    }
+}
+
+impl Stored for ShaderProgram {
+    fn storage(assets: &AssetManager) -> &Storage<Self> {
+        &assets.shaders
+    }
+
+    fn storage_mut(assets: &mut AssetManager) -> &mut Storage<Self> {
+        &mut assets.shaders
+    }
+
+    fn memory(&self) -> usize {
+        0
+    }
+}
+
+impl Stored for Mesh {
+    fn storage(assets: &AssetManager) -> &Storage<Self> {
+        &assets.meshes
+    }
+
+    fn storage_mut(assets: &mut AssetManager) -> &mut Storage<Self> {
+        &mut assets.meshes
+    }
+
+    fn memory(&self) -> usize {
+        Self::memory(self)
+    }
+}
+
+impl Stored for Material {
+    fn storage(assets: &AssetManager) -> &Storage<Self> {
+        &assets.materials
+    }
+
+    fn storage_mut(assets: &mut AssetManager) -> &mut Storage<Self> {
+        &mut assets.materials
+    }
+
+    // Its textures are counted wherever they are stored
+    fn memory(&self) -> usize {
+        0
+    }
+}
+
+/// How many assets of each type are loaded and the GPU memory they use in bytes. Shader
+/// programs and materials are counted but their memory is not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub textures: usize,
+    pub texture_bytes: usize,
+    pub shaders: usize,
+    pub meshes: usize,
+    pub mesh_bytes: usize,
+    pub materials: usize,
+}
+
+impl MemoryUsage {
+    #[must_use]
+    #[inline]
+    pub const fn total_bytes(&self) -> usize {
+        self.texture_bytes + self.mesh_bytes
+    }
+}
+
+#[derive(Default)]
+pub struct AssetManager {
+    textures: Storage<Texture>,
+    shaders: Storage<ShaderProgram>,
+    meshes: Storage<Mesh>,
+    materials: Storage<Material>,
+}
+
+impl AssetManager {
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the image at `path` as a texture, or reference it again if already loaded
+    /// # Errors
+    /// Returns an error if the image cannot be opened or decoded
+    #[inline]
+    pub fn load_texture(&mut self, path: &str) -> Result<Handle<Texture>> {
+        self.load_texture_with(path, |builder| builder)
+    }
+
+    /// Load the image at `path` as a texture, adjusting the builder with `configure` first.
+    /// `configure` is not called if the texture is already loaded.
+    /// # Errors
+    /// Returns an error if the image cannot be opened or decoded
+    #[inline]
+    pub fn load_texture_with(
+        &mut self,
+        path: &str,
+        configure: impl FnOnce(texture::Builder) -> texture::Builder,
+    ) -> Result<Handle<Texture>> {
+        if let Some(handle) = self.textures.find(path) {
+            return Ok(handle);
+        }
+
+        let texture = configure(Texture::builder().image(path)?).build()?;
+        Ok(self.insert(Some(path.to_owned()), texture))
+    }
+
+    /// Start loading the image at `path` on `loader`'s threads, or reference it again if already
+    /// loaded or loading. The texture is a placeholder until `Loader::update` uploads it.
+    #[inline]
+    pub fn load_texture_async(&mut self, loader: &mut Loader, path: &str) -> Handle<Texture> {
+        self.load_texture_async_with(loader, path, |builder| builder)
+    }
+
+    /// As `load_texture_async`, adjusting the builder with `configure` on the worker thread.
+    /// `configure` is not called if the texture is already loaded.
+    #[inline]
+    pub fn load_texture_async_with(
+        &mut self,
+        loader: &mut Loader,
+        path: &str,
+        configure: impl FnOnce(texture::Builder) -> texture::Builder + Send + 'static,
+    ) -> Handle<Texture> {
+        self.textures.find(path).unwrap_or_else(|| {
+            self.insert(
+                Some(path.to_owned()),
+                loader.load_texture_with(path, configure),
+            )
+        })
+    }
+
+    /// Compile and link the shaders at `vertex` and `fragment`, or reference the program again
+    /// if that pair is already loaded
+    /// # Errors
+    /// Returns an error if the program fails to compile or link
+    #[inline]
+    pub fn load_shader(&mut self, vertex: &str, fragment: &str) -> Result<Handle<ShaderProgram>> {
+        let key = format!("{vertex}\n{fragment}");
+        if let Some(handle) = self.shaders.find(&key) {
+            return Ok(handle);
+        }
+
+        let shader_program = ShaderProgram::builder()
+            .add_vertex_shader(vertex)
+            .add_fragment_shader(fragment)
+            .build()?;
+        Ok(self.insert(Some(key), shader_program))
+    }
+
+    /// Hold an asset made some other way, such as a generated mesh
+    #[inline]
+    pub fn add<T: Asset>(&mut self, asset: T) -> Handle<T> {
+        self.insert(None, asset)
+    }
+
+    /// Hold an asset under `name`, or reference the asset already held under `name` instead
+    /// and drop `asset`
+    #[inline]
+    pub fn add_named<T: Asset>(&mut self, name: &str, asset: T) -> Handle<T> {
+        T::storage_mut(self)
+            .find(name)
+            .unwrap_or_else(|| self.insert(Some(name.to_owned()), asset))
+    }
+
+    fn insert<T: Asset>(&mut self, key: Option<String>, asset: T) -> Handle<T> {
+        T::storage_mut(self).insert(asset, key)
+    }
+
+    /// `None` if the asset has been unloaded
+    #[must_use]
+    #[inline]
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Option<&T> {
+        T::storage(self).slot(handle)?.asset.as_ref()
+    }
+
+    /// `None` if the asset has been unloaded
+    #[must_use]
+    #[inline]
+    pub fn get_mut<T: Asset>(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        T::storage_mut(self).slot_mut(handle)?.asset.as_mut()
+    }
+
+    /// The handle of whatever was loaded or added under `path` or name, without referencing it
+    #[must_use]
+    #[inline]
+    pub fn find<T: Asset>(&self, path: &str) -> Option<Handle<T>> {
+        let storage = T::storage(self);
+        let index = *storage.keys.get(path)?;
+        Some(Handle::new(index, storage.slots[index].generation))
+    }
+
+    /// Reference the asset again, to be released separately. Returns `false` if it has been
+    /// unloaded.
+    #[inline]
+    pub fn retain<T: Asset>(&mut self, handle: Handle<T>) -> bool {
+        T::storage_mut(self)
+            .slot_mut(handle)
+            .map(|slot| slot.references += 1)
+            .is_some()
+    }
+
+    /// Drop one reference to the asset. It stays loaded until `unload_unused`, even with no
+    /// references left.
+    #[inline]
+    pub fn release<T: Asset>(&mut self, handle: Handle<T>) {
+        if let Some(slot) = T::storage_mut(self).slot_mut(handle) {
+            slot.references = slot.references.saturating_sub(1);
+        }
+    }
+
+    /// How many references the asset has, 0 if it has been unloaded
+    #[must_use]
+    #[inline]
+    pub fn references<T: Asset>(&self, handle: Handle<T>) -> usize {
+        T::storage(self)
+            .slot(handle)
+            .map_or(0, |slot| slot.references)
+    }
+
+    /// Unload the asset now whatever its references. Copies taken with `get(..).cloned()`
+    /// keep textures and meshes alive on the GPU until dropped.
+    #[inline]
+    pub fn unload<T: Asset>(&mut self, handle: Handle<T>) {
+        if T::storage(self).slot(handle).is_some() {
+            T::storage_mut(self).unload(handle.index);
+        }
+    }
+
+    /// Unload every asset without references, returning how many were unloaded
+    #[inline]
+    pub fn unload_unused(&mut self) -> usize {
+        self.textures.unload_unused()
+            + self.shaders.unload_unused()
+            + self.meshes.unload_unused()
+            + self.materials.unload_unused()
+    }
+
+    /// Totals over every loaded asset
+    #[must_use]
+    #[inline]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let (textures, texture_bytes) = self.textures.usage();
+        let (meshes, mesh_bytes) = self.meshes.usage();
+        MemoryUsage {
+            textures,
+            texture_bytes,
+            shaders: self.shaders.usage().0,
+            meshes,
+            mesh_bytes,
+            materials: self.materials.usage().0,
+        }
+    }
+}
+
+/// Shorthand for `AssetManager::get` where the asset is known to still be loaded, e.g. while
+/// setting up a scene
+impl<T: Asset> ops::Index<Handle<T>> for AssetManager {
+    type Output = T;
+
+    /// # Panics
+    /// Panics if the asset has been unloaded, use `AssetManager::get` wherever it may have been
+    #[inline]
+    fn index(&self, handle: Handle<T>) -> &T {
+        self.get(handle).expect("Asset has been unloaded")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Assets need OpenGL, so the storage behind every `AssetManager` is tested with numbers
+    fn storage() -> Storage<u32> {
+        Storage::default()
+    }
+
+    #[test]
+    fn loads_are_deduplicated_by_key() {
+        let mut storage = storage();
+        assert!(storage.find("a.png").is_none());
+
+        let first = storage.insert(1, Some("a.png".to_owned()));
+        let second = storage.find("a.png").unwrap();
+        assert_eq!(first, second);
+        assert_eq!(storage.slot(first).unwrap().references, 2);
+
+        let other = storage.insert(2, None);
+        assert_ne!(first, other);
+        assert_eq!(storage.slot(other).unwrap().asset, Some(2));
+    }
+
+    #[test]
+    fn only_unused_assets_are_unloaded() {
+        let mut storage = storage();
+        let used = storage.insert(1, Some("used".to_owned()));
+        let unused = storage.insert(2, Some("unused".to_owned()));
+        storage.slot_mut(unused).unwrap().references = 0;
+
+        assert_eq!(storage.unload_unused(), 1);
+        assert!(storage.slot(used).is_some());
+        assert!(storage.slot(unused).is_none());
+        assert!(storage.find("unused").is_none());
+        assert_eq!(storage.unload_unused(), 0);
+    }
+
+    #[test]
+    fn handles_go_stale_when_their_slot_is_reused() {
+        let mut storage = storage();
+        let stale = storage.insert(1, Some("a.png".to_owned()));
+        storage.unload(stale.index);
+        assert!(storage.slot(stale).is_none());
+        assert!(storage.slot_mut(stale).is_none());
+
+        // The slot is reused for the next asset, but the old handle still refers to nothing
+        let fresh = storage.insert(2, Some("a.png".to_owned()));
+        assert_eq!(fresh.index, stale.index);
+        assert_ne!(fresh, stale);
+        assert!(storage.slot(stale).is_none());
+        assert_eq!(storage.slot(fresh).unwrap().asset, Some(2));
+        assert_eq!(storage.find("a.png"), Some(fresh));
+    }
+}
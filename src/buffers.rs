@@ -1,4 +1,33 @@
 pub(crate) mod element_array_buffer;
 pub mod framebuffer;
+pub mod instance_buffer;
 pub mod vertex_array;
 pub(crate) mod vertex_buffer;
+pub mod vertex_format;
+
+/// How often the contents of a vertex or element buffer are expected to change
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Uploaded once and drawn many times
+    #[default]
+    Static,
+    /// Changed occasionally and drawn many times
+    Dynamic,
+    /// Rebuilt most frames, replacing the contents orphans the old storage rather than waiting
+    /// for the GPU to finish with it
+    Stream,
+    /// Vertex data is written straight into `segments` persistently mapped copies of the buffer in
+    /// turn, each guarded by a fence. Requires OpenGL 4.4 or `ARB_buffer_storage`. Element buffers
+    /// treat this as `Stream`.
+    Ring { segments: usize },
+}
+
+impl BufferUsage {
+    pub(crate) const fn gl_usage(self) -> u32 {
+        match self {
+            Self::Static => gl::STATIC_DRAW,
+            Self::Dynamic => gl::DYNAMIC_DRAW,
+            Self::Stream | Self::Ring { .. } => gl::STREAM_DRAW,
+        }
+    }
+}
@@ -1,10 +1,48 @@
-use crate::{error_fmt, EngineError::ElementArrayErr};
-use std::{mem, ptr::addr_of_mut, rc::Rc};
+use crate::{
+    buffers::{vertex_array::RESTART_INDEX, BufferUsage},
+    error_fmt,
+    EngineError::ElementArrayErr,
+};
+use std::{cell::Cell, mem, ptr, ptr::addr_of_mut, rc::Rc};
+
+/// Width of the indices stored in GPU memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+    U16,
+    U32,
+}
+
+impl IndexType {
+    pub(crate) const fn gl_type(self) -> u32 {
+        match self {
+            Self::U16 => gl::UNSIGNED_SHORT,
+            Self::U32 => gl::UNSIGNED_INT,
+        }
+    }
+
+    pub(crate) const fn size(self) -> usize {
+        match self {
+            Self::U16 => mem::size_of::<u16>(),
+            Self::U32 => mem::size_of::<u32>(),
+        }
+    }
+
+    /// The index OpenGL treats as a restart for this width
+    pub(crate) const fn restart_index(self) -> u32 {
+        match self {
+            Self::U16 => u16::MAX as u32,
+            Self::U32 => u32::MAX,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 struct ElementArrayBufferInternal {
     id: u32,
-    len: i32,
+    len: Cell<i32>,
+    index_type: Cell<IndexType>,
+    usage: BufferUsage,
+    primitive_restart: bool,
 }
 
 impl Drop for ElementArrayBufferInternal {
@@ -15,9 +53,94 @@ impl Drop for ElementArrayBufferInternal {
     }
 }
 
+fn byte_size<T>(contents: &[T]) -> crate::Result<isize> {
+    mem::size_of_val(contents).try_into().map_err(|_| {
+        ElementArrayErr(error_fmt!(ElementArrayBuffer, "EAB size exceeds isize"))
+    })
+}
+
+fn element_count(contents: &[u32]) -> crate::Result<i32> {
+    contents.len().try_into().map_err(|_| {
+        ElementArrayErr(error_fmt!(ElementArrayBuffer, "EAB length exceeds i32"))
+    })
+}
+
+/// `contents` as 16 bit indices, or `None` if any of them needs 32 bits.
+/// With primitive restart `u16::MAX` is reserved for `RESTART_INDEX`.
+fn narrow(contents: &[u32], primitive_restart: bool) -> Option<Vec<u16>> {
+    contents
+        .iter()
+        .map(|&index| {
+            if primitive_restart && index == RESTART_INDEX {
+                Some(u16::MAX)
+            } else if primitive_restart && index == u32::from(u16::MAX) {
+                None
+            } else {
+                index.try_into().ok()
+            }
+        })
+        .collect()
+}
+
+/// Upload `contents` as the whole buffer, or part of it if `offset` is given
+unsafe fn upload<T>(
+    id: u32,
+    offset: Option<usize>,
+    contents: &[T],
+    usage: BufferUsage,
+) -> crate::Result<()> {
+    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, id);
+
+    if let Some(offset) = offset {
+        gl::BufferSubData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            offset.try_into().map_err(|_| {
+                ElementArrayErr(error_fmt!(ElementArrayBuffer, "Offset exceeds isize"))
+            })?,
+            byte_size(contents)?,
+            contents.as_ptr().cast(),
+        );
+    } else {
+        // Orphan the old storage rather than waiting for draws still reading it
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            byte_size(contents)?,
+            ptr::null(),
+            usage.gl_usage(),
+        );
+        gl::BufferSubData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            0,
+            byte_size(contents)?,
+            contents.as_ptr().cast(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Upload `contents` as the whole buffer, as 16 bit indices when they all fit
+unsafe fn upload_narrowest(
+    id: u32,
+    contents: &[u32],
+    usage: BufferUsage,
+    primitive_restart: bool,
+) -> crate::Result<IndexType> {
+    if let Some(narrowed) = narrow(contents, primitive_restart) {
+        upload(id, None, &narrowed, usage)?;
+        Ok(IndexType::U16)
+    } else {
+        upload(id, None, contents, usage)?;
+        Ok(IndexType::U32)
+    }
+}
+
 /// Tells OpenGL in which order the vertices of the VertexBuffer should be drawn.
-/// Internally (inside GPU memory) is an array of u32.
-/// Three u32 in a row form one triangle primitive.
+/// Internally (inside GPU memory) is an array of u16 if every index fits, u32 otherwise.
+/// How the indices form primitives is up to the `Primitive` of the owning vertex array.
+///
+/// Binding an element buffer changes the currently bound vertex array, so `update` and `replace`
+/// must only be called with the owning vertex array bound.
 #[derive(Clone, Debug)]
 pub struct ElementArrayBuffer(Rc<ElementArrayBufferInternal>);
 
@@ -25,29 +148,25 @@ impl ElementArrayBuffer {
     /// Create new `ElementArrayBuffer`. There is no builder pattern for this type.
     /// # Errors
     /// Returns error if the EAB object length exceeds an i32
-    pub(crate) fn new(contents: &[u32]) -> crate::Result<Self> {
-        let len = contents.len().try_into().map_err(|_| {
-            ElementArrayErr(error_fmt!(ElementArrayBuffer, "EAB length exceeds i32"))
-        })?;
+    pub(crate) fn new(
+        contents: &[u32],
+        usage: BufferUsage,
+        primitive_restart: bool,
+    ) -> crate::Result<Self> {
+        let len = element_count(contents)?;
 
-        let id = unsafe {
-            let mut id = 0;
+        let mut id = 0;
+        let index_type = unsafe {
             gl::GenBuffers(1, addr_of_mut!(id));
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, id);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                mem::size_of_val(contents).try_into().map_err(|_| {
-                    ElementArrayErr(error_fmt!(ElementArrayBuffer, "EAB size exceeds isize"))
-                })?,
-                contents.as_ptr().cast(),
-                gl::STATIC_DRAW,
-            );
-            id
+            upload_narrowest(id, contents, usage, primitive_restart)?
         };
 
         Ok(Self(Rc::new(ElementArrayBufferInternal {
             id,
-            len,
+            len: Cell::new(len),
+            index_type: Cell::new(index_type),
+            usage,
+            primitive_restart,
         })))
     }
 
@@ -59,6 +178,107 @@ impl ElementArrayBuffer {
     /// Get the length of the buffer. No `is_empty()` is needed.
     #[allow(clippy::len_without_is_empty)]
     pub(crate) fn len(&self) -> i32 {
-        self.0.len
+        self.0.len.get()
+    }
+
+    /// Bytes of storage used by the elements
+    pub(crate) fn memory(&self) -> usize {
+        self.len().unsigned_abs() as usize * self.index_type().size()
+    }
+
+    pub(crate) fn index_type(&self) -> IndexType {
+        self.0.index_type.get()
+    }
+
+    pub(crate) fn primitive_restart(&self) -> bool {
+        self.0.primitive_restart
+    }
+
+    /// Copy the elements back from the GPU, widened to `u32` with restarts as `RESTART_INDEX`
+    pub(crate) fn read(&self) -> Vec<u32> {
+        let len = self.0.len.get().unsigned_abs() as usize;
+
+        unsafe fn read_as<T: Copy + Default>(len: usize) -> Vec<T> {
+            let mut data = vec![T::default(); len];
+            gl::GetBufferSubData(
+                gl::COPY_READ_BUFFER,
+                0,
+                mem::size_of_val(data.as_slice())
+                    .try_into()
+                    .unwrap_or(isize::MAX),
+                data.as_mut_ptr().cast(),
+            );
+            data
+        }
+
+        unsafe {
+            // Unlike `ELEMENT_ARRAY_BUFFER`, binding here leaves the current vertex array alone
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.0.id);
+            let elements = match self.0.index_type.get() {
+                IndexType::U16 => read_as::<u16>(len)
+                    .into_iter()
+                    .map(|index| {
+                        if self.0.primitive_restart && index == u16::MAX {
+                            RESTART_INDEX
+                        } else {
+                            u32::from(index)
+                        }
+                    })
+                    .collect(),
+                IndexType::U32 => read_as::<u32>(len),
+            };
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+            elements
+        }
+    }
+
+    /// Overwrite elements in place, starting from element `first`
+    /// # Errors
+    /// Returns an error if the elements run past the end of the buffer, or no longer fit in a
+    /// 16 bit buffer
+    pub(crate) fn update(&self, first: usize, contents: &[u32]) -> crate::Result<()> {
+        let end = first.saturating_add(contents.len());
+        if end > self.0.len.get().unsigned_abs() as usize {
+            return Err(ElementArrayErr(error_fmt!(
+                ElementArrayBuffer,
+                "Update of elements {first}..{end} is outside the buffer of {}",
+                self.0.len.get()
+            )));
+        }
+
+        let index_type = self.0.index_type.get();
+        let offset = Some(first.saturating_mul(index_type.size()));
+
+        unsafe {
+            match index_type {
+                IndexType::U16 => {
+                    let narrowed =
+                        narrow(contents, self.0.primitive_restart).ok_or_else(|| {
+                            ElementArrayErr(error_fmt!(
+                                ElementArrayBuffer,
+                                "Elements do not fit in this 16 bit buffer, replace them instead"
+                            ))
+                        })?;
+                    upload(self.0.id, offset, &narrowed, self.0.usage)
+                }
+                IndexType::U32 => upload(self.0.id, offset, contents, self.0.usage),
+            }
+        }
+    }
+
+    /// Replace every element, orphaning the old storage so that draws still reading it are not
+    /// waited on. The number of elements, and whether they are stored as 16 bit, may change.
+    /// # Errors
+    /// Returns error if the EAB object length exceeds an i32
+    pub(crate) fn replace(&self, contents: &[u32]) -> crate::Result<()> {
+        let len = element_count(contents)?;
+
+        let index_type = unsafe {
+            upload_narrowest(self.0.id, contents, self.0.usage, self.0.primitive_restart)?
+        };
+
+        self.0.len.set(len);
+        self.0.index_type.set(index_type);
+        Ok(())
     }
 }
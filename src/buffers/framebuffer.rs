@@ -1,6 +1,6 @@
 use crate::{
-    error_fmt, material::Material, texture::Texture, EngineError, EngineError::FrameBufferErr,
-    Result,
+    error_fmt, gl_state, material::Material, texture::Texture, EngineError,
+    EngineError::FrameBufferErr, Result,
 };
 
 /// A `FrameBuffer` is a destination for drawing a scene, the default FrameBuffer is accessible
@@ -39,34 +39,60 @@ impl FrameBuffer {
 
     /// Bind the `FrameBuffer` for all subsequent draw calls.
     pub(crate) fn bind(&mut self) {
+        self.bind_no_clear();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+    }
+
+    /// Bind the `FrameBuffer` keeping its current contents, to draw on top of them.
+    pub(crate) fn bind_no_clear(&mut self) {
         unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
             match &self.stencilordepth {
                 StencilOrDepth::DefaultFrameBuffer => {
-                    gl::Enable(gl::DEPTH_TEST);
-                    gl::Enable(gl::STENCIL_TEST);
+                    gl_state::set_capability(gl::DEPTH_TEST, true);
+                    gl_state::set_capability(gl::STENCIL_TEST, true);
                 }
                 StencilOrDepth::DepthStencil(_) => {
-                    gl::Enable(gl::DEPTH_TEST);
-                    gl::Enable(gl::STENCIL_TEST);
+                    gl_state::set_capability(gl::DEPTH_TEST, true);
+                    gl_state::set_capability(gl::STENCIL_TEST, true);
                 }
                 StencilOrDepth::Depth(_) => {
-                    gl::Enable(gl::DEPTH_TEST);
-                    gl::Disable(gl::STENCIL_TEST);
+                    gl_state::set_capability(gl::DEPTH_TEST, true);
+                    gl_state::set_capability(gl::STENCIL_TEST, false);
                 }
                 StencilOrDepth::None => {
-                    gl::Disable(gl::DEPTH_TEST);
-                    gl::Disable(gl::STENCIL_TEST);
+                    gl_state::set_capability(gl::DEPTH_TEST, false);
+                    gl_state::set_capability(gl::STENCIL_TEST, false);
                 }
             }
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
         }
     }
 
+    /// Get the first colour attachment as a texture
+    /// # Errors
+    /// Returns an error for the default framebuffer
     #[inline]
     pub fn get_colour(&self) -> Result<Texture> {
+        self.get_colour_attachment(0)
+    }
+
+    /// Get colour attachment `index`, in the order they were added with `Builder::add_colour`
+    /// # Errors
+    /// Returns an error for the default framebuffer, or if there is no such attachment
+    #[inline]
+    pub fn get_colour_attachment(&self, index: usize) -> Result<Texture> {
         match &self.colour {
-            InternalBufferColourType::TexRgb(x) => Ok(x.clone()),
+            InternalBufferColourType::Textures(textures) => {
+                textures.get(index).cloned().ok_or_else(|| {
+                    FrameBufferErr(error_fmt!(
+                        frame_buffer::FrameBuffer,
+                        "No colour attachment {index}, there are {}",
+                        textures.len()
+                    ))
+                })
+            }
             InternalBufferColourType::DefaultRgb => Err(FrameBufferErr(error_fmt!(
                 frame_buffer::FrameBuffer,
                 "Cannot get default framebuffer as a texture"
@@ -92,16 +118,21 @@ impl Drop for FrameBuffer {
 #[derive(Debug)]
 pub enum BufferColourType {
     TexRgb,
+    /// 8 bit colour stored in sRGB. Writes are encoded and samples decoded, so shaders work in
+    /// linear space throughout.
+    TexSrgb,
+    /// Floating point colour for HDR rendering, values are not clamped to `[0.0, 1.0]`
+    TexRgba16F,
 }
 
 enum InternalBufferColourType {
     DefaultRgb,
-    TexRgb(Texture),
+    Textures(Vec<Texture>),
 }
 
 #[derive(Default, Debug)]
 pub struct Builder {
-    colour: Option<BufferColourType>,
+    colour: Vec<BufferColourType>,
     stencil: bool,
     depth: bool,
     width: i32,
@@ -109,9 +140,11 @@ pub struct Builder {
 }
 
 impl Builder {
+    /// Add a colour attachment. May be called repeatedly, the n-th call is written by
+    /// `layout (location = n)` in the fragment shader.
     #[inline]
     pub fn add_colour(mut self, colour: BufferColourType) -> Self {
-        self.colour = Some(colour);
+        self.colour.push(colour);
         self
     }
 
@@ -145,32 +178,77 @@ impl Builder {
             gl::BindFramebuffer(gl::FRAMEBUFFER, id);
         }
 
-        let colour_enum = self.colour.unwrap_or(BufferColourType::TexRgb);
-        let colour = match colour_enum {
-            BufferColourType::TexRgb => {
-                InternalBufferColourType::TexRgb(Texture::framebuffer_attachment(
+        let mut colour_types = self.colour;
+        if colour_types.is_empty() {
+            colour_types.push(BufferColourType::TexRgb);
+        }
+
+        let textures = colour_types
+            .into_iter()
+            .map(|colour_type| match colour_type {
+                BufferColourType::TexRgb => Texture::framebuffer_attachment(
                     gl::RGB as i32,
                     self.width,
                     self.height,
                     gl::RGB,
                     gl::UNSIGNED_BYTE,
-                ))
-            }
-        };
+                ),
+                BufferColourType::TexSrgb => Texture::framebuffer_attachment(
+                    gl::SRGB8_ALPHA8 as i32,
+                    self.width,
+                    self.height,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                ),
+                BufferColourType::TexRgba16F => Texture::framebuffer_attachment(
+                    gl::RGBA16F as i32,
+                    self.width,
+                    self.height,
+                    gl::RGBA,
+                    gl::FLOAT,
+                ),
+            })
+            .collect::<Vec<_>>();
 
-        match &colour {
-            InternalBufferColourType::TexRgb(texture) => unsafe {
+        let attachments = (0..textures.len())
+            .map(|index| {
+                u32::try_from(index)
+                    .map(|index| gl::COLOR_ATTACHMENT0 + index)
+                    .map_err(|_| {
+                        FrameBufferErr(error_fmt!(
+                            FrameBufferBuilder,
+                            "Too many colour attachments"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (texture, attachment) in textures.iter().zip(&attachments) {
+            unsafe {
                 gl::FramebufferTexture2D(
                     gl::FRAMEBUFFER,
-                    gl::COLOR_ATTACHMENT0,
+                    *attachment,
                     gl::TEXTURE_2D,
                     texture.id(),
                     0,
                 );
-            },
-            InternalBufferColourType::DefaultRgb => {}
+            }
+        }
+
+        unsafe {
+            gl::DrawBuffers(
+                attachments.len().try_into().map_err(|_| {
+                    FrameBufferErr(error_fmt!(
+                        FrameBufferBuilder,
+                        "Too many colour attachments"
+                    ))
+                })?,
+                attachments.as_ptr(),
+            );
         }
 
+        let colour = InternalBufferColourType::Textures(textures);
+
         // format, internal_format, type, attachment
         let stencilordepth = match (self.stencil, self.depth) {
             (true, true) => {
@@ -0,0 +1,165 @@
+use std::{cell::Cell, mem, ptr};
+
+use crate::{
+    buffers::{vertex_buffer::VertexBuffer, BufferUsage},
+    error_fmt,
+    linear_algebra::matrix::Matrix,
+    shader_program::ActiveAttribute,
+    EngineError::VertexBufferErr,
+    Result,
+};
+
+/// The per-instance data read by instanced shaders as `in_instance_model` and `in_instance_tint`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    /// Column-major, applied after the `Model`'s own transform
+    pub model: [f32; 16],
+    /// Multiplies the diffuse colour
+    pub tint: [f32; 4],
+}
+
+impl Instance {
+    #[must_use]
+    #[inline]
+    pub fn new(model: Matrix<4, 4>) -> Self {
+        Self::tinted(model, [1.0; 4])
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn tinted(model: Matrix<4, 4>, tint: [f32; 4]) -> Self {
+        Self {
+            model: model.col_major(),
+            tint,
+        }
+    }
+}
+
+/// A buffer of `Instance`s drawn together with `Draw::add_instanced`, so that every copy of a
+/// `Model` is rendered by a single draw call per mesh
+#[derive(Clone, Debug)]
+pub struct InstanceBuffer {
+    buffer: VertexBuffer,
+    count: Cell<i32>,
+}
+
+fn instance_count(instances: &[Instance]) -> Result<i32> {
+    instances.len().try_into().map_err(|_| {
+        VertexBufferErr(error_fmt!(InstanceBuffer, "Instance count exceeds i32"))
+    })
+}
+
+impl InstanceBuffer {
+    /// # Errors
+    /// Returns an error if there are more than `i32::MAX` instances
+    #[inline]
+    pub fn new(instances: &[Instance], usage: BufferUsage) -> Result<Self> {
+        Ok(Self {
+            count: Cell::new(instance_count(instances)?),
+            buffer: VertexBuffer::with_usage(instances, usage, 0)?,
+        })
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn count(&self) -> i32 {
+        self.count.get()
+    }
+
+    /// Overwrite instances starting from `first`, keeping the number of instances
+    /// # Errors
+    /// Returns an error if the instances run past the end of the buffer
+    #[inline]
+    pub fn update(&self, first: usize, instances: &[Instance]) -> Result<()> {
+        self.buffer
+            .update(first.saturating_mul(mem::size_of::<Instance>()), instances)
+    }
+
+    /// Replace every instance, the number drawn changes to match
+    /// # Errors
+    /// Returns an error if the instances do not fit in a ring buffer segment
+    #[inline]
+    pub fn replace(&self, instances: &[Instance]) -> Result<()> {
+        let count = instance_count(instances)?;
+        self.buffer.replace(instances)?;
+        self.count.set(count);
+        Ok(())
+    }
+
+    /// Where `inputs` read the instance attributes, as each location with the byte offset of
+    /// its column of `model` or of `tint` within an `Instance`. Inputs the shader leaves out are
+    /// skipped, so the locations are whichever the shader chose.
+    pub(crate) fn attributes(inputs: &[ActiveAttribute]) -> Vec<(u32, usize)> {
+        let column = mem::size_of::<[f32; 4]>();
+        inputs
+            .iter()
+            .flat_map(|input| {
+                let offsets = match input.name.as_str() {
+                    "in_instance_model" => (0..4).map(|index| index * column).collect(),
+                    "in_instance_tint" => vec![mem::offset_of!(Instance, tint)],
+                    _ => Vec::new(),
+                };
+                (input.location..).zip(offsets)
+            })
+            .collect()
+    }
+
+    /// Point `attributes` of the bound vertex array at this buffer, one step per instance
+    pub(crate) fn enable(&self, attributes: &[(u32, usize)]) {
+        let stride = mem::size_of::<Instance>() as i32;
+        let base = self.buffer.draw_offset();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer.id());
+
+            for &(location, offset) in attributes {
+                gl::VertexAttribPointer(
+                    location,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    ptr::null::<u8>().wrapping_add(base + offset).cast(),
+                );
+                gl::VertexAttribDivisor(location, 1);
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, location: u32, locations: u32) -> ActiveAttribute {
+        ActiveAttribute {
+            name: name.into(),
+            location,
+            components: 4,
+            locations,
+            integer: false,
+        }
+    }
+
+    #[test]
+    fn attributes_follow_the_shader() {
+        let inputs = [
+            input("in_position", 0, 1),
+            input("in_instance_model", 11, 4),
+            input("in_instance_tint", 15, 1),
+        ];
+        assert_eq!(
+            InstanceBuffer::attributes(&inputs),
+            [(11, 0), (12, 16), (13, 32), (14, 48), (15, 64)]
+        );
+
+        // A shader without a tint only gets the matrix
+        let inputs = [input("in_instance_model", 6, 4)];
+        assert_eq!(
+            InstanceBuffer::attributes(&inputs),
+            [(6, 0), (7, 16), (8, 32), (9, 48)]
+        );
+    }
+}
@@ -1,16 +1,81 @@
+pub mod processing;
+
 use crate::{
-    buffers::{element_array_buffer::ElementArrayBuffer, vertex_buffer::VertexBuffer}, error_fmt, linear_algebra::multizip, EngineError::VertexArrayErr, Result
+    buffers::{
+        element_array_buffer::ElementArrayBuffer,
+        instance_buffer::InstanceBuffer,
+        BufferUsage,
+        vertex_buffer::VertexBuffer,
+        vertex_format::{AttributeFormat, ComponentType, MeshVertex, Vertex},
+    },
+    error_fmt, gl_state,
+    linear_algebra::{multizip, vector::Vector},
+    modelling::bounds::BoundingBox,
+    shader_program::{ActiveAttribute, ShaderProgram},
+    EngineError::VertexArrayErr,
+    Result,
 };
 
-use std::{mem, ptr, rc::Rc, vec};
+use std::{cell::Cell, collections::HashMap, ffi::c_void, mem, mem::MaybeUninit, ptr, rc::Rc};
+
+/// Marks the end of a strip, fan or loop in the element buffer when `Builder::primitive_restart`
+/// is enabled, e.g. `[0, 1, 2, 3, RESTART_INDEX, 4, 5, 6, 7]` draws two separate strips
+pub const RESTART_INDEX: u32 = u32::MAX;
+
+/// How the elements of a vertex array are assembled into primitives
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Primitive {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    #[default]
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    /// Control points for a tessellation shader, `vertices` per patch. Requires OpenGL 4.0.
+    Patches { vertices: i32 },
+}
+
+impl Primitive {
+    pub(crate) const fn gl_mode(self) -> u32 {
+        match self {
+            Self::Points => gl::POINTS,
+            Self::Lines => gl::LINES,
+            Self::LineStrip => gl::LINE_STRIP,
+            Self::LineLoop => gl::LINE_LOOP,
+            Self::Triangles => gl::TRIANGLES,
+            Self::TriangleStrip => gl::TRIANGLE_STRIP,
+            Self::TriangleFan => gl::TRIANGLE_FAN,
+            Self::Patches { .. } => gl::PATCHES,
+        }
+    }
+}
+
+/// A run of elements drawn by a single command, as produced by `VertexArray::merge`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrawRange {
+    /// Index of the first element
+    pub first: usize,
+    /// Number of elements
+    pub count: i32,
+    /// Added to every element before it is looked up in the vertices
+    pub base_vertex: i32,
+}
 
 #[derive(Debug)]
 pub struct VertexArray {
     id: u32,
     vertex_buffer: VertexBuffer,
     element_buffer: ElementArrayBuffer,
+    primitive: Primitive,
     labels: Rc<Vec<String>>,
-    lengths: Rc<Vec<usize>>,
+    formats: Rc<Vec<AttributeFormat>>,
+    stride: usize,
+    /// The generation of the last program `validate` succeeded for, 0 if none
+    validated_for: Cell<u64>,
+    /// Around the "position" attribute when built, for culling
+    bounds: Option<BoundingBox>,
 }
 
 impl Drop for VertexArray {
@@ -19,6 +84,7 @@ impl Drop for VertexArray {
         unsafe {
             gl::DeleteVertexArrays(1, ptr::addr_of!(self.id));
         }
+        gl_state::forget_vertex_array(self.id);
     }
 }
 
@@ -29,46 +95,369 @@ impl VertexArray {
     }
 
     pub(crate) fn configure_strides(&self) -> crate::Result<()> {
-        let mut pointer = ptr::null::<f32>();
-        let vbo_stride = mem::size_of::<f32>() * self.lengths.iter().sum::<usize>();
+        let stride = self
+            .stride
+            .try_into()
+            .map_err(|_| VertexArrayErr(error_fmt!(VertexArray, "Stride exceeds i32")))?;
+
         unsafe {
-            gl::BindVertexArray(self.id);
-            for (index, length) in self.lengths.iter().enumerate() {
-                gl::VertexAttribPointer(
-                    index.try_into().map_err(|_| {
-                        VertexArrayErr(error_fmt!(VertexArray, "VBO length exceeds u32"))
-                    })?,
-                    (*length).try_into().map_err(|_| {
-                        VertexArrayErr(error_fmt!(VertexArray, "Attribute length exceeds i32"))
-                    })?,
-                    gl::FLOAT,
-                    gl::FALSE,
-                    vbo_stride.try_into().map_err(|_| {
-                        VertexArrayErr(error_fmt!(VertexArray, "Stride exceeds i32"))
-                    })?,
-                    pointer.cast(),
-                );
-                pointer = pointer.wrapping_add(*length);
+            gl_state::bind_vertex_array(self.id);
+            for format in self.formats.iter() {
+                point_attribute(format, stride);
+            }
+        }
+        Ok(())
+    }
 
-                gl::EnableVertexAttribArray(index.try_into().map_err(|_| {
-                    VertexArrayErr(error_fmt!(VertexArray, "VBO length exceeds u32"))
-                })?);
+    /// Put `locations` of the bound vertex array back as they were built after instanced
+    /// drawing: pointing at this vertex array's attribute if it has one there, otherwise
+    /// disabled to read the constant default
+    unsafe fn restore_attributes(&self, locations: impl Iterator<Item = u32>) {
+        let stride = self.stride.try_into().unwrap_or(i32::MAX);
+        for location in locations {
+            gl::VertexAttribDivisor(location, 0);
+            match self.formats.iter().find(|format| format.location == location) {
+                Some(format) => {
+                    gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer.id());
+                    point_attribute(format, stride);
+                }
+                None => gl::DisableVertexAttribArray(location),
             }
         }
+    }
+
+    /// Check every attribute read by `shader` against this vertex array. Each label is matched to
+    /// the shader input of the same name with an `in_` prefix, so "normal" feeds `in_normal`.
+    ///
+    /// An input at a location holding a differently named attribute, with fewer components than
+    /// the attribute or of a different base type, is an error. Components an input reads beyond
+    /// the attribute's are filled from `(0, 0, 0, 1)`, and inputs with no attribute at their
+    /// location read that constant whole. Attributes the shader ignores are allowed.
+    /// # Errors
+    /// Returns a `VertexArrayErr` describing the first mismatch
+    pub(crate) fn validate(&self, shader: &ShaderProgram) -> Result<()> {
+        if self.validated_for.get() == shader.generation() {
+            return Ok(());
+        }
+
+        check_inputs(&self.labels, &self.formats, shader.active_attributes())?;
+        self.validated_for.set(shader.generation());
         Ok(())
     }
 
-    pub(crate) fn draw(&self) {
+    fn check_stride<T>(&self, vertices: &[T]) -> Result<()> {
+        if mem::size_of_val(vertices).is_multiple_of(self.stride.max(1)) {
+            Ok(())
+        } else {
+            Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "{} bytes of vertex data is not a whole number of {} byte vertices",
+                mem::size_of_val(vertices),
+                self.stride
+            )))
+        }
+    }
+
+    /// Overwrite vertices starting from `first_vertex`, keeping the size of the buffer.
+    /// `vertices` must be laid out as the vertex array was built: the same `Vertex` type, or
+    /// interleaved `f32`s in attribute order. Shared with every clone of this vertex array.
+    /// # Errors
+    /// Returns an error if the vertices run past the end of the buffer or the buffer is a
+    /// `BufferUsage::Ring`
+    #[inline]
+    pub fn update_vertices<T: Copy>(&self, first_vertex: usize, vertices: &[T]) -> Result<()> {
+        self.check_stride(vertices)?;
+        self.vertex_buffer
+            .update(first_vertex.saturating_mul(self.stride), vertices)
+    }
+
+    /// Replace every vertex, the number of vertices may change. Best suited to
+    /// `BufferUsage::Stream` and `BufferUsage::Ring`, see `BufferUsage`. The elements are not
+    /// changed, replace those too if the number of vertices changes.
+    /// # Errors
+    /// Returns an error if the vertices are laid out differently or do not fit in a ring segment
+    #[inline]
+    pub fn replace_vertices<T: Copy>(&self, vertices: &[T]) -> Result<()> {
+        self.check_stride(vertices)?;
+        self.vertex_buffer.replace(vertices)
+    }
+
+    /// Overwrite elements starting from `first`, keeping the number of elements
+    /// # Errors
+    /// Returns an error if the elements run past the end of the buffer
+    #[inline]
+    pub fn update_elements(&self, first: usize, elements: &[u32]) -> Result<()> {
+        gl_state::bind_vertex_array(self.id);
+        let result = self.element_buffer.update(first, elements);
+        gl_state::bind_vertex_array(0);
+        result
+    }
+
+    /// Replace every element, the number of elements drawn changes to match
+    /// # Errors
+    #[inline]
+    pub fn replace_elements(&self, elements: &[u32]) -> Result<()> {
+        gl_state::bind_vertex_array(self.id);
+        let result = self.element_buffer.replace(elements);
+        gl_state::bind_vertex_array(0);
+        result
+    }
+
+    pub(crate) const fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Bytes of GPU memory used by the vertices and elements
+    #[must_use]
+    #[inline]
+    pub fn memory(&self) -> usize {
+        self.vertex_buffer.memory() + self.element_buffer.memory()
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn primitive(&self) -> Primitive {
+        self.primitive
+    }
+
+    /// Set up the per-draw state for this vertex array's primitive
+    unsafe fn begin_draw(&self) {
+        gl_state::bind_vertex_array(self.id);
+
+        if let Primitive::Patches { vertices } = self.primitive {
+            gl::PatchParameteri(gl::PATCH_VERTICES, vertices);
+        }
+
+        let primitive_restart = self.element_buffer.primitive_restart();
+        gl_state::set_capability(gl::PRIMITIVE_RESTART, primitive_restart);
+        if primitive_restart {
+            gl::PrimitiveRestartIndex(self.element_buffer.index_type().restart_index());
+        }
+    }
+
+    /// Ring buffers draw from whichever segment was written last
+    fn base_vertex(&self) -> i32 {
+        (self.vertex_buffer.draw_offset() / self.stride.max(1))
+            .try_into()
+            .unwrap_or(i32::MAX)
+    }
+
+    /// The box around the "position" attribute, `None` if there is no such attribute with at
+    /// least 3 `F32` components
+    #[must_use]
+    #[inline]
+    pub const fn bounding_box(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    /// Override the bounds used for culling, e.g. after replacing the vertices. `None` means the
+    /// vertex array is never culled.
+    #[inline]
+    pub fn set_bounding_box(&mut self, bounds: Option<BoundingBox>) {
+        self.bounds = bounds;
+    }
+
+    /// Every element, as drawn by `draw`
+    pub(crate) fn whole_range(&self) -> DrawRange {
+        DrawRange {
+            first: 0,
+            count: self.element_buffer.len(),
+            base_vertex: 0,
+        }
+    }
+
+    fn range_offset(&self, range: &DrawRange) -> *const c_void {
+        ptr::null::<u8>()
+            .wrapping_add(range.first * self.element_buffer.index_type().size())
+            .cast()
+    }
+
+    /// Whether `other` can be merged with this vertex array, having the same attributes, vertex
+    /// size, primitive and restart setting
+    #[must_use]
+    #[inline]
+    pub fn same_layout(&self, other: &Self) -> bool {
+        self.labels == other.labels
+            && self.formats == other.formats
+            && self.stride == other.stride
+            && self.primitive == other.primitive
+            && self.element_buffer.primitive_restart() == other.element_buffer.primitive_restart()
+    }
+
+    /// Copy `arrays` into a single vertex array with `BufferUsage::Static` buffers, returning
+    /// where each of them ended up in order. Elements are kept relative to their own vertices
+    /// and offset by each range's `base_vertex`, so they stay 16 bit where they were before.
+    ///
+    /// Draw the result with `Mesh::with_ranges`, meshes given several ranges draw them all
+    /// with one command.
+    /// # Errors
+    /// Returns an error if `arrays` is empty, their layouts differ, or the merged buffers are
+    /// too large
+    #[inline]
+    pub fn merge(arrays: &[&Self]) -> Result<(Self, Vec<DrawRange>)> {
+        let Some(first) = arrays.first() else {
+            return Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "Cannot merge an empty list of vertex arrays"
+            )));
+        };
+
+        if arrays.iter().any(|array| !array.same_layout(first)) {
+            return Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "Cannot merge vertex arrays with different layouts"
+            )));
+        }
+
+        let stride = first.stride.max(1);
+        let mut vertices = Vec::new();
+        let mut elements = Vec::new();
+        let mut sizes = Vec::with_capacity(arrays.len());
+        let bounds = arrays
+            .iter()
+            .map(|array| array.bounds)
+            .reduce(|a, b| a.zip(b).map(|(a, b)| a.union(&b)))
+            .flatten();
+
+        for array in arrays {
+            let mut array_vertices = array.vertex_buffer.read();
+            // Trailing capacity holds no whole vertex
+            array_vertices.truncate(array_vertices.len() / stride * stride);
+            let array_elements = array.element_buffer.read();
+
+            sizes.push((array_vertices.len() / stride, array_elements.len()));
+            vertices.extend(array_vertices);
+            elements.extend(array_elements);
+        }
+        let ranges = merged_ranges(&sizes)?;
+
+        let id = unsafe {
+            let mut id = 0;
+            gl::GenVertexArrays(1, &mut id);
+            gl_state::bind_vertex_array(id);
+            id
+        };
+
+        let output = Self {
+            id,
+            vertex_buffer: VertexBuffer::with_usage(&vertices, BufferUsage::Static, 0)?,
+            element_buffer: ElementArrayBuffer::new(
+                &elements,
+                BufferUsage::Static,
+                first.element_buffer.primitive_restart(),
+            )?,
+            primitive: first.primitive,
+            labels: first.labels.clone(),
+            formats: first.formats.clone(),
+            stride: first.stride,
+            validated_for: Cell::new(0),
+            bounds,
+        };
+
+        let result = output.configure_strides();
+
+        unsafe {
+            gl_state::bind_vertex_array(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        result.map(|()| (output, ranges))
+    }
+
+    /// Check that `ranges` lie within the elements
+    /// # Errors
+    /// Returns an error naming the first range that does not
+    pub(crate) fn check_ranges(&self, ranges: &[DrawRange]) -> Result<()> {
+        let len = self.element_buffer.len().unsigned_abs() as usize;
+
+        ranges
+            .iter()
+            .find(|range| {
+                range.count < 0
+                    || range.first.saturating_add(range.count.unsigned_abs() as usize) > len
+            })
+            .map_or(Ok(()), |range| {
+                Err(VertexArrayErr(error_fmt!(
+                    VertexArray,
+                    "{range:?} is outside the {len} elements"
+                )))
+            })
+    }
+
+    /// Draw every instance in `instances` in a single call per range, with the instance
+    /// attributes wherever `shader` reads them
+    pub(crate) fn draw_instanced(
+        &self,
+        shader: &ShaderProgram,
+        instances: &InstanceBuffer,
+        ranges: &[DrawRange],
+    ) {
+        let base_vertex = self.base_vertex();
+        let attributes = InstanceBuffer::attributes(shader.active_attributes());
+
         unsafe {
-            gl::BindVertexArray(self.id);
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.element_buffer.len(),
-                gl::UNSIGNED_INT,
-                ptr::null(),
+            self.begin_draw();
+            instances.enable(&attributes);
+            for range in ranges {
+                gl::DrawElementsInstancedBaseVertex(
+                    self.primitive.gl_mode(),
+                    range.count,
+                    self.element_buffer.index_type().gl_type(),
+                    self.range_offset(range),
+                    instances.count(),
+                    base_vertex + range.base_vertex,
+                );
+            }
+            self.restore_attributes(attributes.iter().map(|&(location, _)| location));
+        }
+    }
+
+    /// Draw several ranges of elements with a single command
+    pub(crate) fn draw_ranges(&self, ranges: &[DrawRange]) {
+        let base_vertex = self.base_vertex();
+        let counts: Vec<i32> = ranges.iter().map(|range| range.count).collect();
+        let offsets: Vec<*const c_void> =
+            ranges.iter().map(|range| self.range_offset(range)).collect();
+        let base_vertices: Vec<i32> = ranges
+            .iter()
+            .map(|range| base_vertex + range.base_vertex)
+            .collect();
+
+        unsafe {
+            self.begin_draw();
+            gl::MultiDrawElementsBaseVertex(
+                self.primitive.gl_mode(),
+                counts.as_ptr(),
+                self.element_buffer.index_type().gl_type(),
+                offsets.as_ptr(),
+                counts.len().try_into().unwrap_or(i32::MAX),
+                base_vertices.as_ptr(),
             );
         }
     }
+
+    pub(crate) fn draw(&self) {
+        let base_vertex = self.base_vertex();
+
+        let mode = self.primitive.gl_mode();
+        let index_type = self.element_buffer.index_type().gl_type();
+
+        unsafe {
+            self.begin_draw();
+            if base_vertex == 0 {
+                gl::DrawElements(mode, self.element_buffer.len(), index_type, ptr::null());
+            } else {
+                gl::DrawElementsBaseVertex(
+                    mode,
+                    self.element_buffer.len(),
+                    index_type,
+                    ptr::null(),
+                    base_vertex,
+                );
+            }
+        }
+    }
 }
 
 impl Clone for VertexArray {
@@ -77,7 +466,7 @@ impl Clone for VertexArray {
         let vao_id = unsafe {
             let mut vao_id = 0;
             gl::GenVertexArrays(1, ptr::addr_of_mut!(vao_id));
-            gl::BindVertexArray(vao_id);
+            gl_state::bind_vertex_array(vao_id);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer.id());
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.element_buffer.id());
             vao_id
@@ -87,8 +476,12 @@ impl Clone for VertexArray {
             id: vao_id,
             vertex_buffer: self.vertex_buffer.clone(),
             element_buffer: self.element_buffer.clone(),
+            primitive: self.primitive,
             labels: self.labels.clone(),
-            lengths: self.lengths.clone(),
+            formats: self.formats.clone(),
+            stride: self.stride,
+            validated_for: Cell::new(0),
+            bounds: self.bounds,
         };
 
         output
@@ -96,7 +489,7 @@ impl Clone for VertexArray {
             .expect("This has already run once, and should be correct");
 
         unsafe {
-            gl::BindVertexArray(0);
+            gl_state::bind_vertex_array(0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
@@ -105,13 +498,49 @@ impl Clone for VertexArray {
     }
 }
 
+/// Interleaved vertex data from `Builder::vertices`, copied byte for byte including padding
+struct TypedVertices {
+    data: Vec<MaybeUninit<u8>>,
+    count: usize,
+    stride: usize,
+    attributes: &'static [(&'static str, AttributeFormat)],
+}
+
+impl TypedVertices {
+    /// The box around a "position" field of at least 3 `F32`s
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (_, format) = self.attributes.iter().find(|(name, format)| {
+            *name == "position"
+                && format.component_type == ComponentType::F32
+                && format.components >= 3
+        })?;
+
+        BoundingBox::from_points((0..self.count).map(|vertex| {
+            let start = vertex * self.stride + format.offset;
+            Vector::new(unsafe {
+                // Within the vertex as checked by `build`, and a field rather than padding
+                ptr::read_unaligned(self.data[start..].as_ptr().cast::<[f32; 3]>())
+            })
+        }))
+    }
+}
+
 #[derive(Default)]
 pub struct Builder {
     vb_content: Vec<(String, Vec<Vec<f32>>, usize)>,
+    typed: Option<TypedVertices>,
     eb_content: Vec<u32>,
+    primitive: Primitive,
+    primitive_restart: bool,
+    usage: BufferUsage,
+    vertex_capacity: usize,
 }
 
 impl Builder {
+    /// Add an attribute at the next location, starting from 0. `name` must match the vertex
+    /// shader input without its `in_` prefix, e.g. "position" for `in_position`.
+    /// # Errors
+    /// Returns an error if `attrib_array` is empty or its vectors differ in length
     #[inline]
     pub fn attribute(
         mut self,
@@ -148,26 +577,215 @@ impl Builder {
         Ok(self)
     }
 
+    /// Use `vertices` as the whole vertex buffer, laid out as described by their `Vertex`
+    /// implementation. Field names are matched to shader inputs as for `attribute`. Cannot be
+    /// combined with `attribute`.
+    #[must_use]
+    #[inline]
+    pub fn vertices<V: Vertex>(mut self, vertices: &[V]) -> Self {
+        let size = mem::size_of_val(vertices);
+        let mut data = Vec::<MaybeUninit<u8>>::with_capacity(size);
+        unsafe {
+            // `MaybeUninit` bytes may hold the struct's padding
+            ptr::copy_nonoverlapping(vertices.as_ptr().cast(), data.as_mut_ptr(), size);
+            data.set_len(size);
+        }
+
+        self.typed = Some(TypedVertices {
+            data,
+            count: vertices.len(),
+            stride: mem::size_of::<V>(),
+            attributes: V::ATTRIBUTES,
+        });
+        self
+    }
+
+    fn attribute_data(&self, name: &str, min_length: usize) -> Result<&Vec<Vec<f32>>> {
+        let (_, data, length) = self
+            .vb_content
+            .iter()
+            .find(|(label, ..)| label == name)
+            .ok_or_else(|| {
+                VertexArrayErr(error_fmt!(
+                    vertex_array::Builder,
+                    "No attribute {name}"
+                ))
+            })?;
+
+        if *length < min_length {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Attribute {name} has {length} components, at least {min_length} are needed"
+            )));
+        }
+
+        Ok(data)
+    }
+
+    /// Add a 4 component "tangent" attribute computed from the named position, texture
+    /// coordinate and normal attributes, which must already have been added.
+    ///
+    /// The tangents match MikkTSpace, which most tools bake normal maps against. Each vertex gets
+    /// the average tangent of the triangles around it, projected onto its normal and weighted by
+    /// the angle at its corner. Only triangles whose texture is mirrored the same way are
+    /// averaged, so a vertex on a mirrored seam is copied with every attribute, one copy for
+    /// each side. `w` holds the handedness so that `bitangent = w * cross(normal, tangent)`.
+    /// Call after `element_buffer` for indexed meshes.
+    /// # Errors
+    /// Returns an error if an attribute is missing or too short, an index is out of range, or
+    /// the primitive is not `Primitive::Triangles`
+    #[inline]
+    pub fn generate_tangents(
+        mut self,
+        position: &str,
+        texture_coord: &str,
+        normal: &str,
+    ) -> Result<Self> {
+        let indices = self.triangle_indices("generate tangents")?;
+        let positions = self.attribute_data(position, 3)?;
+        let texture_coords = self.attribute_data(texture_coord, 2)?;
+        let normals = self.attribute_data(normal, 3)?;
+
+        let num_vertices = positions.len();
+        if texture_coords.len() != num_vertices || normals.len() != num_vertices {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Cannot generate tangents, not all attributes are the same length"
+            )));
+        }
+
+        let as_array = |vector: &Vec<f32>| [vector[0], vector[1], vector[2]];
+        let generated = tangents(
+            &positions.iter().map(as_array).collect::<Vec<_>>(),
+            &texture_coords
+                .iter()
+                .map(|coord| [coord[0], coord[1]])
+                .collect::<Vec<_>>(),
+            &normals.iter().map(as_array).collect::<Vec<_>>(),
+            &indices,
+        );
+
+        if generated.sources.len() > num_vertices {
+            self.reorder_vertices(&generated.sources);
+            self.eb_content = generated
+                .indices
+                .iter()
+                .map(|&index| index.try_into().unwrap_or(u32::MAX))
+                .collect();
+        }
+
+        let tangent_data = generated
+            .tangents
+            .into_iter()
+            .map(|tangent| tangent.to_vec())
+            .collect();
+        self.attribute("tangent".into(), tangent_data)
+    }
+
+    /// How often the vertices and elements will be changed after building, `Static` by default
+    #[must_use]
+    #[inline]
+    pub const fn usage(mut self, usage: BufferUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Allocate room for at least `vertex_capacity` vertices, so that more than the initial
+    /// vertices can be written later. For `BufferUsage::Ring` this is the size of each segment.
+    #[must_use]
+    #[inline]
+    pub const fn vertex_capacity(mut self, vertex_capacity: usize) -> Self {
+        self.vertex_capacity = vertex_capacity;
+        self
+    }
+
+    /// How the elements are assembled into primitives, `Primitive::Triangles` by default
+    #[must_use]
+    #[inline]
+    pub const fn primitive(mut self, primitive: Primitive) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    /// Treat `RESTART_INDEX` in the elements as the end of one strip, fan or loop and the start
+    /// of the next, so several can be drawn at once
+    #[must_use]
+    #[inline]
+    pub const fn primitive_restart(mut self, primitive_restart: bool) -> Self {
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    /// Indices into the vertices, in the order they are drawn. Stored as 16 bit when they all
+    /// fit. Defaults to every vertex in order.
     #[inline]
     pub fn element_buffer(mut self, buffer: Vec<u32>) -> Self {
         self.eb_content = buffer;
         self
     }
 
+    /// Bytes that `build` will upload, roughly
+    pub(crate) fn upload_size(&self) -> usize {
+        let vertices = self.typed.as_ref().map_or_else(
+            || {
+                self.vb_content
+                    .iter()
+                    .flat_map(|(_, attribute, _)| attribute)
+                    .map(|vector| mem::size_of_val(vector.as_slice()))
+                    .sum()
+            },
+            |typed| typed.data.len(),
+        );
+        vertices + mem::size_of_val(self.eb_content.as_slice())
+    }
+
     /// # Errors
     #[inline]
     pub fn build(self) -> Result<VertexArray> {
-        let num_vertices = self
-            .vb_content
-            .first()
-            .ok_or_else(|| {
-                VertexArrayErr(error_fmt!(
+        if self.typed.is_some() && !self.vb_content.is_empty() {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Build error, typed vertices cannot be combined with separate attributes"
+            )));
+        }
+
+        if let Primitive::Patches { vertices } = self.primitive {
+            if vertices < 1 {
+                return Err(VertexArrayErr(error_fmt!(
                     vertex_array::Builder,
-                    "No attributes in Vertex Array"
-                ))
-            })?
-            .1
-            .len();
+                    "Build error, patches need at least one vertex, not {vertices}"
+                )));
+            }
+        }
+
+        if let Some(typed) = &self.typed {
+            if let Some((name, _)) = typed.attributes.iter().find(|(_, format)| {
+                !(1..=4).contains(&format.components)
+                    || format.offset
+                        + format.component_type.size() * format.components.unsigned_abs() as usize
+                        > typed.stride
+            }) {
+                return Err(VertexArrayErr(error_fmt!(
+                    vertex_array::Builder,
+                    "Attribute {name} does not fit within its vertex"
+                )));
+            }
+        }
+
+        let num_vertices = match &self.typed {
+            Some(typed) => typed.count,
+            None => self
+                .vb_content
+                .first()
+                .ok_or_else(|| {
+                    VertexArrayErr(error_fmt!(
+                        vertex_array::Builder,
+                        "No attributes in Vertex Array"
+                    ))
+                })?
+                .1
+                .len(),
+        };
 
         if !self.vb_content.iter().all(|x| x.1.len() == num_vertices) {
             return Err(VertexArrayErr(error_fmt!(
@@ -176,43 +794,88 @@ impl Builder {
             )));
         }
 
+        let bounds = self.typed.as_ref().map_or_else(
+            || self.bounding_box("position").ok(),
+            TypedVertices::bounding_box,
+        );
+
         let id = unsafe {
             let mut id = 0;
             gl::GenVertexArrays(1, &mut id);
-            gl::BindVertexArray(id);
+            gl_state::bind_vertex_array(id);
             id
         };
 
-        // Unzip
-        let mut vbo_labels = Vec::with_capacity(self.vb_content.len());
-        let mut vbo_attrib = Vec::with_capacity(self.vb_content.len());
-        let mut vbo_length = Vec::with_capacity(self.vb_content.len());
+        let (vertex_buffer, vbo_labels, vbo_formats, stride) = match self.typed {
+            Some(typed) => (
+                VertexBuffer::with_usage(
+                    &typed.data,
+                    self.usage,
+                    self.vertex_capacity.saturating_mul(typed.stride),
+                )?,
+                typed
+                    .attributes
+                    .iter()
+                    .map(|(name, _)| (*name).to_owned())
+                    .collect(),
+                typed.attributes.iter().map(|(_, format)| *format).collect(),
+                typed.stride,
+            ),
+            None => {
+                // Unzip
+                let mut vbo_labels = Vec::with_capacity(self.vb_content.len());
+                let mut vbo_attrib = Vec::with_capacity(self.vb_content.len());
+                let mut vbo_formats = Vec::with_capacity(self.vb_content.len());
 
-        for (label, attrib, length) in self.vb_content {
-            vbo_labels.push(label);
-            vbo_attrib.push(attrib);
-            vbo_length.push(length);
-        }
+                // Sequential locations, tightly packed as f32
+                let mut offset = 0;
+                for (location, (label, attrib, length)) in (0..).zip(self.vb_content) {
+                    vbo_labels.push(label);
+                    vbo_attrib.push(attrib);
+                    vbo_formats.push(AttributeFormat {
+                        location,
+                        components: length.try_into().map_err(|_| {
+                            VertexArrayErr(error_fmt!(
+                                vertex_array::Builder,
+                                "Attribute length exceeds i32"
+                            ))
+                        })?,
+                        component_type: ComponentType::F32,
+                        offset,
+                    });
+                    offset += length * mem::size_of::<f32>();
+                }
 
-        let vbo_data = {
-            let mut vbo_data = Vec::new();
-            let text = vbo_attrib
-                .into_iter()
-                .map(|x| x.into_iter())
-                .collect::<Vec<_>>();
+                let vbo_data = {
+                    let mut vbo_data = Vec::new();
+                    let text = vbo_attrib
+                        .into_iter()
+                        .map(|x| x.into_iter())
+                        .collect::<Vec<_>>();
 
-            let mz = multizip::Multizip(text);
+                    let mz = multizip::Multizip(text);
 
-            for vertex in mz {
-                for attribute in vertex {
-                    vbo_data.extend(attribute.into_iter());
-                }
-            }
+                    for vertex in mz {
+                        for attribute in vertex {
+                            vbo_data.extend(attribute.into_iter());
+                        }
+                    }
 
-            vbo_data
-        };
+                    vbo_data
+                };
 
-        let vertex_buffer = VertexBuffer::new(&vbo_data)?;
+                (
+                    VertexBuffer::with_usage(
+                        &vbo_data,
+                        self.usage,
+                        self.vertex_capacity.saturating_mul(offset),
+                    )?,
+                    vbo_labels,
+                    vbo_formats,
+                    offset,
+                )
+            }
+        };
 
         let element_buffer = {
             let ebo_data = if self.eb_content.is_empty() {
@@ -221,21 +884,25 @@ impl Builder {
                 self.eb_content
             };
 
-            ElementArrayBuffer::new(&ebo_data)
+            ElementArrayBuffer::new(&ebo_data, self.usage, self.primitive_restart)
         }?;
 
         let output = VertexArray {
             id,
             vertex_buffer,
             element_buffer, // do with sharing buffer data, probably a lot of rubbish
+            primitive: self.primitive,
             labels: Rc::new(vbo_labels),
-            lengths: Rc::new(vbo_length),
+            formats: Rc::new(vbo_formats),
+            stride,
+            validated_for: Cell::new(0),
+            bounds,
         };
 
         let _ = output.configure_strides();
 
         unsafe {
-            gl::BindVertexArray(0);
+            gl_state::bind_vertex_array(0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
@@ -244,52 +911,552 @@ impl Builder {
     }
 }
 
+/// Point the attribute at `format.location` of the bound vertex array into the bound
+/// `ARRAY_BUFFER`, and enable it
+unsafe fn point_attribute(format: &AttributeFormat, stride: i32) {
+    let pointer = ptr::null::<u8>().wrapping_add(format.offset).cast();
+    let component_type = format.component_type;
+
+    if component_type.is_integer() {
+        gl::VertexAttribIPointer(
+            format.location,
+            format.components,
+            component_type.gl_type(),
+            stride,
+            pointer,
+        );
+    } else {
+        gl::VertexAttribPointer(
+            format.location,
+            format.components,
+            component_type.gl_type(),
+            if component_type.is_normalised() {
+                gl::TRUE
+            } else {
+                gl::FALSE
+            },
+            stride,
+            pointer,
+        );
+    }
+
+    gl::EnableVertexAttribArray(format.location);
+}
+
+/// The checks of `VertexArray::validate`, for attributes `labels` laid out as `formats`. A
+/// matrix input is checked against every location its columns use.
+fn check_inputs(
+    labels: &[String],
+    formats: &[AttributeFormat],
+    inputs: &[ActiveAttribute],
+) -> Result<()> {
+    for input in inputs {
+        let used = input.location..input.location.saturating_add(input.locations);
+        let Some((label, format)) = labels
+            .iter()
+            .zip(formats)
+            .find(|(_, format)| used.contains(&format.location))
+        else {
+            continue;
+        };
+
+        if input.name.strip_prefix("in_") != Some(label.as_str()) || input.locations > 1 {
+            return Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "Location {} holds attribute \"{label}\" but the shader reads {} there",
+                format.location,
+                input.name
+            )));
+        }
+
+        if format.components > input.components {
+            return Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "Attribute \"{label}\" has {} components but {} only reads {}",
+                format.components,
+                input.name,
+                input.components
+            )));
+        }
+
+        if format.component_type.is_integer() != input.integer {
+            return Err(VertexArrayErr(error_fmt!(
+                VertexArray,
+                "Attribute \"{label}\" and {} disagree on being integer or floating point",
+                input.name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Where arrays of the given vertex and element counts end up once merged one after another
+fn merged_ranges(sizes: &[(usize, usize)]) -> Result<Vec<DrawRange>> {
+    let (mut first, mut vertices) = (0, 0);
+    sizes
+        .iter()
+        .map(|&(vertex_count, element_count)| {
+            let range = DrawRange {
+                first,
+                count: element_count.try_into().map_err(|_| {
+                    VertexArrayErr(error_fmt!(VertexArray, "Element count exceeds i32"))
+                })?,
+                base_vertex: vertices.try_into().map_err(|_| {
+                    VertexArrayErr(error_fmt!(VertexArray, "Vertex count exceeds i32"))
+                })?,
+            };
+            first += element_count;
+            vertices += vertex_count;
+            Ok(range)
+        })
+        .collect()
+}
+
+/// Tangents with handedness in `w`, as described for `Builder::generate_tangents`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Tangents {
+    /// One per vertex, including the copies made to split mirrored seams
+    pub tangents: Vec<[f32; 4]>,
+    /// The vertex each vertex copies, every vertex in order followed by the copies
+    pub sources: Vec<usize>,
+    /// The triangles, pointing at the copies where a vertex was split
+    pub indices: Vec<usize>,
+}
+
+/// MikkTSpace tangents, as described for `Builder::generate_tangents`. The attributes must be
+/// the same length and `indices` whole triangles within them.
+pub(crate) fn tangents(
+    positions: &[[f32; 3]],
+    texture_coords: &[[f32; 2]],
+    normals: &[[f32; 3]],
+    indices: &[usize],
+) -> Tangents {
+    let point = |index: usize| Vector::new(positions[index]);
+    let normal = |index: usize| {
+        let normal = Vector::new(normals[index]);
+        if normal.is_zero() {
+            Vector::new([0.0, 0.0, 1.0])
+        } else {
+            normal.normalize()
+        }
+    };
+    // Without their component along the normal, so that only the tangent plane is compared
+    let flatten = |vector: Vector<3>, normal: &Vector<3>| {
+        let flat = vector - normal.scale(normal.dot(&vector));
+        if flat.is_zero() {
+            flat
+        } else {
+            flat.normalize()
+        }
+    };
+
+    // Vertices with identical attributes are one vertex to MikkTSpace, whatever their index
+    let mut identities = HashMap::new();
+    let identity: Vec<usize> = (0..positions.len())
+        .map(|index| {
+            let [x, y, z] = positions[index];
+            let [u, v] = texture_coords[index];
+            let [i, j, k] = normals[index];
+            let bits = [x, y, z, u, v, i, j, k].map(f32::to_bits);
+            *identities.entry(bits).or_insert(index)
+        })
+        .collect();
+
+    // The direction `u` increases in across each triangle, and whether its texture is mirrored.
+    // Triangles with no area in texture space have no orientation of their own.
+    let faces: Vec<(Vector<3>, Option<bool>)> = indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+            let [du_1, dv_1] = [0, 1].map(|i| texture_coords[b][i] - texture_coords[a][i]);
+            let [du_2, dv_2] = [0, 1].map(|i| texture_coords[c][i] - texture_coords[a][i]);
+
+            let area = du_1.mul_add(dv_2, -(dv_1 * du_2));
+            let tangent = (point(b) - point(a)).scale(dv_2) - (point(c) - point(a)).scale(dv_1);
+            let preserving = area > 0.0;
+            let tangent = if preserving { tangent } else { tangent.scale(-1.0) };
+            (tangent, (area.abs() > f32::MIN_POSITIVE).then_some(preserving))
+        })
+        .collect();
+
+    // Corners only share a tangent with corners of the same vertex and orientation, so mirrored
+    // seams keep both sides apart. Each adds its triangle's tangent weighted by its angle.
+    let mut sums: HashMap<(usize, bool), Vector<3>> = HashMap::new();
+    for (triangle, &(tangent, orientation)) in indices.chunks_exact(3).zip(&faces) {
+        let Some(preserving) = orientation else {
+            continue;
+        };
+        for (corner, previous, next) in [
+            (triangle[0], triangle[2], triangle[1]),
+            (triangle[1], triangle[0], triangle[2]),
+            (triangle[2], triangle[1], triangle[0]),
+        ] {
+            let normal = normal(corner);
+            let to_previous = flatten(point(previous) - point(corner), &normal);
+            let to_next = flatten(point(next) - point(corner), &normal);
+            let sum = sums
+                .entry((identity[corner], preserving))
+                .or_insert_with(Vector::new_zero);
+            if to_previous.is_zero() || to_next.is_zero() {
+                continue;
+            }
+
+            let angle = to_previous.dot(&to_next).clamp(-1.0, 1.0).acos();
+            *sum = *sum + flatten(tangent, &normal).scale(angle);
+        }
+    }
+
+    // A vertex used with both orientations is copied, the copy taking the second
+    let mut sources: Vec<usize> = (0..positions.len()).collect();
+    let mut orientations = vec![None; positions.len()];
+    let mut copies = HashMap::new();
+    let indices = indices
+        .iter()
+        .enumerate()
+        .map(|(corner, &index)| {
+            // Corners of triangles without an orientation join whichever group is there
+            let preserving = faces[corner / 3].1.unwrap_or_else(|| {
+                sums.contains_key(&(identity[index], true))
+                    || !sums.contains_key(&(identity[index], false))
+            });
+            match orientations[index] {
+                None => {
+                    orientations[index] = Some(preserving);
+                    index
+                }
+                Some(first) if first == preserving => index,
+                Some(_) => *copies.entry(index).or_insert_with(|| {
+                    sources.push(index);
+                    orientations.push(Some(preserving));
+                    sources.len() - 1
+                }),
+            }
+        })
+        .collect();
+
+    let tangents = sources
+        .iter()
+        .zip(&orientations)
+        .map(|(&source, orientation)| {
+            let preserving = orientation.unwrap_or(true);
+            let normal = normal(source);
+            let sum = sums
+                .get(&(identity[source], preserving))
+                .copied()
+                .unwrap_or_else(Vector::new_zero);
+
+            // Any perpendicular will do for unmapped vertices
+            let tangent = if sum.is_zero() {
+                let axis = if normal[0].abs() < 0.9 {
+                    [1.0, 0.0, 0.0]
+                } else {
+                    [0.0, 1.0, 0.0]
+                };
+                normal.cross(axis.into()).normalize()
+            } else {
+                sum.normalize()
+            };
+            let handedness = if preserving { 1.0 } else { -1.0 };
+
+            [tangent[0], tangent[1], tangent[2], handedness]
+        })
+        .collect();
+
+    Tangents {
+        tangents,
+        sources,
+        indices,
+    }
+}
+
 impl VertexArray {
-    pub fn cube(side_length: f32) -> Result<Vec<Self>> {  
-        let vertices: Vec<Vec<f32>> = vec![
-               // x    y    z
-            vec![0.0, 0.0, 0.0],
-            vec![0.0, 0.0, 1.0],
-            vec![0.0, 1.0, 0.0],
-            vec![0.0, 1.0, 1.0],
-            vec![1.0, 0.0, 0.0],
-            vec![1.0, 0.0, 1.0],
-            vec![1.0, 1.0, 0.0],
-            vec![1.0, 1.0, 1.0f32],
-        ]
-        .into_iter().map(|face| face.into_iter().map(|elem| (elem - 0.5) * side_length).collect()).collect();
-
-        #[rustfmt::ignore]
-        let face_pos_normals = vec![
-            (vec![5, 4, 6, 7], vec![1.0, 0.0, 0.0]    ),
-            (vec![0, 1, 3, 2], vec![-1.0, 0.0, 0.0]   ),
-            (vec![2, 3, 7, 6], vec![0.0, 1.0, 0.0]    ),
-            (vec![1, 0, 4, 5], vec![0.0, -1.0, 0.0]   ),
-            (vec![1, 5, 7, 3], vec![0.0, 0.0, 1.0]    ),
-            (vec![4, 0, 2, 6], vec![0.0, 0.0, -1.0f32]),
+    /// All six faces of a cube centred on the origin, each with its own normals and texture
+    /// coordinates
+    /// # Errors
+    pub fn cube(side_length: f32) -> Result<Self> {
+        let corner = |index: usize| {
+            [index & 4, index & 2, index & 1].map(|bit| {
+                let unit = if bit == 0 { -0.5 } else { 0.5 };
+                unit * side_length
+            })
+        };
+
+        #[rustfmt::skip]
+        let faces = [
+            ([5, 4, 6, 7], [1.0, 0.0, 0.0] ),
+            ([0, 1, 3, 2], [-1.0, 0.0, 0.0]),
+            ([2, 3, 7, 6], [0.0, 1.0, 0.0] ),
+            ([1, 0, 4, 5], [0.0, -1.0, 0.0]),
+            ([1, 5, 7, 3], [0.0, 0.0, 1.0] ),
+            ([4, 0, 2, 6], [0.0, 0.0, -1.0]),
         ];
+        let texture_coords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
 
-        face_pos_normals
-            .into_iter()
-            .map(|(face, normal)| {
-                VertexArray::builder()
-                    .attribute(
-                        "location".into(),
-                        face.iter().copied().map(|index| vertices[index].clone()).collect(),
-                    )?
-                    .attribute(
-                        "texture".into(),
-                        vec![
-                            vec![0.0, 0.0],
-                            vec![1.0, 0.0],
-                            vec![1.0, 1.0],
-                            vec![0.0, 1.0],
-                        ],
-                    )?
-                    .attribute("normal".into(), vec::from_elem(normal.to_vec(), 4))?
-                    .element_buffer(vec![0, 1, 2, 0, 2, 3])
-                    .build()
+        let mut vertices = Vec::with_capacity(24);
+        let mut elements = Vec::with_capacity(36);
+
+        for (first, (face, normal)) in (0..).step_by(4).zip(faces) {
+            vertices.extend(
+                face.into_iter()
+                    .zip(texture_coords)
+                    .map(|(index, coord)| MeshVertex::new(corner(index), coord, normal)),
+            );
+            elements.extend([0, 1, 2, 0, 2, 3].map(|index| first + index));
+        }
+        MeshVertex::generate_tangents(&mut vertices, &mut elements)?;
+
+        VertexArray::builder()
+            .vertices(&vertices)
+            .element_buffer(elements)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_near;
+
+    const QUAD: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const QUAD_INDICES: [usize; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn quad_tangents(texture_coords: [[f32; 2]; 4], normal: [f32; 3]) -> Vec<[f32; 4]> {
+        tangents(&QUAD, &texture_coords, &[normal; 4], &QUAD_INDICES).tangents
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mapped = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        for tangent in quad_tangents(mapped, [0.0, 0.0, 1.0]) {
+            assert_near(tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+
+        // Turned a quarter, `u` runs up the quad and `v` to the left
+        let rotated = [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        for tangent in quad_tangents(rotated, [0.0, 0.0, 1.0]) {
+            assert_near(tangent, [0.0, 1.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_texture_coordinates_flip_handedness() {
+        let mirrored = [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        for tangent in quad_tangents(mirrored, [0.0, 0.0, 1.0]) {
+            assert_near(tangent, [-1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn tangents_are_perpendicular_to_normals() {
+        let mapped = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        for tangent in quad_tangents(mapped, [0.6, 0.0, 0.8]) {
+            assert_near(tangent, [0.8, 0.0, -0.6, 1.0]);
+        }
+    }
+
+    #[test]
+    fn tangents_are_weighted_by_angle() {
+        // Vertex 0 is the right angle of a triangle with `u` along x and the 45 degree corner of
+        // one with `u` along y
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [-1.0, 1.0, 0.0],
+        ];
+        let texture_coords = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+        let normals = [[0.0, 0.0, 1.0]; 5];
+        let tangents =
+            tangents(&positions, &texture_coords, &normals, &[0, 1, 2, 0, 3, 4]).tangents;
+
+        let (x, y) = (2.0 / 5.0_f32.sqrt(), 1.0 / 5.0_f32.sqrt());
+        assert_near(tangents[0], [x, y, 0.0, 1.0]);
+        assert_near(tangents[1], [1.0, 0.0, 0.0, 1.0]);
+        assert_near(tangents[4], [0.0, 1.0, 0.0, 1.0]);
+    }
+
+    /// The quad's texture mirrored across its diagonal, `u` running along x in the first
+    /// triangle and along y, mirrored, in the second
+    const SEAM_COORDS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [1.0, 0.0]];
+
+    #[test]
+    fn mirrored_seams_split_vertices() {
+        let split = tangents(&QUAD, &SEAM_COORDS, &[[0.0, 0.0, 1.0]; 4], &QUAD_INDICES);
+
+        // The diagonal's vertices are copied for the mirrored side instead of averaged
+        assert_eq!(split.sources, [0, 1, 2, 3, 0, 2]);
+        assert_eq!(split.indices, [0, 1, 2, 4, 5, 3]);
+        for index in [0, 1, 2] {
+            assert_near(split.tangents[index], [1.0, 0.0, 0.0, 1.0]);
+        }
+        for index in [3, 4, 5] {
+            assert_near(split.tangents[index], [0.0, 1.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn vertices_with_identical_attributes_share_tangents() {
+        // Two separate triangles meeting at a right angle, with vertex 0 repeated as vertex 3
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [-1.0, 0.0, 0.0],
+        ];
+        let texture_coords = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 1.0],
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 1.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 6];
+        let tangents =
+            tangents(&positions, &texture_coords, &normals, &[0, 1, 2, 3, 4, 5]).tangents;
+
+        let half = 0.5_f32.sqrt();
+        assert_near(tangents[0], [half, half, 0.0, 1.0]);
+        assert_near(tangents[3], [half, half, 0.0, 1.0]);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct ColouredVertex {
+        position: [f32; 3],
+        colour: [u8; 4],
+    }
+
+    crate::impl_vertex!(ColouredVertex {
+        0 => position: F32 * 3,
+        4 => colour: U8Normalised * 4,
+    });
+
+    fn input(name: &str, location: u32, components: i32, locations: u32) -> ActiveAttribute {
+        ActiveAttribute {
+            name: name.into(),
+            location,
+            components,
+            locations,
+            integer: false,
+        }
+    }
+
+    fn check_coloured(inputs: &[ActiveAttribute]) -> Result<()> {
+        let (labels, formats): (Vec<String>, Vec<AttributeFormat>) = ColouredVertex::ATTRIBUTES
+            .iter()
+            .map(|(name, format)| ((*name).to_owned(), *format))
+            .unzip();
+        check_inputs(&labels, &formats, inputs)
+    }
+
+    #[test]
+    fn instance_inputs_beside_a_vertex_attribute_at_location_4() {
+        let position = input("in_position", 0, 3, 1);
+        let colour = input("in_colour", 4, 4, 1);
+
+        // The bundled shaders' instance locations are clear of the colour
+        let clear = [
+            position.clone(),
+            colour,
+            input("in_instance_model", 11, 4, 4),
+            input("in_instance_tint", 15, 4, 1),
+        ];
+        assert!(check_coloured(&clear).is_ok());
+        assert!(InstanceBuffer::attributes(&clear)
+            .iter()
+            .all(|&(location, _)| location != 4));
+
+        // Any column of an instance matrix over the colour is caught before drawing
+        for location in 1..=4 {
+            let overlapping = [position.clone(), input("in_instance_model", location, 4, 4)];
+            assert!(check_coloured(&overlapping).is_err());
+        }
+        assert!(check_coloured(&[position, input("in_instance_tint", 4, 4, 1)]).is_err());
+    }
+
+    #[test]
+    fn inputs_may_read_more_components_than_given() {
+        // The missing `w` of a 3 component position is filled with 1
+        assert!(check_coloured(&[input("in_position", 0, 4, 1)]).is_ok());
+        assert!(check_coloured(&[input("in_position", 0, 2, 1)]).is_err());
+
+        let mut integer = input("in_position", 0, 3, 1);
+        integer.integer = true;
+        assert!(check_coloured(&[integer]).is_err());
+    }
+
+    #[test]
+    fn merged_arrays_follow_one_another() {
+        // A quad of 4 vertices and 6 elements, then a triangle
+        let ranges = merged_ranges(&[(4, 6), (3, 3)]).unwrap();
+        let expected = [
+            DrawRange {
+                first: 0,
+                count: 6,
+                base_vertex: 0,
+            },
+            DrawRange {
+                first: 6,
+                count: 3,
+                base_vertex: 4,
+            },
+        ];
+        assert_eq!(ranges, expected);
+
+        let too_many = merged_ranges(&[(usize::MAX / 2, 3), (3, 3)]);
+        assert!(too_many.is_err());
+    }
+
+    #[test]
+    fn builder_adds_tangents() {
+        let builder = VertexArray::builder()
+            .attribute("position".into(), QUAD.iter().map(|point| point.to_vec()).collect())
+            .and_then(|builder| {
+                builder.attribute(
+                    "texture_coord".into(),
+                    vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![1.0, 1.0], vec![0.0, 1.0]],
+                )
             })
-            .collect()
+            .and_then(|builder| builder.attribute("normal".into(), vec![vec![0.0, 0.0, 1.0]; 4]))
+            .map(|builder| builder.element_buffer(vec![0, 1, 2, 0, 2, 3]))
+            .and_then(|builder| builder.generate_tangents("position", "texture_coord", "normal"))
+            .unwrap();
+
+        let (_, data, length) = builder.vb_content.last().unwrap();
+        assert_eq!(*length, 4);
+        assert_eq!(*data, vec![vec![1.0, 0.0, 0.0, 1.0]; 4]);
+
+        let seam = VertexArray::builder()
+            .attribute("position".into(), QUAD.iter().map(|point| point.to_vec()).collect())
+            .and_then(|builder| {
+                builder.attribute(
+                    "texture_coord".into(),
+                    SEAM_COORDS.iter().map(|coord| coord.to_vec()).collect(),
+                )
+            })
+            .and_then(|builder| builder.attribute("normal".into(), vec![vec![0.0, 0.0, 1.0]; 4]))
+            .map(|builder| builder.element_buffer(vec![0, 1, 2, 0, 2, 3]))
+            .and_then(|builder| builder.generate_tangents("position", "texture_coord", "normal"))
+            .unwrap();
+        assert_eq!(seam.eb_content, [0, 1, 2, 4, 5, 3]);
+        for (name, data, _) in &seam.vb_content {
+            assert_eq!(data.len(), 6, "{name} was not split");
+        }
+        assert_eq!(seam.vb_content[0].1[4], seam.vb_content[0].1[0]);
+
+        let missing = VertexArray::builder()
+            .attribute("position".into(), vec![vec![0.0; 3]; 3])
+            .and_then(|builder| builder.generate_tangents("position", "texture_coord", "normal"));
+        assert!(missing.is_err());
     }
 }
@@ -0,0 +1,586 @@
+//! Operations on the attributes and elements of a `Builder` before it is built. They work on
+//! attributes added with `Builder::attribute`, not on typed `Builder::vertices`.
+
+use std::collections::HashMap;
+
+use super::{Builder, Primitive, RESTART_INDEX};
+use crate::{
+    error_fmt,
+    linear_algebra::vector::Vector,
+    modelling::bounds::{BoundingBox, BoundingSphere},
+    EngineError::VertexArrayErr,
+    Result,
+};
+
+/// Vertices the optimised order is scored against, a little larger than most GPUs' caches
+const CACHE_SIZE: usize = 32;
+
+/// How `Builder::generate_normals` shares normals between triangles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Normals {
+    /// Vertices shared between triangles get the average of their normals, weighted by the
+    /// angle at each corner
+    #[default]
+    Smooth,
+    /// Every triangle gets its own vertices and its face normal, for faceted shading
+    Flat,
+}
+
+impl Builder {
+    fn vertex_count(&self) -> Result<usize> {
+        if self.typed.is_some() {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Typed vertices cannot be processed, use separate attributes"
+            )));
+        }
+
+        let count = self.vb_content.first().map_or(0, |(_, data, _)| data.len());
+        if self
+            .vb_content
+            .iter()
+            .any(|(_, data, _)| data.len() != count)
+        {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Not all attributes are the same length"
+            )));
+        }
+
+        Ok(count)
+    }
+
+    /// The elements, or every vertex in order if none were given, checked to be in range
+    fn elements(&self) -> Result<Vec<u32>> {
+        let count = self.vertex_count()?;
+        if self.eb_content.is_empty() {
+            return Ok((0..count.try_into().unwrap_or(u32::MAX)).collect());
+        }
+
+        let restart = self.primitive_restart.then_some(RESTART_INDEX);
+        self.eb_content
+            .iter()
+            .find(|&&index| index as usize >= count && Some(index) != restart)
+            .map_or_else(
+                || Ok(self.eb_content.clone()),
+                |index| {
+                    Err(VertexArrayErr(error_fmt!(
+                        vertex_array::Builder,
+                        "Element {index} is out of range of {count} vertices"
+                    )))
+                },
+            )
+    }
+
+    /// The elements of a list of whole triangles, for processing that needs them
+    pub(super) fn triangle_indices(&self, operation: &str) -> Result<Vec<usize>> {
+        if self.primitive != Primitive::Triangles {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Cannot {operation} for {:?}, only Primitive::Triangles",
+                self.primitive
+            )));
+        }
+
+        let elements = self.elements()?;
+        if !elements.len().is_multiple_of(3) {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Cannot {operation}, {} elements are not whole triangles",
+                elements.len()
+            )));
+        }
+        if elements.contains(&RESTART_INDEX) && self.primitive_restart {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_array::Builder,
+                "Cannot {operation}, triangle lists do not restart"
+            )));
+        }
+
+        Ok(elements.into_iter().map(|index| index as usize).collect())
+    }
+
+    fn points(&self, position: &str) -> Result<Vec<Vector<3>>> {
+        self.vertex_count()?;
+        Ok(self
+            .attribute_data(position, 3)?
+            .iter()
+            .map(|point| Vector::new([point[0], point[1], point[2]]))
+            .collect())
+    }
+
+    /// Replace the attribute called `name`, or add it if there is none
+    fn set_attribute(&mut self, name: &str, data: Vec<Vec<f32>>, length: usize) {
+        match self.vb_content.iter_mut().find(|(label, ..)| label == name) {
+            Some(attribute) => {
+                attribute.1 = data;
+                attribute.2 = length;
+            }
+            None => self.vb_content.push((name.into(), data, length)),
+        }
+    }
+
+    /// Keep only the vertices in `order`, so that vertex `order[i]` becomes vertex `i`
+    pub(super) fn reorder_vertices(&mut self, order: &[usize]) {
+        for (_, data, _) in &mut self.vb_content {
+            *data = order.iter().map(|&index| data[index].clone()).collect();
+        }
+    }
+
+    /// Set the "normal" attribute from the named position attribute, replacing any normals
+    /// already given. Flat normals duplicate every vertex shared between triangles.
+    /// # Errors
+    /// Returns an error if the position attribute is missing, an index is out of range, or the
+    /// primitive is not `Primitive::Triangles`
+    #[inline]
+    pub fn generate_normals(mut self, position: &str, normals: Normals) -> Result<Self> {
+        let mut indices = self.triangle_indices("generate normals")?;
+
+        if normals == Normals::Flat {
+            self.reorder_vertices(&indices);
+            indices = (0..indices.len()).collect();
+            self.eb_content = (0..indices.len().try_into().unwrap_or(u32::MAX)).collect();
+        }
+
+        let points = self.points(position)?;
+        let mut sums = vec![Vector::<3>::new_zero(); points.len()];
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+            let face = (points[b] - points[a]).cross(points[c] - points[a]);
+            if face.is_zero() {
+                continue;
+            }
+            let face = face.normalize();
+
+            for (corner, previous, next) in [(a, c, b), (b, a, c), (c, b, a)] {
+                let weight = match normals {
+                    Normals::Flat => 1.0,
+                    Normals::Smooth => {
+                        let to_previous = points[previous] - points[corner];
+                        let to_next = points[next] - points[corner];
+                        if to_previous.is_zero() || to_next.is_zero() {
+                            continue;
+                        }
+                        to_previous
+                            .normalize()
+                            .dot(&to_next.normalize())
+                            .clamp(-1.0, 1.0)
+                            .acos()
+                    }
+                };
+                sums[corner] = sums[corner] + face.scale(weight);
+            }
+        }
+
+        let data = sums
+            .into_iter()
+            .map(|sum| {
+                let normal = if sum.is_zero() {
+                    [0.0, 0.0, 1.0]
+                } else {
+                    sum.normalize().into_inner()
+                };
+                normal.to_vec()
+            })
+            .collect();
+
+        self.set_attribute("normal", data, 3);
+        Ok(self)
+    }
+
+    /// Merge vertices whose attributes all round to the same multiple of `epsilon`, or are
+    /// exactly equal if `epsilon` is not positive, and point the elements at the survivors
+    /// # Errors
+    /// Returns an error if an index is out of range
+    #[inline]
+    pub fn weld_vertices(mut self, epsilon: f32) -> Result<Self> {
+        let elements = self.elements()?;
+        let count = self.vertex_count()?;
+
+        #[expect(clippy::cast_possible_truncation)]
+        let key = |value: f32| {
+            if epsilon > 0.0 {
+                (value / epsilon).round() as i64
+            } else {
+                i64::from(value.to_bits())
+            }
+        };
+
+        let mut welded = HashMap::new();
+        let mut kept = Vec::new();
+        let remap: Vec<u32> = (0..count)
+            .map(|vertex| {
+                let vertex_key: Vec<i64> = self
+                    .vb_content
+                    .iter()
+                    .flat_map(|(_, data, _)| data[vertex].iter().copied().map(key))
+                    .collect();
+
+                *welded.entry(vertex_key).or_insert_with(|| {
+                    kept.push(vertex);
+                    u32::try_from(kept.len() - 1).unwrap_or(u32::MAX)
+                })
+            })
+            .collect();
+
+        self.reorder_vertices(&kept);
+        self.eb_content = elements
+            .into_iter()
+            .map(|index| {
+                if index == RESTART_INDEX && self.primitive_restart {
+                    index
+                } else {
+                    remap[index as usize]
+                }
+            })
+            .collect();
+
+        Ok(self)
+    }
+
+    /// Drop triangles that cover no area, having a repeated vertex or all three on a line.
+    /// Vertices left unused are removed by `optimise_vertex_cache`.
+    /// # Errors
+    /// Returns an error if the position attribute is missing, an index is out of range, or the
+    /// primitive is not `Primitive::Triangles`
+    #[inline]
+    pub fn remove_degenerate_triangles(mut self, position: &str) -> Result<Self> {
+        let indices = self.triangle_indices("remove degenerate triangles")?;
+        let points = self.points(position)?;
+
+        self.eb_content = indices
+            .chunks_exact(3)
+            .filter(|triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                if a == b || b == c || c == a {
+                    return false;
+                }
+
+                let (edge_1, edge_2) = (points[b] - points[a], points[c] - points[a]);
+                let longest = edge_1
+                    .dot(&edge_1)
+                    .max(edge_2.dot(&edge_2))
+                    .max(points[c].distance_squared(points[b]));
+                let area = edge_1.cross(edge_2);
+
+                area.dot(&area).sqrt() > f32::EPSILON * longest
+            })
+            .flatten()
+            .map(|&index| u32::try_from(index).unwrap_or(u32::MAX))
+            .collect();
+
+        Ok(self)
+    }
+
+    /// Reorder the triangles so that vertices are reused while still in the GPU's post-transform
+    /// cache (Forsyth's algorithm), then reorder the vertices into the order they are first used
+    /// and drop any that are unused
+    /// # Errors
+    /// Returns an error if an index is out of range or the primitive is not
+    /// `Primitive::Triangles`
+    #[inline]
+    pub fn optimise_vertex_cache(mut self) -> Result<Self> {
+        let indices = self.triangle_indices("optimise the vertex cache")?;
+        let count = self.vertex_count()?;
+        let triangles: Vec<[usize; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); count];
+        for (triangle, vertices) in triangles.iter().enumerate() {
+            for &vertex in vertices {
+                vertex_triangles[vertex].push(triangle);
+            }
+        }
+
+        let mut cache_position: Vec<Option<usize>> = vec![None; count];
+        let mut vertex_scores: Vec<f32> = (0..count)
+            .map(|vertex| vertex_score(None, vertex_triangles[vertex].len()))
+            .collect();
+        let mut triangle_scores: Vec<f32> = triangles
+            .iter()
+            .map(|vertices| vertices.iter().map(|&vertex| vertex_scores[vertex]).sum())
+            .collect();
+        let mut emitted = vec![false; triangles.len()];
+        let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+        let mut order = Vec::with_capacity(indices.len());
+
+        let mut best = None;
+        for _ in 0..triangles.len() {
+            // Fall back to searching everything when nothing in the cache has triangles left
+            let next = best.unwrap_or_else(|| {
+                (0..triangles.len())
+                    .filter(|&triangle| !emitted[triangle])
+                    .max_by(|&a, &b| triangle_scores[a].total_cmp(&triangle_scores[b]))
+                    .unwrap_or(0)
+            });
+
+            emitted[next] = true;
+            order.extend(triangles[next]);
+
+            for &vertex in triangles[next].iter().rev() {
+                vertex_triangles[vertex].retain(|&triangle| triangle != next);
+                cache.retain(|&cached| cached != vertex);
+                cache.insert(0, vertex);
+            }
+
+            // Rescore everything that moved in or fell out of the cache
+            let evicted = cache.split_off(cache.len().min(CACHE_SIZE));
+            for &vertex in &evicted {
+                cache_position[vertex] = None;
+            }
+
+            best = None;
+            let mut best_score = f32::MIN;
+            for (position, &vertex) in cache.iter().enumerate() {
+                cache_position[vertex] = Some(position);
+            }
+            for &vertex in cache.iter().chain(&evicted) {
+                let score = vertex_score(cache_position[vertex], vertex_triangles[vertex].len());
+                let change = score - vertex_scores[vertex];
+                vertex_scores[vertex] = score;
+
+                for &triangle in &vertex_triangles[vertex] {
+                    triangle_scores[triangle] += change;
+                }
+            }
+            for &vertex in &cache {
+                for &triangle in &vertex_triangles[vertex] {
+                    if triangle_scores[triangle] > best_score {
+                        best_score = triangle_scores[triangle];
+                        best = Some(triangle);
+                    }
+                }
+            }
+        }
+
+        // Vertices in order of first use
+        let mut remap = vec![None; count];
+        let mut kept = Vec::new();
+        for &vertex in &order {
+            if remap[vertex].is_none() {
+                remap[vertex] = Some(u32::try_from(kept.len()).unwrap_or(u32::MAX));
+                kept.push(vertex);
+            }
+        }
+
+        self.reorder_vertices(&kept);
+        self.eb_content = order
+            .into_iter()
+            .map(|vertex| remap[vertex].unwrap_or_default())
+            .collect();
+
+        Ok(self)
+    }
+
+    /// The box around the named position attribute
+    /// # Errors
+    /// Returns an error if the attribute is missing or has fewer than 3 components
+    #[inline]
+    pub fn bounding_box(&self, position: &str) -> Result<BoundingBox> {
+        BoundingBox::from_points(self.points(position)?).ok_or_else(|| {
+            VertexArrayErr(error_fmt!(vertex_array::Builder, "No vertices to bound"))
+        })
+    }
+
+    /// A sphere around the named position attribute, see `BoundingSphere::from_points`
+    /// # Errors
+    /// Returns an error if the attribute is missing or has fewer than 3 components
+    #[inline]
+    pub fn bounding_sphere(&self, position: &str) -> Result<BoundingSphere> {
+        BoundingSphere::from_points(&self.points(position)?).ok_or_else(|| {
+            VertexArrayErr(error_fmt!(vertex_array::Builder, "No vertices to bound"))
+        })
+    }
+}
+
+/// Forsyth's score for a vertex at `cache_position` with `remaining` triangles still to draw.
+/// The three most recent vertices score a little lower, as the triangle just drawn used them.
+#[expect(clippy::cast_precision_loss)]
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+
+    // Favour vertices with few triangles left, so they are finished off and leave the cache
+    2.0f32.mul_add((remaining as f32).powf(-0.5), cache_score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffers::vertex_array::VertexArray;
+    use crate::test_util::assert_near;
+
+    fn mesh(positions: &[[f32; 3]], elements: Vec<u32>) -> Builder {
+        VertexArray::builder()
+            .attribute(
+                "position".into(),
+                positions.iter().map(|position| position.to_vec()).collect(),
+            )
+            .unwrap()
+            .element_buffer(elements)
+    }
+
+    fn attribute(builder: &Builder, name: &str) -> Vec<Vec<f32>> {
+        builder
+            .vb_content
+            .iter()
+            .find(|(label, ..)| label == name)
+            .map(|(_, data, _)| data.clone())
+            .unwrap()
+    }
+
+    /// Two triangles meeting at a right angle along the x axis, one facing +z and one +y
+    fn bent() -> Builder {
+        mesh(
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            vec![0, 1, 2, 0, 3, 1],
+        )
+    }
+
+    #[test]
+    fn smooth_normals_average_by_angle() {
+        let builder = bent().generate_normals("position", Normals::Smooth).unwrap();
+        let normals = attribute(&builder, "normal");
+        let diagonal = 0.5_f32.sqrt();
+
+        assert_near(&normals[0], [0.0, diagonal, diagonal]);
+        assert_near(&normals[1], [0.0, diagonal, diagonal]);
+        assert_near(&normals[2], [0.0, 0.0, 1.0]);
+        assert_near(&normals[3], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn flat_normals_split_vertices() {
+        let builder = bent().generate_normals("position", Normals::Flat).unwrap();
+        let normals = attribute(&builder, "normal");
+
+        assert_eq!(builder.eb_content, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(attribute(&builder, "position").len(), 6);
+        for (vertex, normal) in normals.iter().enumerate() {
+            let expected = if vertex < 3 { [0.0, 0.0, 1.0] } else { [0.0, 1.0, 0.0] };
+            assert_near(normal, expected);
+        }
+    }
+
+    #[test]
+    fn welding_a_duplicated_quad() {
+        let builder = mesh(
+            &[
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+            Vec::new(),
+        )
+        .weld_vertices(0.0)
+        .unwrap();
+
+        assert_eq!(attribute(&builder, "position").len(), 4);
+        assert_eq!(builder.eb_content, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn welding_within_epsilon() {
+        let nearly = mesh(&[[0.0; 3], [1.0e-4, 0.0, 0.0], [1.0, 0.0, 0.0]], Vec::new());
+        assert_eq!(nearly.weld_vertices(1.0e-3).unwrap().eb_content, [0, 0, 1]);
+
+        let nearly = mesh(&[[0.0; 3], [1.0e-4, 0.0, 0.0], [1.0, 0.0, 0.0]], Vec::new());
+        assert_eq!(nearly.weld_vertices(0.0).unwrap().eb_content, [0, 1, 2]);
+    }
+
+    #[test]
+    fn welding_keeps_vertices_differing_in_any_attribute() {
+        let builder = mesh(&[[0.0; 3], [0.0; 3], [1.0, 0.0, 0.0]], Vec::new())
+            .attribute("texture_coord".into(), vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0, 0.0]])
+            .unwrap()
+            .weld_vertices(0.0)
+            .unwrap();
+
+        assert_eq!(builder.eb_content, [0, 1, 2]);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_removed() {
+        let builder = mesh(
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [2.0, 0.0, 0.0]],
+            vec![0, 1, 2, 0, 0, 1, 0, 1, 3, 2, 1, 0],
+        )
+        .remove_degenerate_triangles("position")
+        .unwrap();
+
+        assert_eq!(builder.eb_content, [0, 1, 2, 2, 1, 0]);
+    }
+
+    /// The position of each corner of each triangle, sorted so the lists can be compared
+    fn triangle_set(builder: &Builder) -> Vec<[[u32; 3]; 3]> {
+        let positions = attribute(builder, "position");
+        let mut triangles: Vec<_> = builder
+            .eb_content
+            .chunks_exact(3)
+            .map(|triangle| {
+                [0, 1, 2].map(|corner| {
+                    let position = &positions[triangle[corner] as usize];
+                    [0, 1, 2].map(|axis| position[axis].to_bits())
+                })
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn optimising_keeps_triangles_and_drops_unused_vertices() {
+        // A 3x3 grid of vertices with an unused vertex in the middle of the list
+        let mut positions: Vec<[f32; 3]> = (0..9_u8)
+            .map(|vertex| [f32::from(vertex % 3), f32::from(vertex / 3), 0.0])
+            .collect();
+        positions.insert(4, [5.0, 5.0, 5.0]);
+        let grid = |vertex: u32| if vertex >= 4 { vertex + 1 } else { vertex };
+
+        let mut elements = Vec::new();
+        for quad in [2, 0, 3, 1] {
+            let corner = quad + quad / 2;
+            let quad = [corner, corner + 1, corner + 4, corner, corner + 4, corner + 3];
+            elements.extend(quad.map(grid));
+        }
+
+        let original = mesh(&positions, elements);
+        let expected = triangle_set(&original);
+        let optimised = original.optimise_vertex_cache().unwrap();
+
+        assert_eq!(triangle_set(&optimised), expected);
+        assert_eq!(attribute(&optimised, "position").len(), 9);
+        assert!(!attribute(&optimised, "position").contains(&vec![5.0, 5.0, 5.0]));
+
+        // Vertices are numbered in the order they are first used
+        let mut next = 0;
+        for &index in &optimised.eb_content {
+            assert!(index <= next);
+            if index == next {
+                next += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn processing_needs_triangles() {
+        let lines = mesh(&[[0.0; 3], [1.0, 0.0, 0.0]], Vec::new()).primitive(Primitive::Lines);
+        assert!(lines.optimise_vertex_cache().is_err());
+
+        let out_of_range = mesh(&[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], vec![0, 1, 3]);
+        assert!(out_of_range.weld_vertices(0.0).is_err());
+    }
+}
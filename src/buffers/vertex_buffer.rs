@@ -1,45 +1,307 @@
-use crate::{error_fmt, EngineError::VertexBufferErr};
-use std::{mem, sync::Arc};
+use crate::{
+    buffers::BufferUsage, capabilities::GlCapabilities, error_fmt, EngineError::VertexBufferErr,
+};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    mem, ptr,
+    rc::Rc,
+};
+
+/// Wait at most this long for the GPU to release a ring buffer segment
+const RING_TIMEOUT_NANOSECONDS: u64 = 1_000_000_000;
+
+/// Persistently mapped storage split into equally sized segments, written one after another
+#[derive(Debug)]
+struct Ring {
+    mapping: *mut c_void,
+    segments: usize,
+    current: Cell<usize>,
+    fences: RefCell<Vec<gl::types::GLsync>>,
+}
 
 #[derive(Debug)] // No Clone
 pub struct VertexBufferInternal {
     id: u32,
+    usage: BufferUsage,
+    /// Bytes of storage, of a single segment for a ring buffer
+    size: Cell<usize>,
+    ring: Option<Ring>,
 }
 
 #[derive(Clone, Debug)]
-pub struct VertexBuffer(Arc<VertexBufferInternal>);
+pub struct VertexBuffer(Rc<VertexBufferInternal>);
+
+fn byte_size<T>(contents: &[T]) -> crate::Result<isize> {
+    mem::size_of_val(contents)
+        .try_into()
+        .map_err(|_| VertexBufferErr(error_fmt!(VertexBuffer, "VBO length exceeds isize")))
+}
 
 impl VertexBuffer {
+    /// Upload `contents` as raw bytes with room for at least `capacity` bytes, so that later
+    /// updates up to that size need no reallocation. `T` only determines the size of the buffer.
     /// # Errors
-    pub fn new(contents: &[f32]) -> crate::Result<Self> {
-        let id = unsafe {
-            let mut id = 0;
+    /// Returns an error if the size exceeds `isize`, or for a ring buffer with no segments or
+    /// without buffer storage support
+    pub fn with_usage<T: Copy>(
+        contents: &[T],
+        usage: BufferUsage,
+        capacity: usize,
+    ) -> crate::Result<Self> {
+        let size = mem::size_of_val(contents).max(capacity);
+        let gl_size: isize = size
+            .try_into()
+            .map_err(|_| VertexBufferErr(error_fmt!(VertexBuffer, "VBO length exceeds isize")))?;
+
+        let contents_size = byte_size(contents)?;
+
+        // Checked before the buffer exists so that no error leaves it behind
+        let ring_storage = match usage {
+            BufferUsage::Ring { segments: 0 } => {
+                return Err(VertexBufferErr(error_fmt!(
+                    VertexBuffer,
+                    "A ring buffer needs at least one segment"
+                )));
+            }
+            BufferUsage::Ring { .. } if !GlCapabilities::buffer_storage() => {
+                return Err(VertexBufferErr(error_fmt!(
+                    VertexBuffer,
+                    "Ring buffers need OpenGL 4.4 or GL_ARB_buffer_storage"
+                )));
+            }
+            BufferUsage::Ring { segments } => {
+                let total = gl_size
+                    .checked_mul(segments.try_into().unwrap_or(isize::MAX))
+                    .ok_or_else(|| {
+                        VertexBufferErr(error_fmt!(VertexBuffer, "Ring buffer size exceeds isize"))
+                    })?;
+                Some((segments, total))
+            }
+            _ => None,
+        };
+
+        let mut id = 0;
+        let ring = unsafe {
             gl::GenBuffers(1, &mut id);
             gl::BindBuffer(gl::ARRAY_BUFFER, id);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (mem::size_of_val(contents)).try_into().map_err(|_| {
-                    VertexBufferErr(error_fmt!(VertexBuffer, "VBO length exceeds i32"))
-                })?,
-                contents.as_ptr().cast(),
-                gl::STATIC_DRAW,
-            );
 
-            id
+            if let Some((segments, total)) = ring_storage {
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+                gl::BufferStorage(gl::ARRAY_BUFFER, total, ptr::null(), flags);
+                let mapping = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, total, flags);
+                if mapping.is_null() {
+                    gl::DeleteBuffers(1, &id);
+                    return Err(VertexBufferErr(error_fmt!(
+                        VertexBuffer,
+                        "Could not persistently map the ring buffer"
+                    )));
+                }
+
+                ptr::copy_nonoverlapping(
+                    contents.as_ptr().cast::<u8>(),
+                    mapping.cast(),
+                    mem::size_of_val(contents),
+                );
+
+                Some(Ring {
+                    mapping,
+                    segments,
+                    current: Cell::new(0),
+                    fences: RefCell::new(vec![ptr::null(); segments]),
+                })
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, gl_size, ptr::null(), usage.gl_usage());
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, contents_size, contents.as_ptr().cast());
+                None
+            }
         };
 
-        Ok(Self(Arc::new(VertexBufferInternal { id })))
+        Ok(Self(Rc::new(VertexBufferInternal {
+            id,
+            usage,
+            size: Cell::new(size),
+            ring,
+        })))
     }
 
     pub fn id(&self) -> u32 {
         self.0.id
     }
+
+    /// Bytes of storage, including every segment of a ring buffer
+    pub(crate) fn memory(&self) -> usize {
+        self.0.size.get() * self.0.ring.as_ref().map_or(1, |ring| ring.segments)
+    }
+
+    /// Byte offset of the data to draw from, only non-zero for ring buffers
+    pub(crate) fn draw_offset(&self) -> usize {
+        self.0
+            .ring
+            .as_ref()
+            .map_or(0, |ring| ring.current.get() * self.0.size.get())
+    }
+
+    /// Copy the current contents back from the GPU, the segment last written for a ring buffer
+    pub(crate) fn read(&self) -> Vec<u8> {
+        let size = self.0.size.get();
+        let mut data = vec![0_u8; size];
+
+        unsafe {
+            if let Some(ring) = &self.0.ring {
+                ptr::copy_nonoverlapping(
+                    ring.mapping.cast::<u8>().add(self.draw_offset()),
+                    data.as_mut_ptr(),
+                    size,
+                );
+            } else {
+                // The copy target leaves the vertex array bindings alone
+                gl::BindBuffer(gl::COPY_READ_BUFFER, self.0.id);
+                gl::GetBufferSubData(
+                    gl::COPY_READ_BUFFER,
+                    0,
+                    size.try_into().unwrap_or(isize::MAX),
+                    data.as_mut_ptr().cast(),
+                );
+                gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+            }
+        }
+
+        data
+    }
+
+    /// Overwrite part of the buffer in place, starting `offset` bytes in
+    /// # Errors
+    /// Returns an error if the data runs past the end of the buffer, or for a ring buffer
+    pub(crate) fn update<T: Copy>(&self, offset: usize, data: &[T]) -> crate::Result<()> {
+        if self.0.ring.is_some() {
+            return Err(VertexBufferErr(error_fmt!(
+                VertexBuffer,
+                "Ring buffers cannot be updated in place, replace their contents instead"
+            )));
+        }
+
+        let end = offset.saturating_add(mem::size_of_val(data));
+        if end > self.0.size.get() {
+            return Err(VertexBufferErr(error_fmt!(
+                VertexBuffer,
+                "Update of bytes {offset}..{end} is outside the buffer of {} bytes",
+                self.0.size.get()
+            )));
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.0.id);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                offset.try_into().map_err(|_| {
+                    VertexBufferErr(error_fmt!(VertexBuffer, "Offset exceeds isize"))
+                })?,
+                byte_size(data)?,
+                data.as_ptr().cast(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Replace the whole contents of the buffer without waiting for draws still reading it.
+    ///
+    /// Ordinary buffers are orphaned, the driver hands back fresh storage of the new size while
+    /// the old one is released once the GPU is done. Ring buffers move on to their next segment
+    /// instead, only blocking if the GPU is still reading that segment from several frames ago.
+    /// # Errors
+    /// Returns an error if the data does not fit in a ring buffer segment, or the GPU does not
+    /// release the segment in time
+    pub(crate) fn replace<T: Copy>(&self, data: &[T]) -> crate::Result<()> {
+        let Some(ring) = &self.0.ring else {
+            unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.0.id);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    byte_size(data)?,
+                    ptr::null(),
+                    self.0.usage.gl_usage(),
+                );
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, byte_size(data)?, data.as_ptr().cast());
+            }
+            self.0.size.set(mem::size_of_val(data));
+            return Ok(());
+        };
+
+        let segment_size = self.0.size.get();
+        if mem::size_of_val(data) > segment_size {
+            return Err(VertexBufferErr(error_fmt!(
+                VertexBuffer,
+                "{} bytes do not fit in a ring buffer segment of {segment_size} bytes",
+                mem::size_of_val(data)
+            )));
+        }
+
+        let mut fences = ring.fences.borrow_mut();
+        let current = ring.current.get();
+        let next = (current + 1) % ring.segments;
+
+        unsafe {
+            // Everything drawn so far may still be reading the current segment
+            gl::DeleteSync(fences[current]);
+            fences[current] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+
+            if !fences[next].is_null() {
+                let status = gl::ClientWaitSync(
+                    fences[next],
+                    gl::SYNC_FLUSH_COMMANDS_BIT,
+                    RING_TIMEOUT_NANOSECONDS,
+                );
+                if status == gl::TIMEOUT_EXPIRED || status == gl::WAIT_FAILED {
+                    return Err(VertexBufferErr(error_fmt!(
+                        VertexBuffer,
+                        "Timed out waiting for the GPU to release a ring buffer segment"
+                    )));
+                }
+                gl::DeleteSync(fences[next]);
+                fences[next] = ptr::null();
+            }
+
+            ptr::copy_nonoverlapping(
+                data.as_ptr().cast::<u8>(),
+                ring.mapping.cast::<u8>().add(next * segment_size),
+                mem::size_of_val(data),
+            );
+        }
+
+        ring.current.set(next);
+        Ok(())
+    }
 }
 
 impl Drop for VertexBufferInternal {
     fn drop(&mut self) {
         unsafe {
+            if let Some(ring) = &self.ring {
+                for fence in ring.fences.borrow().iter() {
+                    gl::DeleteSync(*fence);
+                }
+            }
+            // Deleting the buffer also unmaps it
             gl::DeleteBuffers(1, &self.id);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffers_are_checked_before_creation() {
+        // Both fail before any OpenGL call, as there is no context to call
+        let ring = |segments| {
+            VertexBuffer::with_usage(&[0.0_f32; 3], BufferUsage::Ring { segments }, 0)
+        };
+        assert!(ring(0).is_err());
+        // Without a context there are no capabilities, so buffer storage is unsupported
+        assert!(ring(3).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use crate::{buffers::vertex_array::tangents, error_fmt, EngineError::VertexArrayErr, Result};
+
+/// The type of each component of a vertex attribute as stored in the vertex buffer.
+///
+/// Normalised integers are mapped to `[0.0, 1.0]` (unsigned) or `[-1.0, 1.0]` (signed) and read
+/// as `float`/`vecN` in the shader, `I32` and `U32` are read as `int`/`ivecN` and `uint`/`uvecN`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    F32,
+    F16,
+    U8Normalised,
+    U16Normalised,
+    I8Normalised,
+    I16Normalised,
+    I32,
+    U32,
+}
+
+impl ComponentType {
+    pub(crate) const fn gl_type(self) -> u32 {
+        match self {
+            Self::F32 => gl::FLOAT,
+            Self::F16 => gl::HALF_FLOAT,
+            Self::U8Normalised => gl::UNSIGNED_BYTE,
+            Self::U16Normalised => gl::UNSIGNED_SHORT,
+            Self::I8Normalised => gl::BYTE,
+            Self::I16Normalised => gl::SHORT,
+            Self::I32 => gl::INT,
+            Self::U32 => gl::UNSIGNED_INT,
+        }
+    }
+
+    /// Size of a single component in bytes
+    #[must_use]
+    #[inline]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8Normalised | Self::I8Normalised => 1,
+            Self::F16 | Self::U16Normalised | Self::I16Normalised => 2,
+            Self::F32 | Self::I32 | Self::U32 => 4,
+        }
+    }
+
+    pub(crate) const fn is_normalised(self) -> bool {
+        matches!(
+            self,
+            Self::U8Normalised | Self::U16Normalised | Self::I8Normalised | Self::I16Normalised
+        )
+    }
+
+    /// Integer attributes must be set up with `glVertexAttribIPointer`
+    pub(crate) const fn is_integer(self) -> bool {
+        matches!(self, Self::I32 | Self::U32)
+    }
+}
+
+/// Where one attribute lives within a vertex and which shader input it feeds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttributeFormat {
+    /// Matches `layout (location = N)` in the vertex shader
+    pub location: u32,
+    /// 1 to 4
+    pub components: i32,
+    pub component_type: ComponentType,
+    /// Bytes from the start of the vertex
+    pub offset: usize,
+}
+
+/// A `#[repr(C)]` struct that can be uploaded directly as interleaved vertex data with
+/// `vertex_array::Builder::vertices`. Usually implemented with `impl_vertex!`.
+///
+/// # Safety
+///
+/// Every attribute must lie within the struct at its `offset`, and the fields there must have
+/// the size and layout described by its `components` and `component_type`.
+pub unsafe trait Vertex: Copy {
+    /// Each attribute with the name of the field it comes from
+    const ATTRIBUTES: &'static [(&'static str, AttributeFormat)];
+}
+
+/// Implement `Vertex` for a `#[repr(C)]` struct, listing the location, component type and
+/// component count of each field. Offsets are taken from the struct itself.
+///
+/// Compilation fails if a field is smaller than its components, though it may be padded.
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct CompactVertex {
+///     position: [f32; 3],
+///     normal: [i8; 4],
+///     texture_coord: [F16; 2],
+///     colour: [u8; 4],
+/// }
+///
+/// impl_vertex!(CompactVertex {
+///     0 => position: F32 * 3,
+///     2 => normal: I8Normalised * 3,
+///     1 => texture_coord: F16 * 2,
+///     4 => colour: U8Normalised * 4,
+/// });
+/// ```
+#[macro_export]
+macro_rules! impl_vertex {
+    ($vertex:ty { $($location:literal => $field:ident: $component:ident * $count:literal),* $(,)? }) => {
+        const _: () = {
+            const fn field_size<V, F>(_: fn(&V) -> &F) -> usize {
+                ::core::mem::size_of::<F>()
+            }
+            $(
+                ::core::assert!(
+                    field_size(|vertex: &$vertex| &vertex.$field)
+                        >= $crate::buffers::vertex_format::ComponentType::$component.size() * $count,
+                    concat!(
+                        "`", stringify!($field), "` is smaller than ",
+                        stringify!($count), " ", stringify!($component), " components",
+                    ),
+                );
+            )*
+        };
+
+        unsafe impl $crate::buffers::vertex_format::Vertex for $vertex {
+            const ATTRIBUTES: &'static [(&'static str, $crate::buffers::vertex_format::AttributeFormat)] = &[
+                $((
+                    stringify!($field),
+                    $crate::buffers::vertex_format::AttributeFormat {
+                        location: $location,
+                        components: $count,
+                        component_type: $crate::buffers::vertex_format::ComponentType::$component,
+                        offset: ::core::mem::offset_of!($vertex, $field),
+                    },
+                )),*
+            ];
+        }
+    };
+}
+
+/// IEEE 754 half precision float, for vertex data only, no arithmetic is provided
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct F16(u16);
+
+impl F16 {
+    #[must_use]
+    #[inline]
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<f32> for F16 {
+    /// Round to the nearest representable value, ties to even. Out of range values become
+    /// infinity and NaN stays NaN.
+    #[inline]
+    #[expect(clippy::cast_possible_truncation)]
+    fn from(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xFF) as i32;
+        let mantissa = bits & 0x007F_FFFF;
+
+        // Infinity and NaN
+        if exponent == 0xFF {
+            let nan = if mantissa == 0 { 0 } else { 0x0200 };
+            return Self(sign | 0x7C00 | nan);
+        }
+
+        let half_exponent = exponent - 127 + 15;
+
+        // Overflow to infinity
+        if half_exponent >= 0x1F {
+            return Self(sign | 0x7C00);
+        }
+
+        // Normal half
+        if half_exponent > 0 {
+            let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+            let remainder = mantissa & 0x1FFF;
+            let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+            // A carry out of the mantissa correctly increments the exponent
+            return Self(sign | (half + u32::from(round_up)) as u16);
+        }
+
+        // Too small even for a subnormal half
+        if half_exponent < -10 {
+            return Self(sign);
+        }
+
+        // Subnormal half, shift in the implicit leading one
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half & 1 == 1);
+
+        Self(sign | (half + u32::from(round_up)) as u16)
+    }
+}
+
+/// The layout of the built-in meshes, with the attribute names and locations the engine's
+/// shaders expect
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub texture_coord: [f32; 2],
+    pub normal: [f32; 3],
+    /// Handedness in `w`, see `vertex_array::Builder::generate_tangents`
+    pub tangent: [f32; 4],
+}
+
+crate::impl_vertex!(MeshVertex {
+    0 => position: F32 * 3,
+    1 => texture_coord: F32 * 2,
+    2 => normal: F32 * 3,
+    3 => tangent: F32 * 4,
+});
+
+impl MeshVertex {
+    /// A vertex with its tangent left to be generated
+    #[must_use]
+    #[inline]
+    pub const fn new(position: [f32; 3], texture_coord: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position,
+            texture_coord,
+            normal,
+            tangent: [0.0; 4],
+        }
+    }
+
+    /// Set the tangent of every vertex from the triangle list `elements`, as
+    /// `vertex_array::Builder::generate_tangents` does. Vertices on mirrored seams are copied to
+    /// the end of `vertices` and `elements` pointed at the copies.
+    /// # Errors
+    /// Returns an error if `elements` is not whole triangles or an index is out of range
+    #[inline]
+    pub fn generate_tangents(vertices: &mut Vec<Self>, elements: &mut [u32]) -> Result<()> {
+        if !elements.len().is_multiple_of(3) {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_format::MeshVertex,
+                "Cannot generate tangents, {} elements are not whole triangles",
+                elements.len()
+            )));
+        }
+        if let Some(index) = elements
+            .iter()
+            .find(|&&index| index as usize >= vertices.len())
+        {
+            return Err(VertexArrayErr(error_fmt!(
+                vertex_format::MeshVertex,
+                "Cannot generate tangents, element {index} is out of range for {} vertices",
+                vertices.len()
+            )));
+        }
+
+        let positions: Vec<_> = vertices.iter().map(|vertex| vertex.position).collect();
+        let texture_coords: Vec<_> = vertices.iter().map(|vertex| vertex.texture_coord).collect();
+        let normals: Vec<_> = vertices.iter().map(|vertex| vertex.normal).collect();
+        let indices: Vec<_> = elements.iter().map(|&index| index as usize).collect();
+
+        let generated = tangents(&positions, &texture_coords, &normals, &indices);
+        let copies: Vec<_> = generated.sources[vertices.len()..]
+            .iter()
+            .map(|&source| vertices[source])
+            .collect();
+        vertices.extend(copies);
+        for (element, index) in elements.iter_mut().zip(generated.indices) {
+            *element = index.try_into().unwrap_or(u32::MAX);
+        }
+        for (vertex, tangent) in vertices.iter_mut().zip(generated.tangents) {
+            vertex.tangent = tangent;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffers::vertex_array::VertexArray;
+
+    fn half(value: f32) -> u16 {
+        F16::from(value).to_bits()
+    }
+
+    #[test]
+    fn f16_exact_values() {
+        assert_eq!(half(0.0), 0x0000);
+        assert_eq!(half(-0.0), 0x8000);
+        assert_eq!(half(1.0), 0x3C00);
+        assert_eq!(half(-2.0), 0xC000);
+        assert_eq!(half(0.5), 0x3800);
+        assert_eq!(half(65504.0), 0x7BFF);
+    }
+
+    #[test]
+    fn f16_rounds_to_nearest_even() {
+        // Halfway between 1.0 and the next half, 1 + 2^-10, goes down to the even mantissa
+        assert_eq!(half(1.0 + 2.0_f32.powi(-11)), 0x3C00);
+        // Halfway between 1 + 2^-10 and 1 + 2^-9 goes up to the even mantissa
+        assert_eq!(half(1.0 + 2.0_f32.powi(-10) + 2.0_f32.powi(-11)), 0x3C02);
+        // Just over halfway rounds up
+        assert_eq!(half(1.0 + 2.0_f32.powi(-11) + 2.0_f32.powi(-20)), 0x3C01);
+        // Rounding the largest mantissa carries into the exponent
+        assert_eq!(half(2.0 - 2.0_f32.powi(-12)), 0x4000);
+    }
+
+    #[test]
+    fn f16_subnormals() {
+        assert_eq!(half(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(half(2.0_f32.powi(-14) - 2.0_f32.powi(-24)), 0x03FF);
+        assert_eq!(half(2.0_f32.powi(-14)), 0x0400);
+        assert_eq!(half(-3.0 * 2.0_f32.powi(-24)), 0x8003);
+        // Half the smallest subnormal is a tie, rounding to even zero
+        assert_eq!(half(2.0_f32.powi(-25)), 0x0000);
+        assert_eq!(half(1.5 * 2.0_f32.powi(-25)), 0x0001);
+        assert_eq!(half(2.0_f32.powi(-30)), 0x0000);
+    }
+
+    #[test]
+    fn f16_infinity_and_nan() {
+        assert_eq!(half(f32::INFINITY), 0x7C00);
+        assert_eq!(half(f32::NEG_INFINITY), 0xFC00);
+        assert_eq!(half(65520.0), 0x7C00);
+        assert_eq!(half(1.0e10), 0x7C00);
+        assert_eq!(half(-1.0e10), 0xFC00);
+
+        let nan = half(f32::NAN);
+        assert_eq!(nan & 0x7C00, 0x7C00);
+        assert_ne!(nan & 0x03FF, 0);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct CompactVertex {
+        position: [f32; 3],
+        normal: [i8; 4],
+        texture_coord: [F16; 2],
+        colour: [u8; 4],
+    }
+
+    crate::impl_vertex!(CompactVertex {
+        0 => position: F32 * 3,
+        2 => normal: I8Normalised * 3,
+        1 => texture_coord: F16 * 2,
+        4 => colour: U8Normalised * 4,
+    });
+
+    #[test]
+    fn packed_offsets() {
+        let formats: Vec<_> = CompactVertex::ATTRIBUTES
+            .iter()
+            .map(|(name, format)| (*name, format.location, format.offset))
+            .collect();
+        assert_eq!(
+            formats,
+            [
+                ("position", 0, 0),
+                ("normal", 2, 12),
+                ("texture_coord", 1, 16),
+                ("colour", 4, 20),
+            ]
+        );
+        assert_eq!(size_of::<CompactVertex>(), 24);
+        assert_eq!(size_of::<MeshVertex>(), 48);
+    }
+
+    #[derive(Clone, Copy)]
+    struct Overhanging {
+        _position: [f32; 2],
+    }
+
+    unsafe impl Vertex for Overhanging {
+        const ATTRIBUTES: &'static [(&'static str, AttributeFormat)] = &[(
+            "position",
+            AttributeFormat {
+                location: 0,
+                components: 3,
+                component_type: ComponentType::F32,
+                offset: 0,
+            },
+        )];
+    }
+
+    #[test]
+    fn attributes_must_fit_the_stride() {
+        let result = VertexArray::builder()
+            .vertices(&[Overhanging { _position: [0.0; 2] }])
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn typed_vertices_exclude_attributes() {
+        let result = VertexArray::builder()
+            .vertices(&[MeshVertex::default()])
+            .attribute("colour".into(), vec![vec![1.0]])
+            .and_then(|builder| builder.build());
+        assert!(result.is_err());
+    }
+}
@@ -1,5 +1,9 @@
+pub mod frustum;
+
 use crate::{linear_algebra::{matrix::Matrix, orientation::Orientation, vector::Vector}, some_builder};
 
+use self::frustum::Frustum;
+
 #[derive(Clone)]
 pub struct Camera {
     centre: Vector<3>, // either the position of the head
@@ -163,8 +167,9 @@ impl Camera {
         self.centre = position;
     }
 
+    /// World to view space, the camera sits at the origin looking down -z
     #[inline]
-    pub fn look_at(&self) -> Matrix<4, 4> {
+    pub fn view(&self) -> Matrix<4, 4> {
         let mut camera_right = self.orientation.right();
         let camera_up = self.orientation.view_up(Some(camera_right));
         let direction = -self.direction();
@@ -183,6 +188,25 @@ impl Camera {
 
         let rhs = Matrix::transform_translate(-camera_pos);
 
-        self.perspective * lhs * rhs
+        lhs * rhs
+    }
+
+    /// View to clip space
+    #[inline]
+    pub fn projection(&self) -> Matrix<4, 4> {
+        self.perspective
+    }
+
+    /// World to clip space, `projection() * view()`
+    #[inline]
+    pub fn look_at(&self) -> Matrix<4, 4> {
+        self.perspective * self.view()
+    }
+
+    /// The world space volume this camera can see
+    #[must_use]
+    #[inline]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.look_at())
     }
 }
@@ -0,0 +1,167 @@
+use crate::{
+    linear_algebra::{matrix::Matrix, vector::Vector},
+    modelling::bounds::{BoundingBox, BoundingSphere},
+};
+
+/// The six planes bounding what a camera can see, each as `(a, b, c, d)` with points inside
+/// satisfying `a * x + b * y + c * z + d >= 0`
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vector<4>; 6],
+}
+
+impl Frustum {
+    /// Extract the planes from a world to clip space matrix such as `Camera::look_at`, so that
+    /// they are in world space (Gribb and Hartmann's method)
+    #[must_use]
+    #[inline]
+    pub fn from_matrix(matrix: &Matrix<4, 4>) -> Self {
+        let row = |index: usize| {
+            Vector::new([
+                matrix[(index, 0)],
+                matrix[(index, 1)],
+                matrix[(index, 2)],
+                matrix[(index, 3)],
+            ])
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Left, right, bottom, top, near and far
+    #[must_use]
+    #[inline]
+    pub const fn planes(&self) -> &[Vector<4>; 6] {
+        &self.planes
+    }
+
+    fn distance(plane: &Vector<4>, point: [f32; 3]) -> f32 {
+        plane[0].mul_add(
+            point[0],
+            plane[1].mul_add(point[1], plane[2].mul_add(point[2], plane[3])),
+        )
+    }
+
+    /// Whether any of `bounds` may be inside. Boxes near a corner of the frustum can pass
+    /// without being visible, but none that are visible fail.
+    #[must_use]
+    #[inline]
+    pub fn intersects_box(&self, bounds: &BoundingBox) -> bool {
+        let (min, max) = (bounds.min.into_inner(), bounds.max.into_inner());
+
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal
+            let corner = [0, 1, 2].map(|i| if plane[i] >= 0.0 { max[i] } else { min[i] });
+            Self::distance(plane, corner) >= 0.0
+        })
+    }
+
+    /// Whether any of `bounds` may be inside, as for `intersects_box`
+    #[must_use]
+    #[inline]
+    pub fn intersects_sphere(&self, bounds: &BoundingSphere) -> bool {
+        let centre = bounds.centre.into_inner();
+
+        self.planes.iter().all(|plane| {
+            let normal = Vector::new([plane[0], plane[1], plane[2]]);
+            let length = normal.dot(&normal).sqrt();
+            Self::distance(plane, centre) >= -bounds.radius * length
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A camera at z = 5 looking towards the origin with a 90 degree field of view, so it sees
+    /// points from 1 to 100 in front of it whose x and y are no further out than their depth
+    fn frustum() -> Frustum {
+        let view = Matrix::transform_look_at(
+            Vector::new([0.0, 0.0, 5.0]),
+            Vector::new_zero(),
+            Vector::new([0.0, 1.0, 0.0]),
+        );
+        let projection =
+            Matrix::transform_perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0);
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    fn cube(min: [f32; 3], max: [f32; 3]) -> BoundingBox {
+        BoundingBox {
+            min: Vector::new(min),
+            max: Vector::new(max),
+        }
+    }
+
+    fn sphere(centre: [f32; 3], radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            centre: Vector::new(centre),
+            radius,
+        }
+    }
+
+    #[test]
+    fn planes_of_the_identity() {
+        let planes = Frustum::from_matrix(&Matrix::identity()).planes().map(Vector::into_inner);
+        assert_eq!(
+            planes,
+            [
+                [1.0, 0.0, 0.0, 1.0],
+                [-1.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 1.0],
+                [0.0, -1.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0, 1.0],
+                [0.0, 0.0, -1.0, 1.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn planes_pass_through_the_edges() {
+        let frustum = frustum();
+        let on_edges = [
+            [-1.0, 0.0, 4.0],
+            [1.0, 0.0, 4.0],
+            [0.0, -1.0, 4.0],
+            [0.0, 1.0, 4.0],
+            [0.0, 0.0, 4.0],
+            [0.0, 0.0, -95.0],
+        ];
+
+        for (plane, point) in frustum.planes().iter().zip(on_edges) {
+            assert!(Frustum::distance(plane, point).abs() < 1.0e-3, "{plane:?} misses {point:?}");
+            assert!(Frustum::distance(plane, [0.0; 3]) > 0.0, "{plane:?} excludes the origin");
+        }
+    }
+
+    #[test]
+    fn boxes() {
+        let frustum = frustum();
+
+        assert!(frustum.intersects_box(&cube([-1.0; 3], [1.0; 3])));
+        // Behind the camera, beyond the far plane and off to the side
+        assert!(!frustum.intersects_box(&cube([-1.0, -1.0, 10.0], [1.0, 1.0, 12.0])));
+        assert!(!frustum.intersects_box(&cube([-1.0, -1.0, -200.0], [1.0, 1.0, -150.0])));
+        assert!(!frustum.intersects_box(&cube([-100.0, -1.0, -1.0], [-90.0, 1.0, 1.0])));
+        // Straddling the left, near and far planes
+        assert!(frustum.intersects_box(&cube([-20.0, -1.0, -1.0], [0.0, 1.0, 1.0])));
+        assert!(frustum.intersects_box(&cube([-1.0, -1.0, 3.0], [1.0, 1.0, 6.0])));
+        assert!(frustum.intersects_box(&cube([-1.0, -1.0, -150.0], [1.0, 1.0, -50.0])));
+    }
+
+    #[test]
+    fn spheres() {
+        let frustum = frustum();
+
+        assert!(frustum.intersects_sphere(&sphere([0.0; 3], 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere([0.0, 0.0, 10.0], 1.0)));
+        assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, 10.0], 7.0)));
+        // The centre is 5 / sqrt(2) outside the left plane
+        assert!(!frustum.intersects_sphere(&sphere([-10.0, 0.0, 0.0], 3.0)));
+        assert!(frustum.intersects_sphere(&sphere([-10.0, 0.0, 0.0], 4.0)));
+    }
+}
@@ -0,0 +1,247 @@
+//! Limits and features of the OpenGL implementation, queried once the context exists.
+
+use std::{collections::HashSet, ffi::CStr, sync::OnceLock};
+
+static CAPABILITIES: OnceLock<GlCapabilities> = OnceLock::new();
+
+/// Units every OpenGL 3.3 implementation has, assumed before `Environment::new` queries them
+const GUARANTEED_TEXTURE_UNITS: u32 = 16;
+
+/// Units at the top of the range kept for textures the engine leaves bound, the ambient
+/// occlusion and image based lighting textures
+const RESERVED_TEXTURE_UNITS: u32 = 4;
+
+// From ARB_texture_filter_anisotropic, core only since OpenGL 4.6
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+/// What the driver supports, filled in by `Environment::new` and available from
+/// `Environment::capabilities` or `GlCapabilities::get`
+#[derive(Clone, Debug)]
+pub struct GlCapabilities {
+    version: (u32, u32),
+    version_string: String,
+    glsl_version: String,
+    vendor: String,
+    renderer: String,
+    max_texture_units: u32,
+    max_combined_texture_units: u32,
+    max_samples: u32,
+    max_uniform_block_size: usize,
+    max_anisotropy: f32,
+    extensions: HashSet<String>,
+}
+
+fn get_integer(parameter: u32) -> i32 {
+    let mut value = 0;
+    unsafe {
+        gl::GetIntegerv(parameter, &mut value);
+    }
+    value
+}
+
+fn get_string(parameter: u32) -> String {
+    let string = unsafe { gl::GetString(parameter) };
+    if string.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(string.cast()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl GlCapabilities {
+    /// Query the current context
+    fn query() -> Self {
+        let extension_count = get_integer(gl::NUM_EXTENSIONS).unsigned_abs();
+        let extensions: HashSet<String> = (0..extension_count)
+            .filter_map(|index| {
+                let name = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+                (!name.is_null()).then(|| {
+                    unsafe { CStr::from_ptr(name.cast()) }
+                        .to_string_lossy()
+                        .into_owned()
+                })
+            })
+            .collect();
+
+        let version = (
+            get_integer(gl::MAJOR_VERSION).unsigned_abs(),
+            get_integer(gl::MINOR_VERSION).unsigned_abs(),
+        );
+        let anisotropic = version >= (4, 6)
+            || extensions.contains("GL_ARB_texture_filter_anisotropic")
+            || extensions.contains("GL_EXT_texture_filter_anisotropic");
+        let max_anisotropy = if anisotropic {
+            let mut max = 1.0;
+            unsafe {
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+            }
+            max
+        } else {
+            1.0
+        };
+
+        Self {
+            version,
+            version_string: get_string(gl::VERSION),
+            glsl_version: get_string(gl::SHADING_LANGUAGE_VERSION),
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            max_texture_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS).unsigned_abs(),
+            max_combined_texture_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS)
+                .unsigned_abs(),
+            max_samples: get_integer(gl::MAX_SAMPLES).unsigned_abs(),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE).unsigned_abs() as usize,
+            max_anisotropy,
+            extensions,
+        }
+    }
+
+    /// Query the current context the first time, and return the same capabilities after
+    pub(crate) fn init() -> &'static Self {
+        CAPABILITIES.get_or_init(Self::query)
+    }
+
+    /// `None` before `Environment::new` has made a context
+    #[must_use]
+    #[inline]
+    pub fn get() -> Option<&'static Self> {
+        CAPABILITIES.get()
+    }
+
+    /// Texture units a fragment shader can sample, or the guaranteed 16 before there is a
+    /// context
+    pub(crate) fn texture_units() -> u32 {
+        Self::get().map_or(GUARANTEED_TEXTURE_UNITS, |capabilities| {
+            capabilities.max_texture_units
+        })
+    }
+
+    /// Whether buffers can have immutable storage, which persistently mapped ring buffers need.
+    /// False before there is a context.
+    pub(crate) fn buffer_storage() -> bool {
+        Self::get().is_some_and(|capabilities| {
+            capabilities.version_at_least(4, 4)
+                || capabilities.has_extension("GL_ARB_buffer_storage")
+        })
+    }
+
+    /// The unit `from_top` below the highest a fragment shader can sample, for textures the
+    /// engine keeps bound while materials reuse the low units. Counts down from the guaranteed
+    /// 16 before there is a context.
+    pub(crate) fn reserved_texture_unit(from_top: u32) -> u32 {
+        debug_assert!(from_top < RESERVED_TEXTURE_UNITS, "Unit {from_top} is not reserved");
+        Self::texture_units().saturating_sub(from_top + 1)
+    }
+
+    /// Units below the reserved ones, which `ShaderProgram::bind_textures` hands out in turn
+    pub(crate) fn unreserved_texture_units() -> u32 {
+        Self::texture_units().saturating_sub(RESERVED_TEXTURE_UNITS)
+    }
+
+    /// Major and minor version of the context
+    #[must_use]
+    #[inline]
+    pub const fn version(&self) -> (u32, u32) {
+        self.version
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        self.version.0 > major || (self.version.0 == major && self.version.1 >= minor)
+    }
+
+    /// The full version string, which often names the driver too
+    #[must_use]
+    #[inline]
+    pub fn version_string(&self) -> &str {
+        &self.version_string
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn glsl_version(&self) -> &str {
+        &self.glsl_version
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    /// Usually the name of the GPU
+    #[must_use]
+    #[inline]
+    pub fn renderer(&self) -> &str {
+        &self.renderer
+    }
+
+    /// Texture units a fragment shader can sample, and so the units textures are bound to
+    #[must_use]
+    #[inline]
+    pub const fn max_texture_units(&self) -> u32 {
+        self.max_texture_units
+    }
+
+    /// Textures that can be bound at once across every shader stage
+    #[must_use]
+    #[inline]
+    pub const fn max_combined_texture_units(&self) -> u32 {
+        self.max_combined_texture_units
+    }
+
+    /// Most samples per pixel of a multisampled framebuffer
+    #[must_use]
+    #[inline]
+    pub const fn max_samples(&self) -> u32 {
+        self.max_samples
+    }
+
+    /// Bytes of the largest uniform block
+    #[must_use]
+    #[inline]
+    pub const fn max_uniform_block_size(&self) -> usize {
+        self.max_uniform_block_size
+    }
+
+    /// Highest anisotropic filtering level, 1 without support for it
+    #[must_use]
+    #[inline]
+    pub const fn max_anisotropy(&self) -> f32 {
+        self.max_anisotropy
+    }
+
+    /// Whether the driver has an extension, named as in `GL_ARB_bindless_texture`
+    #[must_use]
+    #[inline]
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn extensions(&self) -> &HashSet<String> {
+        &self.extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_units_sit_above_the_unreserved_ones() {
+        // Without a context the guaranteed 16 units are assumed
+        let unreserved = GlCapabilities::unreserved_texture_units();
+        assert_eq!(unreserved, GUARANTEED_TEXTURE_UNITS - RESERVED_TEXTURE_UNITS);
+
+        let reserved: Vec<u32> = (0..RESERVED_TEXTURE_UNITS)
+            .map(GlCapabilities::reserved_texture_unit)
+            .collect();
+        assert_eq!(reserved, [15, 14, 13, 12]);
+        assert!(reserved.iter().all(|&unit| unit >= unreserved));
+    }
+}
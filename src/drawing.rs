@@ -1,2 +1,6 @@
+pub mod bloom;
+pub mod commands;
 pub mod draw;
 pub mod groups;
+pub mod post_process;
+pub mod ssao;
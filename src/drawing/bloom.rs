@@ -0,0 +1,213 @@
+use crate::{
+    buffers::{
+        framebuffer::{BufferColourType, FrameBuffer},
+        vertex_array::VertexArray,
+    },
+    drawing::post_process::screen_quad,
+    error_fmt, gl_state,
+    shader_program::ShaderProgram,
+    some_builder,
+    texture::Texture,
+    EngineError::FrameBufferErr,
+    Result,
+};
+
+// Must match the constants in `bloom.frag`
+const PREFILTER: i32 = 0;
+const DOWNSAMPLE: i32 = 1;
+const UPSAMPLE: i32 = 2;
+const COMPOSITE: i32 = 3;
+
+/// Glow around bright and emissive parts of the scene.
+///
+/// Fragments brighter than `threshold`, plus everything in the optional emission texture, are
+/// blurred through a chain of progressively smaller buffers and added back onto the scene scaled
+/// by `intensity`. Added to a `PostProcessChain` with `post_process::Builder::bloom`.
+pub struct Bloom {
+    threshold: f32,
+    soft_knee: f32,
+    intensity: f32,
+    radius: f32,
+    emission: Texture,
+    mips: Vec<(FrameBuffer, i32, i32)>,
+    quad: VertexArray,
+    shader: ShaderProgram,
+}
+
+impl Bloom {
+    #[must_use]
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    #[inline]
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    #[inline]
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Texture added to the bright pass regardless of `threshold`, usually the emission
+    /// attachment of the scene's `FrameBuffer`
+    #[inline]
+    pub fn set_emission(&mut self, emission: Texture) {
+        self.emission = emission;
+    }
+
+    fn draw_level(&mut self, level: usize, stage: i32, source: &Texture) -> Result<()> {
+        let (framebuffer, width, height) = &mut self.mips[level];
+        unsafe {
+            gl::Viewport(0, 0, *width, *height);
+        }
+
+        if stage == UPSAMPLE {
+            framebuffer.bind_no_clear();
+        } else {
+            framebuffer.bind();
+        }
+
+        self.shader.set_uniform_iv("stage", [stage])?;
+        self.shader
+            .bind_textures(vec![(source, "source"), (&self.emission, "emission")])?;
+        self.quad.draw();
+
+        Ok(())
+    }
+
+    /// Draw `scene` with bloom added into `target`
+    pub(crate) fn apply(&mut self, scene: &Texture, target: &mut FrameBuffer) -> Result<()> {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+
+        self.shader.use_program();
+        self.shader.set_uniform_fv("threshold", [self.threshold])?;
+        self.shader.set_uniform_fv("soft_knee", [self.soft_knee])?;
+        self.shader.set_uniform_fv("radius", [self.radius])?;
+        self.shader.set_uniform_fv("intensity", [self.intensity])?;
+
+        // Bright pass into the largest mip, then blur down the chain
+        self.draw_level(0, PREFILTER, scene)?;
+        for level in 1..self.mips.len() {
+            let source = self.mips[level - 1].0.get_colour()?;
+            self.draw_level(level, DOWNSAMPLE, &source)?;
+        }
+
+        // Accumulate back up the chain
+        gl_state::blend_func(gl::ONE, gl::ONE);
+        for level in (0..self.mips.len() - 1).rev() {
+            let source = self.mips[level + 1].0.get_colour()?;
+            self.draw_level(level, UPSAMPLE, &source)?;
+        }
+        unsafe {
+            gl_state::blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+
+        let bloom = self.mips[0].0.get_colour()?;
+        target.bind();
+        self.shader.set_uniform_iv("stage", [COMPOSITE])?;
+        self.shader
+            .bind_textures(vec![(scene, "source"), (&bloom, "bloom")])?;
+        self.quad.draw();
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Builder {
+    threshold: Option<f32>,
+    soft_knee: Option<f32>,
+    intensity: Option<f32>,
+    radius: Option<f32>,
+    levels: Option<usize>,
+    emission: Option<Texture>,
+    width: i32,
+    height: i32,
+}
+
+impl Builder {
+    some_builder!(threshold: f32);
+    some_builder!(
+        /// Fraction of `threshold` below it over which fragments fade into the bright pass
+        soft_knee: f32
+    );
+    some_builder!(intensity: f32);
+    some_builder!(
+        /// Spread of each upsampling step, in texels
+        radius: f32
+    );
+    some_builder!(
+        /// Number of times the bright pass is halved in size, more levels give a wider glow
+        levels: usize
+    );
+    some_builder!(emission: Texture);
+
+    /// Dimensions of the scene, normally those of the screen
+    #[must_use]
+    #[inline]
+    pub fn add_dims(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// # Errors
+    #[inline]
+    pub fn build(self) -> Result<Bloom> {
+        let levels = self.levels.unwrap_or(5);
+        if levels == 0 {
+            return Err(FrameBufferErr(error_fmt!(
+                bloom::Builder,
+                "Bloom requires at least one level"
+            )));
+        }
+
+        let mut mips = Vec::with_capacity(levels);
+        let (mut width, mut height) = (self.width, self.height);
+        for _ in 0..levels {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+
+            let framebuffer = FrameBuffer::builder()
+                .add_colour(BufferColourType::TexRgba16F)
+                .add_dims(width, height)
+                .build()?;
+            mips.push((framebuffer, width, height));
+        }
+
+        let shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/quad_vert.vert")
+            .add_fragment_shader("src/shaders/bloom.frag")
+            .build()?;
+
+        Ok(Bloom {
+            threshold: self.threshold.unwrap_or(1.0),
+            soft_knee: self.soft_knee.unwrap_or(0.5),
+            intensity: self.intensity.unwrap_or(1.0),
+            radius: self.radius.unwrap_or(1.0),
+            emission: self.emission.unwrap_or_else(Texture::blank),
+            mips,
+            quad: screen_quad()?,
+            shader,
+        })
+    }
+}
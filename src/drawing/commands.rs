@@ -0,0 +1,313 @@
+//! Draw lists recorded away from the OpenGL thread.
+//!
+//! `Model`s, `ShaderProgram`s and `InstanceBuffer`s own OpenGL objects and cannot leave the
+//! thread that made them. Registering them in `Resources` hands out plain handles instead, which
+//! worker threads record into `CommandBuffer`s that are `Send`. The OpenGL thread then checks
+//! and runs them with `Draw::execute`.
+
+use crate::{
+    buffers::instance_buffer::InstanceBuffer, error_fmt, linear_algebra::matrix::Matrix,
+    modelling::model::Model, shader_program::ShaderProgram, EngineError::MiscErr, Result,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Gives every `Resources` its own id, so that handles from one are rejected by the others
+static NEXT_RESOURCES_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A position in the lists of the `Resources` with id `resources`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Handle {
+    resources: usize,
+    index: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ModelHandle(Handle);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderHandle(Handle);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstancesHandle(Handle);
+
+/// The value of a uniform, set with the matching `ShaderProgram::set_uniform_*`
+#[derive(Clone, Copy, Debug)]
+pub enum Uniform {
+    Int(i32),
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4(Matrix<4, 4>),
+}
+
+impl Uniform {
+    /// The value `name` currently has in `shader_program`, as the same variant as `self`
+    pub(crate) fn get(&self, shader_program: &ShaderProgram, name: &str) -> Result<Self> {
+        Ok(match self {
+            Self::Int(_) => Self::Int(shader_program.uniform_iv::<1>(name)?[0]),
+            Self::Float(_) => Self::Float(shader_program.uniform_fv::<1>(name)?[0]),
+            Self::Vec2(_) => Self::Vec2(shader_program.uniform_fv(name)?),
+            Self::Vec3(_) => Self::Vec3(shader_program.uniform_fv(name)?),
+            Self::Vec4(_) => Self::Vec4(shader_program.uniform_fv(name)?),
+            Self::Mat4(_) => Self::Mat4(shader_program.uniform_mat4f(name)?),
+        })
+    }
+
+    pub(crate) fn set(&self, shader_program: &ShaderProgram, name: &str) -> Result<()> {
+        match *self {
+            Self::Int(value) => shader_program.set_uniform_iv(name, [value]),
+            Self::Float(value) => shader_program.set_uniform_fv(name, [value]),
+            Self::Vec2(value) => shader_program.set_uniform_fv(name, value),
+            Self::Vec3(value) => shader_program.set_uniform_fv(name, value),
+            Self::Vec4(value) => shader_program.set_uniform_fv(name, value),
+            Self::Mat4(value) => shader_program.set_uniform_mat4f(name, value),
+        }
+    }
+}
+
+/// One recorded draw of a model
+#[derive(Clone, Debug)]
+pub(crate) struct DrawCommand {
+    pub model: ModelHandle,
+    pub shader: ShaderHandle,
+    pub instances: Option<InstancesHandle>,
+    /// Replaces the model's own transform
+    pub transform: Option<Matrix<4, 4>>,
+    pub uniforms: Vec<(String, Uniform)>,
+}
+
+/// Draws recorded on any thread, to be run by `Draw::execute` on the OpenGL thread
+#[derive(Clone, Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<DrawCommand>,
+}
+
+// Recording on worker threads is the point of a command buffer
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<CommandBuffer>();
+};
+
+impl CommandBuffer {
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a draw of `model`, as `Draw::add_model`
+    #[inline]
+    pub fn draw(&mut self, model: ModelHandle, shader: ShaderHandle) {
+        self.draw_with(model, shader, None, Vec::new());
+    }
+
+    /// Record a draw of `model` placed by `transform` instead of its own position, orientation
+    /// and scale, e.g. to draw one model in several places
+    #[inline]
+    pub fn draw_at(&mut self, model: ModelHandle, shader: ShaderHandle, transform: Matrix<4, 4>) {
+        self.draw_with(model, shader, Some(transform), Vec::new());
+    }
+
+    /// Record a draw of `model`, placed by `transform` if given, that sets `uniforms` first and
+    /// restores their previous values afterwards. The model sets its own `model`, `instanced`,
+    /// `lod_fade` and `material` uniforms after these, so those are overwritten.
+    #[inline]
+    pub fn draw_with(
+        &mut self,
+        model: ModelHandle,
+        shader: ShaderHandle,
+        transform: Option<Matrix<4, 4>>,
+        uniforms: Vec<(String, Uniform)>,
+    ) {
+        self.commands.push(DrawCommand {
+            model,
+            shader,
+            instances: None,
+            transform,
+            uniforms,
+        });
+    }
+
+    /// Record an instanced draw of `model`, as `Draw::add_instanced`
+    #[inline]
+    pub fn draw_instanced(
+        &mut self,
+        model: ModelHandle,
+        instances: InstancesHandle,
+        shader: ShaderHandle,
+    ) {
+        self.commands.push(DrawCommand {
+            model,
+            shader,
+            instances: Some(instances),
+            transform: None,
+            uniforms: Vec::new(),
+        });
+    }
+
+    /// Move every command of `other` to the end of this buffer, e.g. to join the buffers of
+    /// several workers
+    #[inline]
+    pub fn append(&mut self, other: &mut Self) {
+        self.commands.append(&mut other.commands);
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub(crate) fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+}
+
+impl Extend<Self> for CommandBuffer {
+    #[inline]
+    fn extend<T: IntoIterator<Item = Self>>(&mut self, iter: T) {
+        for mut buffer in iter {
+            self.append(&mut buffer);
+        }
+    }
+}
+
+/// The models, shader programs and instance buffers that handles refer to, kept on the OpenGL
+/// thread. Handles only refer to the `Resources` that issued them.
+pub struct Resources<'a> {
+    id: usize,
+    models: Vec<&'a Model>,
+    shaders: Vec<&'a ShaderProgram>,
+    instances: Vec<&'a InstanceBuffer>,
+}
+
+impl Default for Resources<'_> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            id: NEXT_RESOURCES_ID.fetch_add(1, Ordering::Relaxed),
+            models: Vec::new(),
+            shaders: Vec::new(),
+            instances: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Resources<'a> {
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push<T>(id: usize, list: &mut Vec<&'a T>, item: &'a T) -> Handle {
+        list.push(item);
+        Handle {
+            resources: id,
+            index: list.len() - 1,
+        }
+    }
+
+    #[inline]
+    pub fn add_model(&mut self, model: &'a Model) -> ModelHandle {
+        ModelHandle(Self::push(self.id, &mut self.models, model))
+    }
+
+    #[inline]
+    pub fn add_shader(&mut self, shader_program: &'a ShaderProgram) -> ShaderHandle {
+        ShaderHandle(Self::push(self.id, &mut self.shaders, shader_program))
+    }
+
+    #[inline]
+    pub fn add_instances(&mut self, instances: &'a InstanceBuffer) -> InstancesHandle {
+        InstancesHandle(Self::push(self.id, &mut self.instances, instances))
+    }
+
+    fn get<T>(id: usize, list: &[&'a T], handle: Handle, kind: &str) -> Result<&'a T> {
+        if handle.resources != id {
+            return Err(MiscErr(error_fmt!(
+                commands::Resources,
+                "The {kind} handle was issued by other resources"
+            )));
+        }
+        list.get(handle.index).copied().ok_or_else(|| {
+            MiscErr(error_fmt!(
+                commands::Resources,
+                "No {kind} with handle {}, there are {}",
+                handle.index,
+                list.len()
+            ))
+        })
+    }
+
+    pub(crate) fn model(&self, handle: ModelHandle) -> Result<&'a Model> {
+        Self::get(self.id, &self.models, handle.0, "model")
+    }
+
+    pub(crate) fn shader(&self, handle: ShaderHandle) -> Result<&'a ShaderProgram> {
+        Self::get(self.id, &self.shaders, handle.0, "shader program")
+    }
+
+    pub(crate) fn instances(&self, handle: InstancesHandle) -> Result<&'a InstanceBuffer> {
+        Self::get(self.id, &self.instances, handle.0, "instance buffer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(resources: usize, index: usize) -> Handle {
+        Handle { resources, index }
+    }
+
+    #[test]
+    fn handles_only_fit_their_resources() {
+        let first = Resources::new();
+        let second = Resources::new();
+        assert_ne!(first.id, second.id);
+
+        let items = [&1, &2];
+        let get = |id, handle| Resources::get(id, &items, handle, "number");
+        assert_eq!(*get(first.id, handle(first.id, 1)).unwrap(), 2);
+        assert!(get(first.id, handle(second.id, 1)).is_err());
+        assert!(get(first.id, handle(first.id, 2)).is_err());
+    }
+
+    #[test]
+    fn buffers_record_in_order() {
+        let resources = Resources::new();
+        let model = ModelHandle(handle(resources.id, 0));
+        let shader = ShaderHandle(handle(resources.id, 0));
+        let instances = InstancesHandle(handle(resources.id, 0));
+        let transform = Matrix::<4, 4>::identity();
+
+        let mut first = CommandBuffer::new();
+        first.draw(model, shader);
+        first.draw_at(model, shader, transform);
+        let mut second = CommandBuffer::new();
+        second.draw_instanced(model, instances, shader);
+        second.draw_with(
+            model,
+            shader,
+            None,
+            vec![("tint".to_owned(), Uniform::Float(0.5))],
+        );
+
+        first.extend([second]);
+        assert_eq!(first.len(), 4);
+        let commands = first.commands();
+        assert!(commands[0].transform.is_none() && commands[0].instances.is_none());
+        let placed = commands[1].transform.unwrap();
+        assert_eq!(placed.col_major(), transform.col_major());
+        assert_eq!(commands[2].instances, Some(instances));
+        assert_eq!(commands[3].uniforms.len(), 1);
+    }
+}
@@ -5,18 +5,43 @@
 // Outline Groups -> Can be many and each have their own outline colour and width
 // Sky cube (only one)
 
+use std::ops;
+
 use crate::{
-    buffers::framebuffer::FrameBuffer, camera::Camera, modelling::model::Model, shader_program::ShaderProgram,
-    Result,
+    buffers::{framebuffer::FrameBuffer, instance_buffer::InstanceBuffer}, camera::Camera, modelling::model::Model, shader_program::ShaderProgram,
+    texture::Texture, Result,
+};
+
+use super::{
+    commands::{CommandBuffer, Resources},
+    groups::{ListModelGroup, ModelGroup, TempListLights},
+    post_process::PostProcessChain,
+    ssao::Ssao,
 };
 
-use super::groups::{ListModelGroup, TempListLights};
+/// How many of a `Draw`'s models were sent to the GPU and how many were skipped for being
+/// outside the camera's frustum
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStatistics {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl ops::AddAssign for DrawStatistics {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        self.drawn += rhs.drawn;
+        self.culled += rhs.culled;
+    }
+}
 
 pub struct Draw<'a> {
     framebuffer: &'a mut FrameBuffer,
     camera: Option<&'a Camera>,
     pub lights: Option<TempListLights<'a>>,
     opaque: ListModelGroup<'a>,
+    post_process: Option<(&'a mut PostProcessChain, Texture)>,
+    ambient_occlusion: Option<&'a mut Ssao>,
 }
 
 impl<'a> Draw<'a> {
@@ -31,6 +56,8 @@ impl<'a> Draw<'a> {
             camera: Some(camera),
             lights: Some(lights),
             opaque: ListModelGroup::new(),
+            post_process: None,
+            ambient_occlusion: None,
         }
     }
 
@@ -41,6 +68,8 @@ impl<'a> Draw<'a> {
             camera: None,
             lights: None,
             opaque: ListModelGroup::new(),
+            post_process: None,
+            ambient_occlusion: None,
         }
     }
 
@@ -49,24 +78,181 @@ impl<'a> Draw<'a> {
         self.opaque.push_simple(model, shader_program);
     }
 
+    /// Draw `model` once for every instance in `instances`, with one draw call per mesh
+    #[inline]
+    pub fn add_instanced(
+        &mut self,
+        model: &'a Model,
+        instances: &'a InstanceBuffer,
+        shader_program: &'a ShaderProgram,
+    ) {
+        self.opaque.push_instanced(model, instances, shader_program);
+    }
+
+    /// Add every draw recorded in `commands`, looking up their handles in `resources`. Nothing
+    /// is added if any handle is unknown.
+    /// # Errors
+    /// Returns an error if a handle does not belong to `resources`
+    #[inline]
+    pub fn execute(&mut self, commands: &CommandBuffer, resources: &Resources<'a>) -> Result<()> {
+        let groups = commands
+            .commands()
+            .iter()
+            .map(|command| {
+                Ok(ModelGroup {
+                    model: resources.model(command.model)?,
+                    shader_program: resources.shader(command.shader)?,
+                    instances: command
+                        .instances
+                        .map(|instances| resources.instances(instances))
+                        .transpose()?,
+                    transform: command.transform,
+                    uniforms: command.uniforms.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.opaque.extend(groups);
+        Ok(())
+    }
+
+    /// Fill the framebuffer by running `chain` over `input` instead of clearing it. Any models
+    /// added to this `Draw` are then drawn on top of the result.
+    #[inline]
+    pub fn post_process(&mut self, chain: &'a mut PostProcessChain, input: Texture) {
+        self.post_process = Some((chain, input));
+    }
+
+    /// Darken the ambient lighting of this `Draw`'s models with screen-space ambient occlusion.
+    /// Has no effect without a camera.
+    #[inline]
+    pub fn ambient_occlusion(&mut self, ssao: &'a mut Ssao) {
+        self.ambient_occlusion = Some(ssao);
+    }
+
+    /// Draw every model inside the camera's frustum. Models without bounds, instanced models
+    /// and models drawn without a camera are never culled. Opaque models are drawn first, sorted
+    /// by shader program, then material, then mesh, so that as little state as possible changes.
+    /// Models with a translucent material follow from the farthest to the nearest, so that each
+    /// blends over everything behind it.
+    /// # Errors
     #[inline]
-    pub fn draw(self) -> Result<()> {
-        self.framebuffer.bind();
+    pub fn draw(mut self) -> Result<DrawStatistics> {
+        let frustum = self.camera.map(Camera::frustum);
+        let visible: Vec<&ModelGroup> = self
+            .opaque
+            .as_vec()
+            .iter()
+            .filter(|group| {
+                group.instances.is_some()
+                    || frustum
+                        .zip(group.model.bounding_box_after(&group.model_matrix()))
+                        .is_none_or(|(frustum, bounds)| frustum.intersects_box(&bounds))
+            })
+            .collect();
 
-        for model in self.opaque.as_vec() {
-            if let Some(lightlist) = &self.lights {
-                lightlist.bind(model.shader_program)?;
+        let camera_position = self.camera.map(Camera::position);
+        let keys: Vec<_> = visible
+            .iter()
+            .map(|group| {
+                let distance = group.model.is_translucent().then(|| {
+                    camera_position.map_or(0.0, |position| {
+                        position.distance_squared(group.model.centre_after(&group.model_matrix()))
+                    })
+                });
+                ((group.shader_program.id(), group.model.sort_key()), distance)
+            })
+            .collect();
+        let visible: Vec<&ModelGroup> = draw_order(&keys)
+            .into_iter()
+            .map(|index| visible[index])
+            .collect();
+
+        let statistics = DrawStatistics {
+            drawn: visible.len(),
+            culled: self.opaque.as_vec().len() - visible.len(),
+        };
+
+        if let (Some(ssao), Some(camera)) = (&mut self.ambient_occlusion, self.camera) {
+            ssao.render(
+                camera,
+                visible
+                    .iter()
+                    .map(|group| (group.model, group.instances, group.model_matrix())),
+            )?;
+        }
+
+        if let Some((chain, input)) = self.post_process.take() {
+            chain.run(&input, self.framebuffer)?;
+        } else {
+            self.framebuffer.bind();
+        }
+
+        let mut current_program = None;
+        for model in visible {
+            // Uniforms shared by every model only need setting once per program
+            if current_program != Some(model.shader_program.id()) {
+                current_program = Some(model.shader_program.id());
+                self.bind_shared(model.shader_program)?;
             }
-            if let Some(camera) = self.camera {
-                model
-                    .shader_program
-                    .set_uniform_mat4f("projtimesview", camera.look_at())?;
-                model
-                    .shader_program
-                    .set_uniform_fv("camera_position", camera.position().into())?;
+            model.draw(self.camera)?;
+        }
+        Ok(statistics)
+    }
+
+    /// Set the lights, camera and ambient occlusion uniforms of `shader_program`
+    fn bind_shared(&self, shader_program: &ShaderProgram) -> Result<()> {
+        if let Some(lightlist) = &self.lights {
+            lightlist.bind(shader_program)?;
+        }
+        if let Some(camera) = self.camera {
+            shader_program.set_uniform_mat4f("projtimesview", camera.look_at())?;
+            shader_program.set_uniform_fv("camera_position", camera.position().into())?;
+            match &self.ambient_occlusion {
+                Some(ssao) => ssao.bind_to(shader_program)?,
+                None => shader_program.set_uniform_iv("use_ssao", [0])?,
             }
-            model.draw()?;
         }
+
         Ok(())
     }
 }
+
+/// Indices of `groups` in the order to draw them. Each has a state key and, if translucent, its
+/// distance from the camera. Opaque groups come first sorted by key, then translucent groups
+/// from the farthest to the nearest, keeping their recorded order where they tie.
+fn draw_order<K: Ord>(groups: &[(K, Option<f32>)]) -> Vec<usize> {
+    let (mut opaque, mut translucent): (Vec<usize>, Vec<usize>) =
+        (0..groups.len()).partition(|&index| groups[index].1.is_none());
+
+    opaque.sort_by(|&a, &b| groups[a].0.cmp(&groups[b].0));
+    let distance = |index: usize| groups[index].1.unwrap_or_default();
+    translucent.sort_by(|&a, &b| distance(b).total_cmp(&distance(a)));
+
+    opaque.extend(translucent);
+    opaque
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translucent_groups_follow_opaque_ones_back_to_front() {
+        let groups = [
+            (2, Some(1.0)),
+            (3, None),
+            (1, Some(9.0)),
+            (1, None),
+            (0, Some(4.0)),
+            (2, None),
+        ];
+        assert_eq!(draw_order(&groups), [3, 5, 1, 2, 4, 0]);
+    }
+
+    #[test]
+    fn translucent_groups_keep_their_order_without_a_camera() {
+        let groups = [(0, Some(0.0)), (5, None), (1, Some(0.0)), (0, None)];
+        assert_eq!(draw_order(&groups), [3, 1, 0, 2]);
+    }
+}
@@ -1,5 +1,12 @@
 use crate::{
-    lighting::{far_light::FarLight, point_light::PointLight, spot_light::SpotLight},
+    buffers::instance_buffer::InstanceBuffer,
+    camera::Camera,
+    drawing::commands::Uniform,
+    lighting::{
+        far_light::FarLight, image_based::ImageBasedLighting, point_light::PointLight,
+        spot_light::SpotLight,
+    },
+    linear_algebra::matrix::Matrix,
     modelling::model::Model,
     shader_program::ShaderProgram,
     Result,
@@ -8,13 +15,40 @@ use crate::{
 pub(crate) struct ModelGroup<'a> {
     pub model: &'a Model,
     pub shader_program: &'a ShaderProgram,
+    pub instances: Option<&'a InstanceBuffer>,
+    /// Replaces the model's own transform, from `CommandBuffer::draw_with`
+    pub transform: Option<Matrix<4, 4>>,
+    /// Set before the model draws and restored after, from `CommandBuffer::draw_with`
+    pub uniforms: Vec<(String, Uniform)>,
 }
 
 impl<'a> ModelGroup<'a> {
-    pub(crate) fn draw(&self) -> crate::Result<()> {
+    /// Where the model is drawn, its own transform unless the group replaces it
+    pub(crate) fn model_matrix(&self) -> Matrix<4, 4> {
+        self.transform.unwrap_or_else(|| self.model.model_matrix())
+    }
+
+    pub(crate) fn draw(&self, camera: Option<&Camera>) -> crate::Result<()> {
         self.shader_program.use_program();
-        self.model.draw(self.shader_program)?;
+        let previous = self
+            .uniforms
+            .iter()
+            .map(|(name, value)| Ok((name, value.get(self.shader_program, name)?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (name, value) in &self.uniforms {
+            value.set(self.shader_program, name)?;
+        }
+
+        self.model.draw_placed(
+            self.shader_program,
+            camera,
+            self.instances,
+            &self.model_matrix(),
+        )?;
 
+        for (name, value) in previous {
+            value.set(self.shader_program, name)?;
+        }
         Ok(())
     }
 }
@@ -34,6 +68,24 @@ impl<'a> ListModelGroup<'a> {
         self.push_group(ModelGroup {
             model,
             shader_program,
+            instances: None,
+            transform: None,
+            uniforms: Vec::new(),
+        });
+    }
+
+    pub fn push_instanced(
+        &mut self,
+        model: &'a Model,
+        instances: &'a InstanceBuffer,
+        shader_program: &'a ShaderProgram,
+    ) {
+        self.push_group(ModelGroup {
+            model,
+            shader_program,
+            instances: Some(instances),
+            transform: None,
+            uniforms: Vec::new(),
         });
     }
 
@@ -53,12 +105,26 @@ pub struct TempListLights<'a> {
     pub point: &'a PointLight,
     pub far: &'a FarLight,
     pub spot: &'a SpotLight,
+    pub environment: Option<&'a ImageBasedLighting>,
 }
 
 impl<'a> TempListLights<'a> {
     #[inline]
     pub fn new(point: &'a PointLight, far: &'a FarLight, spot: &'a SpotLight) -> Self {
-        TempListLights { point, far, spot }
+        TempListLights {
+            point,
+            far,
+            spot,
+            environment: None,
+        }
+    }
+
+    /// Light physically based materials from `environment` as well
+    #[must_use]
+    #[inline]
+    pub const fn with_environment(mut self, environment: &'a ImageBasedLighting) -> Self {
+        self.environment = Some(environment);
+        self
     }
 
     #[inline]
@@ -66,6 +132,10 @@ impl<'a> TempListLights<'a> {
         self.point.bind_to(shader_program, "light")?;
         self.spot.bind_to(shader_program, "torch")?;
         self.far.bind_to(shader_program, "sun")?;
+        match self.environment {
+            Some(environment) => environment.bind_to(shader_program)?,
+            None => ImageBasedLighting::unbind_from(shader_program)?,
+        }
 
         Ok(())
     }
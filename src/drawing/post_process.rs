@@ -0,0 +1,283 @@
+use crate::{
+    buffers::{
+        framebuffer::{BufferColourType, FrameBuffer},
+        vertex_array::VertexArray,
+    },
+    gl_state,
+    modelling::primitives::QuadBuilder,
+    shader_program::ShaderProgram,
+    texture::Texture,
+    Result,
+};
+
+use super::bloom::Bloom;
+
+/// A single full-screen pass of a `PostProcessChain`. Parameters are uploaded as uniforms each
+/// time the pass runs, so they may be changed freely between frames.
+#[derive(Clone, Debug)]
+pub enum PostEffect {
+    Greyscale,
+    Invert,
+    /// 3×3 convolution. `weights` are row-major starting at the top-left, `offset` is the
+    /// distance between samples in texels.
+    Kernel { weights: [f32; 9], offset: f32 },
+    /// Darken towards the corners. `radius` and `softness` are fractions of the centre-to-corner
+    /// distance, `strength` blends between no effect (0.0) and full black (1.0).
+    Vignette {
+        radius: f32,
+        softness: f32,
+        strength: f32,
+    },
+    /// Colour grading with a 2D strip LUT of `size` slices, each `size` × `size` texels.
+    /// Red increases to the right within a slice, green increases upwards and blue selects
+    /// the slice from left to right.
+    ColourGrade { lut: Texture, size: f32 },
+}
+
+impl PostEffect {
+    #[must_use]
+    #[inline]
+    pub const fn blur() -> Self {
+        #[rustfmt::skip]
+        let weights = [
+            1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+            2.0 / 16.0, 4.0 / 16.0, 2.0 / 16.0,
+            1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+        ];
+
+        Self::Kernel { weights, offset: 1.0 }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn sharpen() -> Self {
+        #[rustfmt::skip]
+        let weights = [
+             0.0, -1.0,  0.0,
+            -1.0,  5.0, -1.0,
+             0.0, -1.0,  0.0,
+        ];
+
+        Self::Kernel { weights, offset: 1.0 }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn edge_detection() -> Self {
+        #[rustfmt::skip]
+        let weights = [
+            1.0,  1.0, 1.0,
+            1.0, -8.0, 1.0,
+            1.0,  1.0, 1.0,
+        ];
+
+        Self::Kernel { weights, offset: 1.0 }
+    }
+
+    // Must match the constants in `post_process.frag`
+    const fn shader_index(&self) -> i32 {
+        match self {
+            Self::Greyscale => 1,
+            Self::Invert => 2,
+            Self::Kernel { .. } => 3,
+            Self::Vignette { .. } => 4,
+            Self::ColourGrade { .. } => 5,
+        }
+    }
+
+    fn bind_to(&self, shader: &ShaderProgram, source: &Texture) -> Result<()> {
+        shader.set_uniform_iv("effect", [self.shader_index()])?;
+
+        match self {
+            Self::Greyscale | Self::Invert => {
+                shader.bind_textures(vec![(source, "screen")])?;
+            }
+            Self::Kernel { weights, offset } => {
+                for (index, weight) in weights.iter().enumerate() {
+                    shader.set_uniform_fv(&format!("kernel[{index}]"), [*weight])?;
+                }
+                shader.set_uniform_fv("kernel_offset", [*offset])?;
+                shader.bind_textures(vec![(source, "screen")])?;
+            }
+            Self::Vignette {
+                radius,
+                softness,
+                strength,
+            } => {
+                shader.set_uniform_fv("vignette_radius", [*radius])?;
+                shader.set_uniform_fv("vignette_softness", [*softness])?;
+                shader.set_uniform_fv("vignette_strength", [*strength])?;
+                shader.bind_textures(vec![(source, "screen")])?;
+            }
+            Self::ColourGrade { lut, size } => {
+                shader.set_uniform_fv("lut_size", [*size])?;
+                shader.bind_textures(vec![(source, "screen"), (lut, "lut")])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An ordered list of `PostEffect`s applied to a texture.
+///
+/// Intermediate results ping-pong between two internal `FrameBuffer`s and the final effect is
+/// drawn into the target `FrameBuffer` (usually the default framebuffer) via `Draw::post_process`.
+/// An optional `Bloom` runs before the first effect.
+pub struct PostProcessChain {
+    bloom: Option<Bloom>,
+    effects: Vec<PostEffect>,
+    buffers: [FrameBuffer; 2],
+    quad: VertexArray,
+    shader: ShaderProgram,
+}
+
+impl PostProcessChain {
+    #[must_use]
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn effects(&self) -> &Vec<PostEffect> {
+        &self.effects
+    }
+
+    /// Edit, reorder, add or remove effects. Changes apply from the next frame.
+    #[inline]
+    pub fn effects_mut(&mut self) -> &mut Vec<PostEffect> {
+        &mut self.effects
+    }
+
+    #[inline]
+    pub fn push(&mut self, effect: PostEffect) {
+        self.effects.push(effect);
+    }
+
+    /// Adjust the bloom stage, if one was added
+    #[inline]
+    pub fn bloom_mut(&mut self) -> Option<&mut Bloom> {
+        self.bloom.as_mut()
+    }
+
+    /// Apply bloom and every effect in order to `input` and draw the result into `target`. With
+    /// no stages `input` is copied to `target` unchanged.
+    pub(crate) fn run(&mut self, input: &Texture, target: &mut FrameBuffer) -> Result<()> {
+        gl_state::set_capability(gl::CULL_FACE, false);
+
+        let passes = self.effects.len() + usize::from(self.bloom.is_some());
+        if passes == 0 {
+            target.bind();
+            self.shader.use_program();
+            self.shader.set_uniform_iv("effect", [0])?;
+            self.shader.bind_textures(vec![(input, "screen")])?;
+            self.quad.draw();
+            return Ok(());
+        }
+
+        let last = passes - 1;
+        let mut source = input.clone();
+        let mut index = 0;
+
+        if let Some(bloom) = &mut self.bloom {
+            let destination = if index == last {
+                &mut *target
+            } else {
+                &mut self.buffers[index % 2]
+            };
+
+            bloom.apply(&source, destination)?;
+
+            if index != last {
+                source = destination.get_colour()?;
+            }
+            index += 1;
+        }
+
+        self.shader.use_program();
+        for effect in &self.effects {
+            let destination = if index == last {
+                &mut *target
+            } else {
+                &mut self.buffers[index % 2]
+            };
+
+            destination.bind();
+            effect.bind_to(&self.shader, &source)?;
+            self.quad.draw();
+
+            if index != last {
+                source = destination.get_colour()?;
+            }
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// A quad covering the whole screen with "position" and "texture_coord" attributes, for use with
+/// `quad_vert.vert`
+pub(crate) fn screen_quad() -> Result<VertexArray> {
+    QuadBuilder::default().whole_screen().vertex_array()
+}
+
+#[derive(Default)]
+pub struct Builder {
+    bloom: Option<Bloom>,
+    effects: Vec<PostEffect>,
+    width: i32,
+    height: i32,
+}
+
+impl Builder {
+    #[must_use]
+    #[inline]
+    pub fn effect(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Run `bloom` before any other effect
+    #[must_use]
+    #[inline]
+    pub fn bloom(mut self, bloom: Bloom) -> Self {
+        self.bloom = Some(bloom);
+        self
+    }
+
+    /// Dimensions of the intermediate buffers, normally those of the screen
+    #[must_use]
+    #[inline]
+    pub fn add_dims(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// # Errors
+    #[inline]
+    pub fn build(self) -> Result<PostProcessChain> {
+        let buffer = || {
+            FrameBuffer::builder()
+                .add_colour(BufferColourType::TexRgba16F)
+                .add_dims(self.width, self.height)
+                .build()
+        };
+
+        let shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/quad_vert.vert")
+            .add_fragment_shader("src/shaders/post_process.frag")
+            .build()?;
+
+        Ok(PostProcessChain {
+            bloom: self.bloom,
+            effects: self.effects,
+            buffers: [buffer()?, buffer()?],
+            quad: screen_quad()?,
+            shader,
+        })
+    }
+}
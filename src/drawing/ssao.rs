@@ -0,0 +1,336 @@
+use crate::{
+    buffers::{
+        framebuffer::{BufferColourType, FrameBuffer},
+        instance_buffer::InstanceBuffer,
+        vertex_array::VertexArray,
+    },
+    camera::Camera,
+    capabilities::GlCapabilities,
+    drawing::post_process::screen_quad,
+    error_fmt, gl_state,
+    linear_algebra::matrix::Matrix,
+    modelling::model::Model,
+    shader_program::ShaderProgram,
+    texture::{sampler::Filter, Texture},
+    EngineError::ShaderErr,
+    Result,
+};
+
+/// Must match `MAX_KERNEL_SIZE` in `ssao.frag`
+pub const MAX_KERNEL_SIZE: usize = 64;
+
+/// The occlusion texture is kept on the highest fragment texture unit so that material textures
+/// bound on the low units never displace it
+fn ssao_texture_unit() -> u32 {
+    GlCapabilities::reserved_texture_unit(0)
+}
+
+const NOISE_SIZE: u32 = 4;
+
+/// Small deterministic generator for the sample kernel and noise, quality is not a concern
+struct XorShift(u32);
+
+impl XorShift {
+    /// Uniform in `[0.0, 1.0]`
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        #[expect(clippy::cast_precision_loss)]
+        (self.0 as f32 / u32::MAX as f32)
+    }
+}
+
+/// Screen-space ambient occlusion.
+///
+/// Attached to a `Draw` with `Draw::ambient_occlusion`, every opaque model is first drawn into a
+/// view-space position and normal buffer. Each fragment then tests `kernel_size` points in a
+/// hemisphere of `radius` about its normal against that buffer, the result is box blurred over
+/// `2 * blur_size + 1` texels and multiplies the ambient term of every light.
+pub struct Ssao {
+    kernel_size: usize,
+    radius: f32,
+    bias: f32,
+    power: f32,
+    blur_size: i32,
+    noise: Texture,
+    geometry: FrameBuffer,
+    occlusion: FrameBuffer,
+    blurred: FrameBuffer,
+    quad: VertexArray,
+    geometry_shader: ShaderProgram,
+    ssao_shader: ShaderProgram,
+    blur_shader: ShaderProgram,
+}
+
+impl Ssao {
+    #[must_use]
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn kernel_size(&self) -> usize {
+        self.kernel_size
+    }
+
+    /// Regenerate the sample kernel with `kernel_size` points
+    /// # Errors
+    /// Returns an error if `kernel_size` is zero or greater than `MAX_KERNEL_SIZE`
+    #[inline]
+    pub fn set_kernel_size(&mut self, kernel_size: usize) -> Result<()> {
+        upload_kernel(&self.ssao_shader, kernel_size)?;
+        self.kernel_size = kernel_size;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    #[inline]
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    /// Exponent applied to the final occlusion, greater than 1.0 darkens creases further
+    #[inline]
+    pub fn set_power(&mut self, power: f32) {
+        self.power = power;
+    }
+
+    /// Blur `blur_size` texels either side of each fragment, 0 disables the blur
+    #[inline]
+    pub fn set_blur_size(&mut self, blur_size: i32) {
+        self.blur_size = blur_size;
+    }
+
+    /// The most recent blurred occlusion, 1.0 is unoccluded
+    /// # Errors
+    #[inline]
+    pub fn occlusion(&self) -> Result<Texture> {
+        self.blurred.get_colour()
+    }
+
+    /// Draw `models`, instanced or not and placed by their matrices, as seen by `camera` and
+    /// compute their occlusion
+    pub(crate) fn render<'a>(
+        &mut self,
+        camera: &Camera,
+        models: impl Iterator<Item = (&'a Model, Option<&'a InstanceBuffer>, Matrix<4, 4>)>,
+    ) -> Result<()> {
+        self.geometry.bind();
+        self.geometry_shader.use_program();
+        self.geometry_shader.set_uniform_mat4f("view", camera.view())?;
+        self.geometry_shader
+            .set_uniform_mat4f("projection", camera.projection())?;
+        for (model, instances, model_matrix) in models {
+            model.draw_geometry(&self.geometry_shader, instances, camera, &model_matrix)?;
+        }
+
+        gl_state::set_capability(gl::CULL_FACE, false);
+
+        self.occlusion.bind();
+        self.ssao_shader.use_program();
+        self.ssao_shader
+            .set_uniform_mat4f("projection", camera.projection())?;
+        self.ssao_shader.set_uniform_fv("radius", [self.radius])?;
+        self.ssao_shader.set_uniform_fv("bias", [self.bias])?;
+        self.ssao_shader.set_uniform_fv("power", [self.power])?;
+        self.ssao_shader.bind_textures(vec![
+            (&self.geometry.get_colour_attachment(0)?, "position"),
+            (&self.geometry.get_colour_attachment(1)?, "normal"),
+            (&self.noise, "noise"),
+        ])?;
+        self.quad.draw();
+
+        self.blurred.bind();
+        self.blur_shader.use_program();
+        self.blur_shader
+            .set_uniform_iv("blur_size", [self.blur_size.max(0)])?;
+        self.blur_shader
+            .bind_textures(vec![(&self.occlusion.get_colour()?, "occlusion")])?;
+        self.quad.draw();
+
+        Ok(())
+    }
+
+    /// Make the occlusion available to `shader` as `uniform sampler2D ssao`
+    pub(crate) fn bind_to(&self, shader: &ShaderProgram) -> Result<()> {
+        shader.set_uniform_iv("use_ssao", [1])?;
+        shader.bind_texture_to_unit(&self.blurred.get_colour()?, "ssao", ssao_texture_unit())
+    }
+}
+
+/// Upload a hemisphere of `kernel_size` sample points, clustered towards the origin
+fn upload_kernel(shader: &ShaderProgram, kernel_size: usize) -> Result<()> {
+    if kernel_size == 0 || kernel_size > MAX_KERNEL_SIZE {
+        return Err(ShaderErr(error_fmt!(
+            ssao::Ssao,
+            "Kernel size must be between 1 and {MAX_KERNEL_SIZE}, not {kernel_size}"
+        )));
+    }
+
+    let mut random = XorShift(0x9E37_79B9);
+    shader.use_program();
+
+    for index in 0..kernel_size {
+        let sample = [
+            random.next().mul_add(2.0, -1.0),
+            random.next().mul_add(2.0, -1.0),
+            random.next(),
+        ];
+        let length = sample.iter().map(|x| x * x).sum::<f32>().sqrt().max(f32::EPSILON);
+
+        #[expect(clippy::cast_precision_loss)]
+        let fraction = index as f32 / kernel_size as f32;
+        let scale = (fraction * fraction).mul_add(0.9, 0.1) * random.next() / length;
+
+        shader.set_uniform_fv(&format!("samples[{index}]"), sample.map(|x| x * scale))?;
+    }
+
+    shader.set_uniform_iv(
+        "kernel_size",
+        [kernel_size.try_into().map_err(|_| {
+            ShaderErr(error_fmt!(ssao::Ssao, "Kernel size exceeds i32"))
+        })?],
+    )
+}
+
+#[derive(Default)]
+pub struct Builder {
+    kernel_size: Option<usize>,
+    radius: Option<f32>,
+    bias: Option<f32>,
+    power: Option<f32>,
+    blur_size: Option<i32>,
+    width: i32,
+    height: i32,
+}
+
+impl Builder {
+    /// Number of samples per fragment, at most `MAX_KERNEL_SIZE`
+    #[must_use]
+    #[inline]
+    pub fn kernel_size(mut self, kernel_size: usize) -> Self {
+        self.kernel_size = Some(kernel_size);
+        self
+    }
+
+    /// World-space distance within which geometry occludes a fragment
+    #[must_use]
+    #[inline]
+    pub fn radius(mut self, radius: f32) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+
+    /// Depth tolerance that stops flat surfaces occluding themselves
+    #[must_use]
+    #[inline]
+    pub fn bias(mut self, bias: f32) -> Self {
+        self.bias = Some(bias);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn power(mut self, power: f32) -> Self {
+        self.power = Some(power);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn blur_size(mut self, blur_size: i32) -> Self {
+        self.blur_size = Some(blur_size);
+        self
+    }
+
+    /// Dimensions of the `FrameBuffer` the `Draw` renders to
+    #[must_use]
+    #[inline]
+    pub fn add_dims(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// # Errors
+    #[inline]
+    pub fn build(self) -> Result<Ssao> {
+        let geometry = FrameBuffer::builder()
+            .add_colour(BufferColourType::TexRgba16F)
+            .add_colour(BufferColourType::TexRgba16F)
+            .add_depth()
+            .add_dims(self.width, self.height)
+            .build()?;
+
+        let occlusion = FrameBuffer::builder()
+            .add_colour(BufferColourType::TexRgb)
+            .add_dims(self.width, self.height)
+            .build()?;
+
+        let blurred = FrameBuffer::builder()
+            .add_colour(BufferColourType::TexRgb)
+            .add_dims(self.width, self.height)
+            .build()?;
+
+        let noise = {
+            let mut random = XorShift(0x2545_F491);
+            let mut image = image::Rgba32FImage::new(NOISE_SIZE, NOISE_SIZE);
+            for pixel in image.pixels_mut() {
+                pixel.0 = [
+                    random.next().mul_add(2.0, -1.0),
+                    random.next().mul_add(2.0, -1.0),
+                    0.0,
+                    1.0,
+                ];
+            }
+
+            Texture::builder()
+                .image_data(image::DynamicImage::ImageRgba32F(image))
+                .not_normalised()
+                .min_filter(Filter::Nearest)
+                .mag_filter(Filter::Nearest)
+                .build()?
+        };
+
+        let geometry_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/ssao_geometry.vert")
+            .add_fragment_shader("src/shaders/ssao_geometry.frag")
+            .build()?;
+
+        let ssao_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/quad_vert.vert")
+            .add_fragment_shader("src/shaders/ssao.frag")
+            .build()?;
+
+        let blur_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/quad_vert.vert")
+            .add_fragment_shader("src/shaders/ssao_blur.frag")
+            .build()?;
+
+        let kernel_size = self.kernel_size.unwrap_or(32);
+        upload_kernel(&ssao_shader, kernel_size)?;
+
+        Ok(Ssao {
+            kernel_size,
+            radius: self.radius.unwrap_or(0.5),
+            bias: self.bias.unwrap_or(0.025),
+            power: self.power.unwrap_or(1.0),
+            blur_size: self.blur_size.unwrap_or(2),
+            noise,
+            geometry,
+            occlusion,
+            blurred,
+            quad: screen_quad()?,
+            geometry_shader,
+            ssao_shader,
+            blur_shader,
+        })
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 use glfw::{fail_on_errors, Context};
 
 use crate::{
-    buffers::framebuffer::FrameBuffer, drawing::draw::Draw, global_state::GlobalState,
+    buffers::framebuffer::FrameBuffer,
+    capabilities::GlCapabilities,
+    drawing::draw::{Draw, DrawStatistics},
+    gl_state, global_state::GlobalState,
     input::keyboard::Keyboard, input::mouse::Mouse, window::Window, EngineError, Result,
 };
 
@@ -61,6 +64,7 @@ pub struct Environment<G: GlobalState> {
     mouse: Mouse,
     keyboard: Keyboard,
     default_framebuffer: FrameBuffer,
+    capabilities: &'static GlCapabilities,
 
     old_frame: f64,
     frametime: f32,
@@ -90,6 +94,7 @@ impl<G: GlobalState> Environment<G> {
         glfw.window_hint(glfw::WindowHint::OpenGlProfile(
             glfw::OpenGlProfileHint::Core,
         ));
+        glfw.window_hint(glfw::WindowHint::SRgbCapable(true));
 
         // Create a windowed mode window and its OpenGL context
         let (mut internal_window, events) = glfw
@@ -109,14 +114,18 @@ impl<G: GlobalState> Environment<G> {
         internal_window.set_key_polling(true);
 
         gl::load_with(|s| glfw.get_proc_address_raw(s));
+        let capabilities = GlCapabilities::init();
         // Configure glfw
 
         unsafe {
             gl::Enable(gl::DEBUG_OUTPUT);
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            gl::Enable(gl::STENCIL_TEST);
-            gl::Enable(gl::DEPTH_TEST);
+            gl_state::set_capability(gl::BLEND, true);
+            gl_state::blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl_state::set_capability(gl::STENCIL_TEST, true);
+            gl_state::set_capability(gl::DEPTH_TEST, true);
+            // Shaders output linear colour, encoded to sRGB when written to the screen or an
+            // sRGB attachment. Linear attachments are unaffected.
+            gl_state::set_capability(gl::FRAMEBUFFER_SRGB, true);
 
             gl::DebugMessageCallback(Some(debug_callback), ptr::null_mut());
         };
@@ -133,6 +142,7 @@ impl<G: GlobalState> Environment<G> {
             _events: events,
             global_state: None,
             default_framebuffer,
+            capabilities,
 
             mouse,
             keyboard,
@@ -162,6 +172,21 @@ impl<G: GlobalState> Environment<G> {
         self.global_state.as_mut().unwrap()
     }
 
+    /// Limits and extensions of the driver, e.g. for `GlobalState::new` to leave out features
+    /// it lacks
+    #[must_use]
+    #[inline]
+    pub const fn capabilities(&self) -> &'static GlCapabilities {
+        self.capabilities
+    }
+
+    /// Whether linear colours are encoded to sRGB when written to the screen and to sRGB
+    /// attachments, on by default. Turn it off if shaders apply their own gamma correction.
+    #[inline]
+    pub fn set_srgb_output(&mut self, enabled: bool) {
+        gl_state::set_capability(gl::FRAMEBUFFER_SRGB, enabled);
+    }
+
     #[inline]
     pub fn get_screendims(&self) -> (i32, i32) {
         self.window.get_framebuffer_size()
@@ -184,8 +209,13 @@ impl<G: GlobalState> Environment<G> {
         let mut frame_iter = self.iter();
         while let Some(to_draw) = frame_iter.next() {
             // Begin rendering code
+            let mut statistics = DrawStatistics::default();
             for draw in to_draw {
-                draw.draw()?;
+                statistics += draw.draw()?;
+            }
+
+            if let Some(state) = &mut frame_iter.env.global_state {
+                state.frame_statistics(statistics);
             }
         }
 
@@ -0,0 +1,234 @@
+//! A cache of the OpenGL state the engine changes most often.
+//!
+//! Binding something that is already bound costs nothing. Anything changed with raw `gl` calls
+//! must go through here or be followed by `invalidate`, otherwise later binds may be skipped.
+
+use std::cell::RefCell;
+
+/// Texture units tracked, the 16 every driver has. Binds to higher units are always made.
+const TEXTURE_UNITS: usize = 16;
+
+/// Capabilities tracked by `set_capability`, any other capability is always changed
+const CAPABILITIES: [u32; 6] = [
+    gl::BLEND,
+    gl::CULL_FACE,
+    gl::DEPTH_TEST,
+    gl::FRAMEBUFFER_SRGB,
+    gl::PRIMITIVE_RESTART,
+    gl::STENCIL_TEST,
+];
+
+/// What a material last set in a shader program, see `material_changed`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MaterialKey {
+    pub textures: [u32; 8],
+    pub shininess: f32,
+    pub height_scale: f32,
+}
+
+/// `None` wherever the state is unknown, and the next change is always made
+#[derive(Default)]
+struct Cache {
+    program: Option<u32>,
+    vertex_array: Option<u32>,
+    active_unit: Option<u32>,
+    /// Target and texture last bound to each unit
+    textures: [Option<(u32, u32)>; TEXTURE_UNITS],
+    /// Sampler object bound to each unit, 0 for the texture's own settings
+    samplers: [Option<u32>; TEXTURE_UNITS],
+    capabilities: [Option<bool>; CAPABILITIES.len()],
+    blend_func: Option<(u32, u32)>,
+    /// Shader program, uniform name and the material whose uniforms it holds
+    materials: Vec<(u32, String, MaterialKey)>,
+}
+
+thread_local! {
+    // OpenGL contexts are current on one thread, so each thread keeps its own cache
+    static CACHE: RefCell<Cache> = RefCell::default();
+}
+
+/// Forget everything cached, for after changing state with raw `gl` calls
+#[inline]
+pub fn invalidate() {
+    CACHE.with_borrow_mut(|cache| *cache = Cache::default());
+}
+
+pub(crate) fn use_program(id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.program != Some(id) {
+            unsafe {
+                gl::UseProgram(id);
+            }
+            cache.program = Some(id);
+        }
+    });
+}
+
+pub(crate) fn bind_vertex_array(id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.vertex_array != Some(id) {
+            unsafe {
+                gl::BindVertexArray(id);
+            }
+            cache.vertex_array = Some(id);
+        }
+    });
+}
+
+/// Bind `id` to `target` on the active texture unit, e.g. while creating a texture
+pub(crate) fn bind_texture(target: u32, id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        unsafe {
+            gl::BindTexture(target, id);
+        }
+        match cache.active_unit {
+            Some(unit) => {
+                if let Some(bound) = cache.textures.get_mut(unit as usize) {
+                    *bound = Some((target, id));
+                }
+            }
+            // Whichever unit that was is no longer known
+            None => cache.textures = [None; TEXTURE_UNITS],
+        }
+    });
+}
+
+/// Bind `id` to `target` on `unit`
+pub(crate) fn bind_texture_to_unit(unit: u32, target: u32, id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.textures.get(unit as usize) == Some(&Some((target, id))) {
+            return;
+        }
+
+        unsafe {
+            if cache.active_unit != Some(unit) {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                cache.active_unit = Some(unit);
+            }
+            gl::BindTexture(target, id);
+        }
+        if let Some(bound) = cache.textures.get_mut(unit as usize) {
+            *bound = Some((target, id));
+        }
+    });
+}
+
+/// Bind sampler `id` to `unit`, or 0 to use the texture's own settings
+pub(crate) fn bind_sampler(unit: u32, id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.samplers.get(unit as usize) == Some(&Some(id)) {
+            return;
+        }
+
+        unsafe {
+            gl::BindSampler(unit, id);
+        }
+        if let Some(bound) = cache.samplers.get_mut(unit as usize) {
+            *bound = Some(id);
+        }
+    });
+}
+
+/// Enable or disable `capability`, such as `gl::CULL_FACE`
+pub(crate) fn set_capability(capability: u32, enabled: bool) {
+    CACHE.with_borrow_mut(|cache| {
+        let index = CAPABILITIES.iter().position(|&known| known == capability);
+        if index.is_some_and(|index| cache.capabilities[index] == Some(enabled)) {
+            return;
+        }
+
+        unsafe {
+            if enabled {
+                gl::Enable(capability);
+            } else {
+                gl::Disable(capability);
+            }
+        }
+        if let Some(index) = index {
+            cache.capabilities[index] = Some(enabled);
+        }
+    });
+}
+
+pub(crate) fn blend_func(source: u32, destination: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.blend_func != Some((source, destination)) {
+            unsafe {
+                gl::BlendFunc(source, destination);
+            }
+            cache.blend_func = Some((source, destination));
+        }
+    });
+}
+
+/// Whether the uniforms under `name` in `program` need setting for the material described by
+/// `key`, recording it as set if so
+pub(crate) fn material_changed(program: u32, name: &str, key: MaterialKey) -> bool {
+    CACHE.with_borrow_mut(|cache| {
+        let entry = cache
+            .materials
+            .iter_mut()
+            .find(|(entry_program, entry_name, _)| *entry_program == program && entry_name == name);
+
+        match entry {
+            Some((_, _, current)) if *current == key => false,
+            Some((_, _, current)) => {
+                *current = key;
+                true
+            }
+            None => {
+                cache.materials.push((program, name.to_owned(), key));
+                true
+            }
+        }
+    })
+}
+
+/// Forget the uniforms recorded for `program`, e.g. after setting them without `Material`
+pub(crate) fn forget_materials(program: u32) {
+    CACHE.with_borrow_mut(|cache| cache.materials.retain(|(entry, _, _)| *entry != program));
+}
+
+/// Called when a program is deleted, as its id may be reused
+pub(crate) fn forget_program(id: u32) {
+    forget_materials(id);
+    CACHE.with_borrow_mut(|cache| {
+        if cache.program == Some(id) {
+            cache.program = None;
+        }
+    });
+}
+
+/// Called when a vertex array is deleted, as its id may be reused
+pub(crate) fn forget_vertex_array(id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.vertex_array == Some(id) {
+            cache.vertex_array = None;
+        }
+    });
+}
+
+/// Called when a texture is deleted, as its id may be reused
+pub(crate) fn forget_texture(id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        for bound in &mut cache.textures {
+            if bound.is_some_and(|(_, bound_id)| bound_id == id) {
+                *bound = None;
+            }
+        }
+        cache
+            .materials
+            .retain(|(_, _, key)| !key.textures.contains(&id));
+    });
+}
+
+/// Called when a sampler is deleted, as its id may be reused
+pub(crate) fn forget_sampler(id: u32) {
+    CACHE.with_borrow_mut(|cache| {
+        for bound in &mut cache.samplers {
+            if *bound == Some(id) {
+                *bound = None;
+            }
+        }
+    });
+}
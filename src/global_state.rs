@@ -1,7 +1,9 @@
 pub use glfw::{Action::*, Key::*, WindowEvent::Key};
 
 use crate::{
-    buffers::framebuffer::FrameBuffer, drawing::draw::Draw, environment::Environment,
+    buffers::framebuffer::FrameBuffer,
+    drawing::draw::{Draw, DrawStatistics},
+    environment::Environment,
     input::keyboard::Keyboard, input::mouse::Mouse, window::Window, Result,
 };
 
@@ -16,6 +18,12 @@ pub trait GlobalState: Sized {
         time: f32,
     ) -> Vec<Draw<'b>>;
 
+    /// Called once the OpenGL context exists, so `Environment::capabilities` can be checked to
+    /// leave out features the driver lacks
     /// # Errors
     fn new(evironment: &Environment<Self>) -> Result<Self>;
+
+    /// Called by `Environment::run` after every frame with the totals of all its `Draw`s
+    #[inline]
+    fn frame_statistics(&mut self, _statistics: DrawStatistics) {}
 }
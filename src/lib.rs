@@ -26,21 +26,28 @@
 #![expect(clippy::must_use_candidate)]
 #![expect(clippy::single_call_fn)]
 
+pub mod assets;
 pub mod buffers;
 pub mod camera;
+pub mod capabilities;
 pub mod colour;
 pub mod drawing;
 pub mod environment;
+pub mod gl_state;
 pub mod global_state;
 pub mod input;
 pub mod lighting;
 pub mod linear_algebra;
+pub mod loading;
 pub mod material;
 pub mod modelling;
 pub mod shader_program;
 pub mod texture;
 pub mod window;
 
+#[cfg(test)]
+mod test_util;
+
 #[non_exhaustive]
 #[derive(Debug, Clone)]
 pub enum EngineError {
@@ -90,7 +97,8 @@ macro_rules! error_fmt {
 
 #[macro_export]
 macro_rules! some_builder {
-    ($name:ident: $type:ty) => {
+    ($(#[$attr:meta])* $name:ident: $type:ty) => {
+        $(#[$attr])*
         #[must_use]
         #[inline]
         pub fn $name(mut self, $name: $type) -> Self {
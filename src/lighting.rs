@@ -1,3 +1,4 @@
 pub mod far_light;
+pub mod image_based;
 pub mod point_light;
 pub mod spot_light;
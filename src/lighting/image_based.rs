@@ -0,0 +1,357 @@
+use crate::{
+    buffers::vertex_array::VertexArray,
+    capabilities::GlCapabilities,
+    drawing::post_process::screen_quad,
+    error_fmt, gl_state,
+    linear_algebra::{matrix::Matrix, vector::Vector},
+    shader_program::ShaderProgram,
+    texture::Texture,
+    EngineError::TextureErr,
+    Result,
+};
+
+// Kept on the units just below the occlusion texture's, the highest, so that material textures
+// never displace them
+fn irradiance_texture_unit() -> u32 {
+    GlCapabilities::reserved_texture_unit(3)
+}
+
+fn prefiltered_texture_unit() -> u32 {
+    GlCapabilities::reserved_texture_unit(2)
+}
+
+fn brdf_texture_unit() -> u32 {
+    GlCapabilities::reserved_texture_unit(1)
+}
+
+/// Ambient lighting from the surroundings for `pbr.frag`.
+///
+/// An equirectangular HDR image is projected onto a cube map, which is then convolved into an
+/// irradiance map for diffuse light and a mip chain of progressively rougher reflections for
+/// specular light. Together with a lookup table of the split-sum BRDF these replace the flat
+/// ambient colour of each light. Attached to a `Draw` with `TempListLights::with_environment`.
+#[derive(Debug)]
+pub struct ImageBasedLighting {
+    environment: Texture,
+    irradiance: Texture,
+    prefiltered: Texture,
+    brdf_lut: Texture,
+    prefilter_levels: i32,
+    intensity: f32,
+}
+
+impl ImageBasedLighting {
+    #[must_use]
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// The unconvolved environment cube map, e.g. for drawing a sky box
+    #[must_use]
+    #[inline]
+    pub fn environment(&self) -> Texture {
+        self.environment.clone()
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Scale of all light coming from the environment
+    #[inline]
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Make the environment available to `shader` with `use_ibl` set
+    pub(crate) fn bind_to(&self, shader: &ShaderProgram) -> Result<()> {
+        shader.set_uniform_iv("use_ibl", [1])?;
+        shader.set_uniform_fv("ibl_intensity", [self.intensity])?;
+        #[expect(clippy::cast_precision_loss)]
+        shader.set_uniform_fv("max_reflection_lod", [(self.prefilter_levels - 1) as f32])?;
+
+        shader.bind_texture_to_unit(&self.irradiance, "irradiance_map", irradiance_texture_unit())?;
+        shader.bind_texture_to_unit(
+            &self.prefiltered,
+            "prefiltered_map",
+            prefiltered_texture_unit(),
+        )?;
+        shader.bind_texture_to_unit(&self.brdf_lut, "brdf_lut", brdf_texture_unit())
+    }
+
+    /// Turn image based lighting off for `shader`. The cube map samplers are still pointed at
+    /// their own units, as a `samplerCube` sharing unit 0 with a `sampler2D` fails to draw.
+    pub(crate) fn unbind_from(shader: &ShaderProgram) -> Result<()> {
+        shader.set_uniform_iv("use_ibl", [0])?;
+        #[expect(clippy::cast_possible_wrap)]
+        {
+            shader.set_uniform_iv("irradiance_map", [irradiance_texture_unit() as i32])?;
+            shader.set_uniform_iv("prefiltered_map", [prefiltered_texture_unit() as i32])?;
+            shader.set_uniform_iv("brdf_lut", [brdf_texture_unit() as i32])?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the inside of a unit cube onto each face of a cube map texture in turn
+struct Capture {
+    framebuffer: u32,
+    cube: VertexArray,
+}
+
+impl Capture {
+    fn new() -> Result<Self> {
+        let mut framebuffer = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+        }
+
+        Ok(Self {
+            framebuffer,
+            cube: VertexArray::cube(2.0)?,
+        })
+    }
+
+    /// Draw every face of mip `level` of `target`, which is `size` texels square at that level
+    fn render(
+        &self,
+        shader: &ShaderProgram,
+        target: &Texture,
+        level: i32,
+        size: i32,
+    ) -> Result<()> {
+        #[rustfmt::skip]
+        let faces: [([f32; 3], [f32; 3]); 6] = [
+            ([ 1.0,  0.0,  0.0], [0.0, -1.0,  0.0]),
+            ([-1.0,  0.0,  0.0], [0.0, -1.0,  0.0]),
+            ([ 0.0,  1.0,  0.0], [0.0,  0.0,  1.0]),
+            ([ 0.0, -1.0,  0.0], [0.0,  0.0, -1.0]),
+            ([ 0.0,  0.0,  1.0], [0.0, -1.0,  0.0]),
+            ([ 0.0,  0.0, -1.0], [0.0, -1.0,  0.0]),
+        ];
+
+        shader.use_program();
+        shader.set_uniform_mat4f(
+            "projection",
+            Matrix::transform_perspective(90f32.to_radians(), 1.0, 0.1, 10.0),
+        )?;
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, size, size);
+        }
+
+        for (face, (direction, up)) in (0..).zip(faces) {
+            shader.set_uniform_mat4f(
+                "view",
+                Matrix::transform_look_at(Vector::new_zero(), direction.into(), up.into()),
+            )?;
+
+            unsafe {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    target.id(),
+                    level,
+                );
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+
+            self.cube.draw();
+        }
+
+        Ok(())
+    }
+
+    /// Draw a full screen quad into the 2D texture `target`
+    fn render_quad(&self, shader: &ShaderProgram, target: &Texture, size: i32) -> Result<()> {
+        shader.use_program();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, size, size);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                target.id(),
+                0,
+            );
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        screen_quad()?.draw();
+
+        Ok(())
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Builder {
+    equirectangular: Option<Texture>,
+    environment_size: Option<i32>,
+    irradiance_size: Option<i32>,
+    prefilter_size: Option<i32>,
+    prefilter_levels: Option<i32>,
+    intensity: Option<f32>,
+}
+
+impl Builder {
+    /// Panorama of the surroundings in equirectangular projection, ideally loaded from an HDR
+    /// image with `texture::Builder::not_normalised`
+    #[must_use]
+    #[inline]
+    pub fn equirectangular(mut self, equirectangular: Texture) -> Self {
+        self.equirectangular = Some(equirectangular);
+        self
+    }
+
+    /// Width of each face of the environment cube map
+    #[must_use]
+    #[inline]
+    pub fn environment_size(mut self, environment_size: i32) -> Self {
+        self.environment_size = Some(environment_size);
+        self
+    }
+
+    /// Width of each face of the irradiance map, which has little detail and can be small
+    #[must_use]
+    #[inline]
+    pub fn irradiance_size(mut self, irradiance_size: i32) -> Self {
+        self.irradiance_size = Some(irradiance_size);
+        self
+    }
+
+    /// Width of each face of the sharpest level of the prefiltered reflections
+    #[must_use]
+    #[inline]
+    pub fn prefilter_size(mut self, prefilter_size: i32) -> Self {
+        self.prefilter_size = Some(prefilter_size);
+        self
+    }
+
+    /// Number of roughness steps in the prefiltered reflections, from mirror-like to fully rough
+    #[must_use]
+    #[inline]
+    pub fn prefilter_levels(mut self, prefilter_levels: i32) -> Self {
+        self.prefilter_levels = Some(prefilter_levels);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = Some(intensity);
+        self
+    }
+
+    /// Precompute every map, this is slow and should be done while loading
+    /// # Errors
+    /// Returns an error if no equirectangular image was given or the prefiltered reflections have
+    /// more levels than their size allows
+    #[inline]
+    pub fn build(self) -> Result<ImageBasedLighting> {
+        let equirectangular = self.equirectangular.ok_or_else(|| {
+            TextureErr(error_fmt!(
+                image_based::Builder,
+                "No equirectangular environment given"
+            ))
+        })?;
+
+        let environment_size = self.environment_size.unwrap_or(512);
+        let irradiance_size = self.irradiance_size.unwrap_or(32);
+        let prefilter_size = self.prefilter_size.unwrap_or(128);
+        let prefilter_levels = self.prefilter_levels.unwrap_or(5);
+
+        if prefilter_levels < 1 || prefilter_size >> (prefilter_levels - 1) < 1 {
+            return Err(TextureErr(error_fmt!(
+                image_based::Builder,
+                "Cannot prefilter {prefilter_levels} levels from a size of {prefilter_size}"
+            )));
+        }
+
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            gl_state::set_capability(gl::CULL_FACE, false);
+            gl_state::set_capability(gl::DEPTH_TEST, false);
+        }
+
+        let capture = Capture::new()?;
+
+        #[expect(clippy::cast_possible_wrap)]
+        let cube_map = |size, mipmapped| {
+            Texture::cube_map_attachment(gl::RGBA16F as i32, size, gl::RGBA, gl::FLOAT, mipmapped)
+        };
+
+        let environment = cube_map(environment_size, true);
+        let to_cube_map = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/cube_map.vert")
+            .add_fragment_shader("src/shaders/equirectangular_to_cube_map.frag")
+            .build()?;
+        to_cube_map.bind_textures(vec![(&equirectangular, "equirectangular")])?;
+        capture.render(&to_cube_map, &environment, 0, environment_size)?;
+        environment.generate_mipmaps();
+
+        let irradiance = cube_map(irradiance_size, false);
+        let irradiance_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/cube_map.vert")
+            .add_fragment_shader("src/shaders/irradiance.frag")
+            .build()?;
+        irradiance_shader.bind_textures(vec![(&environment, "environment")])?;
+        capture.render(&irradiance_shader, &irradiance, 0, irradiance_size)?;
+
+        let prefiltered = cube_map(prefilter_size, true);
+        let prefilter_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/cube_map.vert")
+            .add_fragment_shader("src/shaders/prefilter.frag")
+            .build()?;
+        prefilter_shader.bind_textures(vec![(&environment, "environment")])?;
+        for level in 0..prefilter_levels {
+            #[expect(clippy::cast_precision_loss)]
+            let roughness = level as f32 / (prefilter_levels - 1).max(1) as f32;
+            prefilter_shader.set_uniform_fv("roughness", [roughness])?;
+            capture.render(
+                &prefilter_shader,
+                &prefiltered,
+                level,
+                prefilter_size >> level,
+            )?;
+        }
+
+        #[expect(clippy::cast_possible_wrap)]
+        let brdf_lut =
+            Texture::framebuffer_attachment(gl::RG16F as i32, 512, 512, gl::RG, gl::FLOAT);
+        let brdf_shader = ShaderProgram::builder()
+            .add_vertex_shader("src/shaders/quad_vert.vert")
+            .add_fragment_shader("src/shaders/brdf.frag")
+            .build()?;
+        capture.render_quad(&brdf_shader, &brdf_lut, 512)?;
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            gl_state::set_capability(gl::DEPTH_TEST, true);
+        }
+
+        Ok(ImageBasedLighting {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+            prefilter_levels,
+            intensity: self.intensity.unwrap_or(1.0),
+        })
+    }
+}
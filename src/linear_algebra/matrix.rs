@@ -222,6 +222,24 @@ impl Matrix<4, 4> {
         matrix
     }
 
+    /// View matrix for an eye at `position` looking towards `target`
+    #[inline]
+    pub fn transform_look_at(position: Vector<3>, target: Vector<3>, up: Vector<3>) -> Self {
+        let forward = Vector::from_to(position, target).normalize();
+        let right = forward.cross(up).normalize();
+        let view_up = right.cross(forward);
+
+        #[rustfmt::skip]
+        let rotation = Self::from_row_major([
+            [  right[0],    right[1],    right[2],   0.0],
+            [  view_up[0],  view_up[1],  view_up[2], 0.0],
+            [ -forward[0], -forward[1], -forward[2], 0.0],
+            [  0.0,         0.0,         0.0,        1.0],
+        ]);
+
+        rotation * Self::transform_translate(-position)
+    }
+
     #[inline]
     pub fn transform_perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
         let mut matrix = Self::zeros();
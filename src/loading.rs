@@ -0,0 +1,299 @@
+//! Decoding assets on background threads, then uploading a few of them each frame.
+//!
+//! Decoding images and building vertex data happens on the `Loader`'s worker threads. Only the
+//! upload to the GPU is left for the OpenGL thread, in `Loader::update`, which stops once the
+//! frame's upload budget is spent so that opening a level never stalls a frame for long.
+
+use std::{
+    cell::OnceCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    buffers::vertex_array::{self, VertexArray},
+    error_fmt,
+    texture::{self, Texture},
+    EngineError::{self, MiscErr},
+    Result,
+};
+
+type Job = Box<dyn FnOnce() -> Decoded + Send>;
+
+/// Finished on a worker thread, waiting to be uploaded
+enum Decoded {
+    Texture(usize, Result<texture::Builder>),
+    VertexArray(usize, Result<vertex_array::Builder>),
+}
+
+impl Decoded {
+    fn upload_size(&self) -> usize {
+        match self {
+            Self::Texture(_, Ok(builder)) => builder.upload_size(),
+            Self::VertexArray(_, Ok(builder)) => builder.upload_size(),
+            Self::Texture(_, Err(_)) | Self::VertexArray(_, Err(_)) => 0,
+        }
+    }
+}
+
+/// Where a load ends up once uploaded
+enum Destination {
+    Texture(Texture),
+    VertexArray(Rc<OnceCell<VertexArray>>),
+}
+
+/// Something being loaded in the background, empty until `Loader::update` uploads it
+pub struct Pending<T>(Rc<OnceCell<T>>);
+
+impl<T> Pending<T> {
+    /// `None` until the load has finished
+    #[must_use]
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        self.0.get()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.0.get().is_some()
+    }
+}
+
+impl<T> Clone for Pending<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+/// Loads assets on a pool of worker threads, call `update` once a frame to upload them
+pub struct Loader {
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<Decoded>,
+    cancelled: Arc<AtomicBool>,
+    workers: Vec<thread::JoinHandle<()>>,
+    destinations: HashMap<usize, Destination>,
+    ready: VecDeque<Decoded>,
+    next_ticket: usize,
+    upload_budget: usize,
+    placeholder: [f32; 4],
+}
+
+impl Loader {
+    #[must_use]
+    #[inline]
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    fn submit(
+        &mut self,
+        destination: Destination,
+        job: impl FnOnce(usize) -> Decoded + Send + 'static,
+    ) {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.destinations.insert(ticket, destination);
+
+        if let Some(jobs) = &self.jobs {
+            // Only fails once every worker has stopped, and those never stop before `drop`
+            let _ = jobs.send(Box::new(move || job(ticket)));
+        }
+    }
+
+    /// Start loading the image at `path`. The returned texture shows the placeholder colour
+    /// until the image is uploaded, and then the image itself.
+    #[inline]
+    pub fn load_texture(&mut self, path: &str) -> Texture {
+        self.load_texture_with(path, |builder| builder)
+    }
+
+    /// Start loading the image at `path`, adjusting the builder with `configure` on the worker
+    /// thread before it is uploaded
+    #[inline]
+    pub fn load_texture_with(
+        &mut self,
+        path: &str,
+        configure: impl FnOnce(texture::Builder) -> texture::Builder + Send + 'static,
+    ) -> Texture {
+        let placeholder = Texture::all_one_colour(self.placeholder);
+        let path = path.to_owned();
+        self.submit(Destination::Texture(placeholder.clone()), move |ticket| {
+            Decoded::Texture(ticket, Texture::builder().image(&path).map(configure))
+        });
+        placeholder
+    }
+
+    /// Run `parse` on a worker thread, e.g. to read a model file, and build its vertex array
+    /// once it is uploaded
+    #[inline]
+    pub fn load_vertex_array(
+        &mut self,
+        parse: impl FnOnce() -> Result<vertex_array::Builder> + Send + 'static,
+    ) -> Pending<VertexArray> {
+        let cell = Rc::new(OnceCell::new());
+        self.submit(Destination::VertexArray(Rc::clone(&cell)), move |ticket| {
+            Decoded::VertexArray(ticket, parse())
+        });
+        Pending(cell)
+    }
+
+    /// Upload finished loads until this frame's budget is spent, at least one if any are
+    /// finished. Returns the errors of loads that failed since the last call, each only once,
+    /// whose placeholders stay.
+    #[inline]
+    pub fn update(&mut self) -> Vec<EngineError> {
+        self.ready.extend(self.results.try_iter());
+
+        let mut errors = Vec::new();
+        let mut uploaded = 0;
+        while let Some(decoded) = self.ready.pop_front() {
+            uploaded += decoded.upload_size();
+            if let Err(error) = self.upload(decoded) {
+                errors.push(error);
+            }
+            if uploaded >= self.upload_budget {
+                break;
+            }
+        }
+        errors
+    }
+
+    fn upload(&mut self, decoded: Decoded) -> Result<()> {
+        let (Decoded::Texture(ticket, _) | Decoded::VertexArray(ticket, _)) = decoded;
+        match (decoded, self.destinations.remove(&ticket)) {
+            (Decoded::Texture(_, builder), Some(Destination::Texture(texture))) => {
+                builder?.build_into(&texture)
+            }
+            (Decoded::VertexArray(_, builder), Some(Destination::VertexArray(cell))) => {
+                // Each ticket is uploaded once, so the cell is still empty
+                let _ = cell.set(builder?.build()?);
+                Ok(())
+            }
+            // Every ticket's destination matches what was submitted
+            _ => Ok(()),
+        }
+    }
+
+    /// Loads started but not yet uploaded
+    #[must_use]
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.destinations.len()
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // Workers stop once the queue closes, skipping whatever is still queued
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub struct Builder {
+    threads: usize,
+    upload_budget: usize,
+    placeholder: [f32; 4],
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get().min(4)),
+            upload_budget: 4 * 1024 * 1024,
+            placeholder: [0.0; 4],
+        }
+    }
+}
+
+impl Builder {
+    /// Worker threads to decode on, by default the number of cores up to 4
+    #[must_use]
+    #[inline]
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Bytes to upload per `Loader::update`, 4 MiB by default
+    #[must_use]
+    #[inline]
+    pub const fn upload_budget(mut self, bytes: usize) -> Self {
+        self.upload_budget = bytes;
+        self
+    }
+
+    /// Colour of textures that are still loading, transparent black by default
+    #[must_use]
+    #[inline]
+    pub const fn placeholder(mut self, colour: [f32; 4]) -> Self {
+        self.placeholder = colour;
+        self
+    }
+
+    /// # Errors
+    /// Returns an error if there are no threads or one cannot be started
+    #[inline]
+    pub fn build(self) -> Result<Loader> {
+        if self.threads == 0 {
+            return Err(MiscErr(error_fmt!(
+                loading::Builder,
+                "At least one worker thread is needed"
+            )));
+        }
+
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let workers = (0..self.threads)
+            .map(|index| {
+                let jobs = Arc::clone(&job_receiver);
+                let results = result_sender.clone();
+                let cancelled = Arc::clone(&cancelled);
+                thread::Builder::new()
+                    .name(format!("loader {index}"))
+                    .spawn(move || loop {
+                        // Released before running the job so that the others can take one
+                        let job = match jobs.lock() {
+                            Ok(jobs) => jobs.recv(),
+                            Err(_) => return,
+                        };
+                        let Ok(job) = job else { return };
+                        if cancelled.load(Ordering::Relaxed) || results.send(job()).is_err() {
+                            return;
+                        }
+                    })
+                    .map_err(|error| {
+                        MiscErr(error_fmt!(
+                            loading::Builder,
+                            "Cannot start a worker thread: {error}"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Loader {
+            jobs: Some(job_sender),
+            results,
+            cancelled,
+            workers,
+            destinations: HashMap::new(),
+            ready: VecDeque::new(),
+            next_ticket: 0,
+            upload_budget: self.upload_budget,
+            placeholder: self.placeholder,
+        })
+    }
+}
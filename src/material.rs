@@ -1,6 +1,20 @@
-use crate::{shader_program::ShaderProgram, some_builder, texture::Texture, Result};
+use crate::{
+    gl_state::{self, MaterialKey},
+    shader_program::ShaderProgram,
+    some_builder,
+    texture::Texture,
+    Result,
+};
 
-#[derive(Clone, Debug)]
+/// Surface properties of a `Mesh`.
+///
+/// The Phong shader (`fragment_shader.frag`) reads `diffuse`, `specular_map` and `shininess`.
+/// The physically based shader (`pbr.frag`) instead treats `diffuse` as the albedo and reads
+/// `metallic_roughness` (roughness in green, metallic in blue, as in glTF) and
+/// `ambient_occlusion`. Both use `emission` masked by `emission_map`, the tangent-space
+/// `normal_map`, and `height_map` for parallax occlusion mapping, where white is the surface and
+/// black lies `height_scale` (in texture coordinates) below it.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Material {
     pub translucent: bool,
     pub shininess: f32,
@@ -8,6 +22,11 @@ pub struct Material {
     pub specular_map: Texture,
     pub emission: Texture,
     pub emission_map: Texture,
+    pub metallic_roughness: Texture,
+    pub normal_map: Texture,
+    pub ambient_occlusion: Texture,
+    pub height_map: Texture,
+    pub height_scale: f32,
 }
 
 impl Material {
@@ -30,16 +49,52 @@ impl Material {
     getter_clone!(emission_map, Texture);
     getter_clone!(translucent, bool);
     */
+    /// Every texture with its field name, in the order they are bound to texture units
+    fn textures(&self) -> [(&Texture, &'static str); 8] {
+        [
+            (&self.diffuse, "diffuse"),
+            (&self.specular_map, "specular_map"),
+            (&self.emission, "emission"),
+            (&self.emission_map, "emission_map"),
+            (&self.metallic_roughness, "metallic_roughness"),
+            (&self.normal_map, "normal_map"),
+            (&self.ambient_occlusion, "ambient_occlusion"),
+            (&self.height_map, "height_map"),
+        ]
+    }
+
+    /// Identifies the material's uniforms, materials with equal keys bind identically
+    pub(crate) fn key(&self) -> MaterialKey {
+        MaterialKey {
+            textures: self.textures().map(|(texture, _)| texture.id()),
+            shininess: self.shininess,
+            height_scale: self.height_scale,
+        }
+    }
+
+    /// Set the uniforms under `name` and bind the textures to units 0 to 7. The uniforms are
+    /// skipped if `shader` already holds this material's.
     /// # Errors
     #[inline]
     pub fn bind_to(&self, shader: &ShaderProgram, name: &str) -> Result<()> {
+        if !gl_state::material_changed(shader.id(), name, self.key()) {
+            // Other programs may have reused the units since
+            for (unit, (texture, _)) in (0..).zip(self.textures()) {
+                texture.bind_to(unit)?;
+            }
+            return Ok(());
+        }
+
         shader.set_uniform_fv(&format!("{name}.shininess"), [self.shininess])?;
-        shader.bind_textures(vec![
-            (&self.diffuse, &format!("{name}.diffuse")),
-            (&self.specular_map, &format!("{name}.specular_map")),
-            (&self.emission, &format!("{name}.emission")),
-            (&self.emission_map, &format!("{name}.emission_map")),
-        ])?;
+        shader.set_uniform_fv(&format!("{name}.height_scale"), [self.height_scale])?;
+        let names = self.textures().map(|(_, field)| format!("{name}.{field}"));
+        shader.bind_textures(
+            self.textures()
+                .iter()
+                .zip(&names)
+                .map(|(&(texture, _), name)| (texture, name.as_str()))
+                .collect(),
+        )?;
 
         Ok(())
     }
@@ -53,6 +108,11 @@ pub struct Builder {
     specular_map: Option<Texture>,
     emission: Option<Texture>,
     emission_map: Option<Texture>,
+    metallic_roughness: Option<Texture>,
+    normal_map: Option<Texture>,
+    ambient_occlusion: Option<Texture>,
+    height_map: Option<Texture>,
+    height_scale: Option<f32>,
 }
 
 impl Builder {
@@ -64,6 +124,10 @@ impl Builder {
     some_builder!(specular_map: Texture);
     some_builder!(emission: Texture);
     some_builder!(emission_map: Texture);
+    some_builder!(metallic_roughness: Texture);
+    some_builder!(normal_map: Texture);
+    some_builder!(ambient_occlusion: Texture);
+    some_builder!(height_map: Texture);
 
     #[must_use]
     #[inline]
@@ -72,6 +136,14 @@ impl Builder {
         self
     }
 
+    /// Depth of the black parts of `height_map`, in texture coordinates
+    #[must_use]
+    #[inline]
+    pub const fn height_scale(mut self, height_scale: f32) -> Self {
+        self.height_scale = Some(height_scale);
+        self
+    }
+
     #[must_use]
     #[inline]
     pub const fn is_translucent(mut self) -> Self {
@@ -88,6 +160,22 @@ impl Builder {
             specular_map: self.specular_map.unwrap_or_else(Texture::blank),
             emission: self.emission.unwrap_or_else(Texture::blank),
             emission_map: self.emission_map.unwrap_or_else(Texture::blank),
+            // Fully rough dielectric
+            metallic_roughness: self
+                .metallic_roughness
+                .unwrap_or_else(|| Texture::all_one_colour([0.0, 1.0, 0.0, 1.0])),
+            // Tangent-space normal pointing straight out of the surface
+            normal_map: self
+                .normal_map
+                .unwrap_or_else(|| Texture::all_one_colour([0.5, 0.5, 1.0, 1.0])),
+            ambient_occlusion: self
+                .ambient_occlusion
+                .unwrap_or_else(|| Texture::grayscale(1.0, 1.0)),
+            // Flat, parallax mapping has no effect
+            height_map: self
+                .height_map
+                .unwrap_or_else(|| Texture::grayscale(1.0, 1.0)),
+            height_scale: self.height_scale.unwrap_or(0.05),
         }
     }
 }
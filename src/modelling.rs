@@ -1,2 +1,4 @@
+pub mod bounds;
 pub mod model;
+pub mod primitives;
 pub mod geometry;
\ No newline at end of file
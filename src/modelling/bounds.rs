@@ -0,0 +1,220 @@
+use crate::linear_algebra::{matrix::Matrix, vector::Vector};
+
+/// The smallest box aligned with the axes that holds every point
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingBox {
+    pub min: Vector<3>,
+    pub max: Vector<3>,
+}
+
+impl BoundingBox {
+    /// `None` if there are no points
+    #[must_use]
+    #[inline]
+    pub fn from_points(points: impl IntoIterator<Item = Vector<3>>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, point| {
+            let point = point.into_inner();
+            let (min, max) = bounds.map_or((point, point), |bounds: Self| {
+                let (min, max) = (bounds.min.into_inner(), bounds.max.into_inner());
+                (
+                    [0, 1, 2].map(|i| min[i].min(point[i])),
+                    [0, 1, 2].map(|i| max[i].max(point[i])),
+                )
+            });
+            Some(Self {
+                min: Vector::new(min),
+                max: Vector::new(max),
+            })
+        })
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn centre(&self) -> Vector<3> {
+        (self.min + self.max).scale(0.5)
+    }
+
+    /// Width, height and depth
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> Vector<3> {
+        self.max - self.min
+    }
+
+    /// The box holding both boxes
+    #[must_use]
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        let (min, other_min) = (self.min.into_inner(), other.min.into_inner());
+        let (max, other_max) = (self.max.into_inner(), other.max.into_inner());
+        Self {
+            min: Vector::new([0, 1, 2].map(|i| min[i].min(other_min[i]))),
+            max: Vector::new([0, 1, 2].map(|i| max[i].max(other_max[i]))),
+        }
+    }
+
+    /// The box aligned with the axes around this box after `transform`, which may be larger
+    /// than the transformed contents if the transform rotates
+    #[must_use]
+    #[inline]
+    pub fn transformed(&self, transform: &Matrix<4, 4>) -> Self {
+        // Arvo's method, each entry of the matrix moves whichever bound it makes larger
+        let (min, max) = (self.min.into_inner(), self.max.into_inner());
+        let mut new_min = [transform[(0, 3)], transform[(1, 3)], transform[(2, 3)]];
+        let mut new_max = new_min;
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let a = transform[(row, column)] * min[column];
+                let b = transform[(row, column)] * max[column];
+                new_min[row] += a.min(b);
+                new_max[row] += a.max(b);
+            }
+        }
+
+        Self {
+            min: Vector::new(new_min),
+            max: Vector::new(new_max),
+        }
+    }
+}
+
+/// A sphere holding every point, not necessarily the smallest one
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub centre: Vector<3>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Ritter's approximation, usually within a few percent of the smallest sphere.
+    /// `None` if there are no points.
+    #[must_use]
+    #[inline]
+    pub fn from_points(points: &[Vector<3>]) -> Option<Self> {
+        let first = *points.first()?;
+        let farthest_from = |from: Vector<3>| {
+            points.iter().copied().fold(from, |farthest, point| {
+                if from.distance_squared(point) > from.distance_squared(farthest) {
+                    point
+                } else {
+                    farthest
+                }
+            })
+        };
+
+        // Start from two points roughly on opposite sides
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+        let mut centre = (a + b).scale(0.5);
+        let mut radius = a.distance_squared(b).sqrt() * 0.5;
+
+        // Grow just enough to take in each point left outside
+        for &point in points {
+            let distance = centre.distance_squared(point).sqrt();
+            if distance > radius {
+                let new_radius = (radius + distance) * 0.5;
+                centre = centre + (point - centre).scale((new_radius - radius) / distance);
+                radius = new_radius;
+            }
+        }
+
+        Some(Self { centre, radius })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_near;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox {
+            min: Vector::new([0.0, 0.0, 0.0]),
+            max: Vector::new([1.0, 2.0, 3.0]),
+        }
+    }
+
+    #[test]
+    fn box_from_points() {
+        let bounds = BoundingBox::from_points([
+            Vector::new([1.0, -2.0, 0.0]),
+            Vector::new([-1.0, 4.0, 0.5]),
+            Vector::new([0.0, 0.0, -3.0]),
+        ])
+        .unwrap();
+
+        assert_near(bounds.min, [-1.0, -2.0, -3.0]);
+        assert_near(bounds.max, [1.0, 4.0, 0.5]);
+        assert!(BoundingBox::from_points([]).is_none());
+    }
+
+    #[test]
+    fn translated_and_scaled_box() {
+        let translation = Matrix::transform_translate(Vector::new([1.0, 2.0, 3.0]));
+        let translated = unit_box().transformed(&translation);
+        assert_near(translated.min, [1.0, 2.0, 3.0]);
+        assert_near(translated.max, [2.0, 4.0, 6.0]);
+
+        // A negative scale swaps which corner is the minimum
+        let scaled = unit_box().transformed(&Matrix::transform_scale(2.0, -1.0, 1.0));
+        assert_near(scaled.min, [0.0, -2.0, 0.0]);
+        assert_near(scaled.max, [2.0, 0.0, 3.0]);
+    }
+
+    #[test]
+    fn rotated_box() {
+        let rotation = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            Matrix::from_row_major([
+                [cos, -sin, 0.0, 0.0],
+                [sin, cos, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+
+        let quarter_turn = unit_box().transformed(&rotation(std::f32::consts::FRAC_PI_2));
+        assert_near(quarter_turn.min, [-2.0, 0.0, 0.0]);
+        assert_near(quarter_turn.max, [0.0, 1.0, 3.0]);
+
+        // An eighth of a turn grows the box to hold the rotated corners
+        let square = BoundingBox {
+            min: Vector::new([0.0, 0.0, 0.0]),
+            max: Vector::new([1.0, 1.0, 1.0]),
+        };
+        let eighth_turn = square.transformed(&rotation(std::f32::consts::FRAC_PI_4));
+        let half_diagonal = 0.5_f32.sqrt();
+        assert_near(eighth_turn.min, [-half_diagonal, 0.0, 0.0]);
+        assert_near(eighth_turn.max, [half_diagonal, 2.0 * half_diagonal, 1.0]);
+    }
+
+    #[test]
+    fn sphere_holds_every_point() {
+        let axes = [
+            Vector::new([1.0, 0.0, 0.0]),
+            Vector::new([-1.0, 0.0, 0.0]),
+            Vector::new([0.0, 1.0, 0.0]),
+            Vector::new([0.0, -1.0, 0.0]),
+            Vector::new([0.0, 0.0, 1.0]),
+            Vector::new([0.0, 0.0, -1.0]),
+        ];
+        let sphere = BoundingSphere::from_points(&axes).unwrap();
+        assert_near(sphere.centre, [0.0, 0.0, 0.0]);
+        assert_near([sphere.radius], [1.0]);
+
+        // Points spread so the first two chosen miss some, making the sphere grow
+        let points: Vec<_> = (0..50_u8)
+            .map(|point| {
+                let t = f32::from(point);
+                Vector::new([(t * 1.3).sin() * 3.0, (t * 0.7).cos() * 2.0, t * 0.1])
+            })
+            .collect();
+        let sphere = BoundingSphere::from_points(&points).unwrap();
+        for point in points {
+            assert!(sphere.centre.distance_squared(point).sqrt() <= sphere.radius + 1.0e-4);
+        }
+
+        assert!(BoundingSphere::from_points(&[]).is_none());
+    }
+}
@@ -1,13 +1,15 @@
 use std::iter;
 
 use crate::{
-    buffers::vertex_array::VertexArray, linear_algebra::{matrix::Matrix, orientation::Orientation, vector::Vector}, material::Material, shader_program::ShaderProgram, some_builder, Result
+    buffers::{instance_buffer::InstanceBuffer, vertex_array::{DrawRange, VertexArray}}, camera::Camera, gl_state, linear_algebra::{matrix::Matrix, orientation::Orientation, vector::Vector}, material::Material, modelling::{bounds::BoundingBox, primitives::QuadBuilder}, shader_program::ShaderProgram, some_builder, Result
 };
 
 #[derive(Debug, Clone)]
 pub struct Mesh {
     vertex_array: VertexArray,
     material: Material,
+    /// Only these elements are drawn when given, all of them otherwise
+    ranges: Option<Vec<DrawRange>>,
 }
 
 impl Mesh {
@@ -17,16 +19,108 @@ impl Mesh {
         Self {
             vertex_array,
             material,
+            ranges: None,
         }
     }
 
+    /// Draw only `ranges` of the vertex array's elements, all with a single command. Usually
+    /// the ranges come from `VertexArray::merge`.
+    /// # Errors
+    /// Returns an error if a range lies outside the elements
+    #[inline]
+    pub fn with_ranges(
+        vertex_array: VertexArray,
+        material: Material,
+        ranges: Vec<DrawRange>,
+    ) -> Result<Self> {
+        vertex_array.check_ranges(&ranges)?;
+        Ok(Self {
+            vertex_array,
+            material,
+            ranges: Some(ranges),
+        })
+    }
+
+    /// Bytes of GPU memory used by the vertex array, textures are counted separately
+    #[must_use]
+    #[inline]
+    pub fn memory(&self) -> usize {
+        self.vertex_array.memory()
+    }
+
     pub(crate) fn draw(&self, shader_program: &ShaderProgram) -> Result<()> {
         self.material.bind_to(shader_program, "material")?;
-        self.vertex_array.draw();
+        self.draw_shape(shader_program, None)
+    }
+
+    fn draw_instanced(
+        &self,
+        shader_program: &ShaderProgram,
+        instances: &InstanceBuffer,
+    ) -> Result<()> {
+        self.material.bind_to(shader_program, "material")?;
+        self.draw_shape(shader_program, Some(instances))
+    }
+
+    /// Draw with whatever material is currently bound
+    fn draw_shape(
+        &self,
+        shader_program: &ShaderProgram,
+        instances: Option<&InstanceBuffer>,
+    ) -> Result<()> {
+        self.vertex_array.validate(shader_program)?;
+
+        match (instances, &self.ranges) {
+            (None, None) => self.vertex_array.draw(),
+            (None, Some(ranges)) => self.vertex_array.draw_ranges(ranges),
+            (Some(instances), None) => self.vertex_array.draw_instanced(
+                shader_program,
+                instances,
+                &[self.vertex_array.whole_range()],
+            ),
+            (Some(instances), Some(ranges)) => {
+                self.vertex_array
+                    .draw_instanced(shader_program, instances, ranges);
+            }
+        }
+
         Ok(())
     }
 }
 
+/// When a level of detail replaces the one before it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LodThreshold {
+    /// Once the camera is at least this far from the centre of the model's bounds
+    Distance(f32),
+    /// Once the model's bounding sphere covers at most this fraction of the screen's height
+    ScreenSize(f32),
+}
+
+impl LodThreshold {
+    /// How far past the threshold, relative to the threshold itself, negative if not reached
+    fn excess(self, distance: f32, screen_size: f32) -> f32 {
+        match self {
+            Self::Distance(threshold) => distance / threshold - 1.0,
+            Self::ScreenSize(threshold) => threshold / screen_size - 1.0,
+        }
+    }
+}
+
+/// A coarser set of meshes drawn in place of the previous level once `threshold` is passed
+#[derive(Clone, Debug)]
+struct Lod {
+    meshes: Vec<Mesh>,
+    threshold: LodThreshold,
+}
+
+/// The level to draw, and the next one with how far it has faded in if blending between them
+#[derive(Clone, Copy, Debug)]
+struct LodChoice {
+    level: usize,
+    incoming: Option<(usize, f32)>,
+}
+
 #[derive(Clone, Default)]
 pub struct Model {
     meshes: Vec<Mesh>,
@@ -34,6 +128,21 @@ pub struct Model {
     position: Vector<3>,
     orientation: Orientation,
     scale: f32,
+    /// In model space, `None` if any mesh has no bounds
+    bounds: Option<BoundingBox>,
+    /// Coarser levels in order, the model's own meshes are the most detailed
+    lods: Vec<Lod>,
+    /// Width of the cross-fade after each threshold, relative to the threshold
+    lod_fade: f32,
+}
+
+/// The box around every mesh, `None` if there are none or any has no bounds
+fn mesh_bounds(meshes: &[Mesh]) -> Option<BoundingBox> {
+    meshes
+        .iter()
+        .map(|mesh| mesh.vertex_array.bounding_box())
+        .reduce(|a, b| a.zip(b).map(|(a, b)| a.union(&b)))
+        .flatten()
 }
 
 impl Model {
@@ -46,11 +155,14 @@ impl Model {
         orientation: Option<Orientation>,
     ) -> Self {
         Self {
+            bounds: mesh_bounds(&mesh_iter),
             meshes: mesh_iter,
             cull_face,
             position,
             orientation: orientation.unwrap_or_default(),
             scale,
+            lods: Vec::new(),
+            lod_fade: 0.0,
         }
     }
 
@@ -92,24 +204,198 @@ impl Model {
         self.position
     }
 
+    /// The box around every mesh in model space, `None` if the model is never culled
+    #[must_use]
+    #[inline]
+    pub const fn bounding_box(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    /// The bounding box after the model's position, orientation and scale
+    #[must_use]
+    #[inline]
+    pub fn world_bounding_box(&self) -> Option<BoundingBox> {
+        self.bounding_box_after(&self.model_matrix())
+    }
+
+    /// The bounding box after `model_matrix`, in place of the model's own transform
+    pub(crate) fn bounding_box_after(&self, model_matrix: &Matrix<4, 4>) -> Option<BoundingBox> {
+        self.bounds.map(|bounds| bounds.transformed(model_matrix))
+    }
+
+    /// The centre of the bounding box after `model_matrix`, or where it places the origin for
+    /// models without bounds
+    pub(crate) fn centre_after(&self, model_matrix: &Matrix<4, 4>) -> Vector<3> {
+        self.bounding_box_after(model_matrix).map_or_else(
+            || Vector::new([model_matrix[(0, 3)], model_matrix[(1, 3)], model_matrix[(2, 3)]]),
+            |bounds| bounds.centre(),
+        )
+    }
+
+    /// Whether any mesh has a translucent material, so the model must be drawn after opaque
+    /// ones
+    pub(crate) fn is_translucent(&self) -> bool {
+        self.meshes.iter().any(|mesh| mesh.material.translucent)
+    }
+
+    /// Groups models sharing their first material and vertex array when sorted, so that
+    /// drawing them one after another changes as little state as possible
+    pub(crate) fn sort_key(&self) -> Option<([u32; 8], u32)> {
+        self.meshes
+            .first()
+            .map(|mesh| (mesh.material.key().textures, mesh.vertex_array.id()))
+    }
+
+    /// Number of levels of detail, including the model's own meshes
+    #[must_use]
+    #[inline]
+    pub fn lod_count(&self) -> usize {
+        self.lods.len() + 1
+    }
+
+    fn lod_meshes(&self, level: usize) -> &[Mesh] {
+        level
+            .checked_sub(1)
+            .map_or(&self.meshes, |index| &self.lods[index].meshes)
+    }
+
+    fn choose_lod(&self, camera: &Camera, model_matrix: &Matrix<4, 4>) -> LodChoice {
+        let bounds = self.bounding_box_after(model_matrix);
+        let centre = self.centre_after(model_matrix);
+        let distance = camera.position().distance_squared(centre).sqrt().max(f32::EPSILON);
+        // Half the sphere's diameter over half the screen's height, which is 1 / projection[1][1]
+        let screen_size = bounds.map_or(f32::INFINITY, |bounds| {
+            let size = bounds.size();
+            0.5 * size.dot(&size).sqrt() * camera.projection()[(1, 1)] / distance
+        });
+
+        let mut level = 0;
+        for (index, lod) in self.lods.iter().enumerate() {
+            let excess = lod.threshold.excess(distance, screen_size);
+            if excess < 0.0 {
+                break;
+            }
+            if excess < self.lod_fade {
+                return LodChoice {
+                    level,
+                    incoming: Some((index + 1, excess / self.lod_fade)),
+                };
+            }
+            level = index + 1;
+        }
+
+        LodChoice {
+            level,
+            incoming: None,
+        }
+    }
+
+    /// Call `draw_mesh` on every mesh of the level of detail for `camera`, the most detailed
+    /// without one. While cross-fading both levels are drawn with complementary dithering,
+    /// through the shader's `lod_fade` uniform.
+    fn draw_lod(
+        &self,
+        shader_program: &ShaderProgram,
+        camera: Option<&Camera>,
+        model_matrix: &Matrix<4, 4>,
+        mut draw_mesh: impl FnMut(&Mesh) -> Result<()>,
+    ) -> Result<()> {
+        let choice = camera.map_or(
+            LodChoice {
+                level: 0,
+                incoming: None,
+            },
+            |camera| self.choose_lod(camera, model_matrix),
+        );
+
+        let Some((incoming, fade)) = choice.incoming else {
+            shader_program.set_uniform_fv("lod_fade", [0.0])?;
+            return self.lod_meshes(choice.level).iter().try_for_each(draw_mesh);
+        };
+
+        // Negative fades out, positive fades in
+        for (level, lod_fade) in [(choice.level, -fade), (incoming, fade)] {
+            shader_program.set_uniform_fv("lod_fade", [lod_fade])?;
+            self.lod_meshes(level).iter().try_for_each(&mut draw_mesh)?;
+        }
+        shader_program.set_uniform_fv("lod_fade", [0.0])
+    }
+
+    fn prepare(&self, shader_program: &ShaderProgram, model_matrix: &Matrix<4, 4>) -> Result<()> {
+        gl_state::set_capability(gl::CULL_FACE, self.cull_face);
+        shader_program.set_uniform_iv("instanced", [0])?;
+        shader_program.set_uniform_mat4f("model", *model_matrix)
+    }
+
+    /// Draw the most detailed level
     /// # Errors
     #[inline]
     pub fn draw(&self, shader_program: &ShaderProgram) -> Result<()> {
-        unsafe {
-            if self.cull_face {
-                gl::Enable(gl::CULL_FACE);
-            } else {
-                gl::Disable(gl::CULL_FACE);
-            }
+        self.draw_placed(shader_program, None, None, &self.model_matrix())
+    }
+
+    /// Draw the level of detail chosen for `camera`, as `Draw` does
+    /// # Errors
+    #[inline]
+    pub fn draw_from(&self, shader_program: &ShaderProgram, camera: &Camera) -> Result<()> {
+        self.draw_placed(shader_program, Some(camera), None, &self.model_matrix())
+    }
+
+    /// Draw a copy of the model for every instance, each transformed by its instance matrix
+    /// after the model's own transform. The shader must read `in_instance_model` when its
+    /// `instanced` uniform is set, as `vertex_shader.vert` does, at locations none of the mesh's
+    /// attributes use. Always the most detailed level.
+    /// # Errors
+    #[inline]
+    pub fn draw_instanced(
+        &self,
+        shader_program: &ShaderProgram,
+        instances: &InstanceBuffer,
+    ) -> Result<()> {
+        self.draw_placed(shader_program, None, Some(instances), &self.model_matrix())
+    }
+
+    /// Draw as `draw_instanced` with `instances`, otherwise as `draw_from` with `camera` or
+    /// `draw` without, transformed by `model_matrix` in place of the model's own transform
+    pub(crate) fn draw_placed(
+        &self,
+        shader_program: &ShaderProgram,
+        camera: Option<&Camera>,
+        instances: Option<&InstanceBuffer>,
+        model_matrix: &Matrix<4, 4>,
+    ) -> Result<()> {
+        self.prepare(shader_program, model_matrix)?;
+        if let Some(instances) = instances {
+            shader_program.set_uniform_iv("instanced", [1])?;
+            return self
+                .meshes
+                .iter()
+                .try_for_each(|mesh| mesh.draw_instanced(shader_program, instances));
         }
 
-        shader_program.set_uniform_mat4f("model", self.model_matrix())?;
+        self.draw_lod(shader_program, camera, model_matrix, |mesh| {
+            mesh.draw(shader_program)
+        })
+    }
 
-        for mesh in &self.meshes {
-            mesh.draw(shader_program)?;
+    /// Draw only the shape of the model without binding any materials, for depth and
+    /// geometry pre-passes. The level of detail is chosen for `camera` unless instanced.
+    pub(crate) fn draw_geometry(
+        &self,
+        shader_program: &ShaderProgram,
+        instances: Option<&InstanceBuffer>,
+        camera: &Camera,
+        model_matrix: &Matrix<4, 4>,
+    ) -> Result<()> {
+        self.prepare(shader_program, model_matrix)?;
+        if instances.is_some() {
+            shader_program.set_uniform_iv("instanced", [1])?;
         }
 
-        Ok(())
+        let camera = instances.is_none().then_some(camera);
+        self.draw_lod(shader_program, camera, model_matrix, |mesh| {
+            mesh.draw_shape(shader_program, instances)
+        })
     }
 }
 
@@ -120,6 +406,9 @@ pub struct Builder {
     position: Option<Vector<3>>,
     orientation: Option<Orientation>,
     scale: Option<f32>,
+    bounds: Option<BoundingBox>,
+    lods: Vec<Lod>,
+    lod_fade: f32,
 }
 
 impl Builder {
@@ -144,7 +433,30 @@ impl Builder {
     some_builder!(position: Vector<3>);
     some_builder!(orientation: Orientation);
     some_builder!(scale: f32);
-    
+
+    /// Add a coarser level of detail, drawn instead of the previous one once `threshold` is
+    /// passed. Levels must be added from most to least detailed.
+    #[must_use]
+    #[inline]
+    pub fn lod(mut self, threshold: LodThreshold, meshes: Vec<Mesh>) -> Self {
+        self.lods.push(Lod { meshes, threshold });
+        self
+    }
+
+    /// Cross-fade between levels of detail from each threshold until `width` past it, relative
+    /// to the threshold, e.g. 0.1 fades from 50 to 55 units for `LodThreshold::Distance(50.0)`.
+    /// The shaders must dither with `lod_discard` from `lod_dither.glsl`, as the bundled ones do.
+    #[must_use]
+    #[inline]
+    pub const fn lod_fade(mut self, width: f32) -> Self {
+        self.lod_fade = width;
+        self
+    }
+
+    // Cull with `bounds` rather than the box around the meshes, e.g. when a vertex shader
+    // moves the vertices
+    some_builder!(bounds: BoundingBox);
+
     pub fn cull_face(mut self, cull_face: bool) -> Self {
         self.cull_face = cull_face;
         self
@@ -159,37 +471,101 @@ impl Builder {
 
     pub fn build(self) -> Model {
         Model {
+            bounds: self.bounds.or_else(|| mesh_bounds(&self.meshes)),
             meshes: self.meshes,
             cull_face: self.cull_face,
             position: self.position.unwrap_or_default(),
             orientation: self.orientation.unwrap_or_default(),
-            scale: self.scale.unwrap_or(1.0)
+            scale: self.scale.unwrap_or(1.0),
+            lods: self.lods,
+            lod_fade: self.lod_fade.max(0.0),
         }
     }
 }
 
 impl Model {
     pub fn cube(side_length: f32, material: Material) -> Result<Builder> {
-        let out = Self::builder()
-            .all_meshes(
-                VertexArray::cube(side_length)?
-                    .into_iter()
-                    .map(|vao| Mesh::new(vao, material.clone()))
-                    .collect()
-            );
-
-        Ok(out)
+        Ok(Self::builder().mesh_from(VertexArray::cube(side_length)?, material))
     }
 
+    /// A quad covering the whole screen unless given a `rect`, see `primitives` for other shapes
     pub fn quad() -> QuadBuilder {
-        QuadBuilder(Model::builder())
+        QuadBuilder::default()
     }
 }
 
-pub struct QuadBuilder(Builder);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_near;
 
-/*impl QuadBuilder {
-    pub fn whole_screen(self) -> Self {
-        self.0.mesh(mesh)
+    /// Levels of detail without meshes, as choosing one never looks at them
+    fn model(thresholds: &[LodThreshold], lod_fade: f32) -> Model {
+        Model {
+            lods: thresholds
+                .iter()
+                .map(|&threshold| Lod {
+                    meshes: Vec::new(),
+                    threshold,
+                })
+                .collect(),
+            lod_fade,
+            ..Model::default()
+        }
     }
-}*/
+
+    /// The level chosen and any incoming level with its fade, from `distance` along z
+    fn choose(model: &Model, camera: &Camera, distance: f32) -> (usize, Option<(usize, f32)>) {
+        let placed = Matrix::transform_translate(Vector::new([0.0, 0.0, distance]));
+        let choice = model.choose_lod(camera, &placed);
+        (choice.level, choice.incoming)
+    }
+
+    #[test]
+    fn distance_thresholds_switch_at_their_boundaries() {
+        let model = model(&[LodThreshold::Distance(10.0), LodThreshold::Distance(20.0)], 0.0);
+        let camera = Camera::default();
+
+        assert_eq!(choose(&model, &camera, 9.9), (0, None));
+        assert_eq!(choose(&model, &camera, 10.0), (1, None));
+        assert_eq!(choose(&model, &camera, 19.9), (1, None));
+        assert_eq!(choose(&model, &camera, 20.0), (2, None));
+        assert_eq!(choose(&model, &camera, 1000.0), (2, None));
+    }
+
+    #[test]
+    fn levels_fade_in_past_their_threshold() {
+        let model = model(&[LodThreshold::Distance(10.0), LodThreshold::Distance(20.0)], 0.1);
+        let camera = Camera::default();
+
+        // Fading runs from each threshold to a tenth past it
+        assert_eq!(choose(&model, &camera, 9.9), (0, None));
+        assert_eq!(choose(&model, &camera, 10.0), (0, Some((1, 0.0))));
+        let (level, incoming) = choose(&model, &camera, 10.5);
+        let (next, fade) = incoming.unwrap();
+        assert_eq!((level, next), (0, 1));
+        assert_near([fade], [0.5]);
+
+        assert_eq!(choose(&model, &camera, 12.0), (1, None));
+        let (level, incoming) = choose(&model, &camera, 21.0);
+        let (next, fade) = incoming.unwrap();
+        assert_eq!((level, next), (1, 2));
+        assert_near([fade], [0.5]);
+        assert_eq!(choose(&model, &camera, 23.0), (2, None));
+    }
+
+    #[test]
+    fn screen_size_thresholds_use_the_bounds() {
+        let mut model = model(&[LodThreshold::ScreenSize(0.1)], 0.0);
+        // Half the diagonal of the box is sqrt(3), so it covers sqrt(3) / distance of the
+        // screen with a 90 degree field of view
+        model.bounds = BoundingBox::from_points([[-1.0; 3], [1.0; 3]].map(Vector::new));
+        let camera = Camera::builder()
+            .perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0)
+            .build();
+
+        let switch = 3.0_f32.sqrt() / 0.1;
+        assert_eq!(choose(&model, &camera, switch - 0.1), (0, None));
+        assert_eq!(choose(&model, &camera, switch + 0.1), (1, None));
+    }
+}
@@ -0,0 +1,446 @@
+//! Procedurally generated meshes.
+//!
+//! Each is made of `MeshVertex`s like `Model::cube`, faces wind counter-clockwise seen from
+//! outside and texture coordinates span `[0, 1]`.
+
+use std::{
+    collections::HashMap,
+    f32::consts::{FRAC_PI_2, PI, TAU},
+};
+
+use crate::{
+    buffers::{vertex_array::VertexArray, vertex_format::MeshVertex},
+    error_fmt,
+    material::Material,
+    modelling::model::{Builder, Mesh, Model},
+    EngineError::VertexArrayErr,
+    Result,
+};
+
+/// Most `icosphere` subdivisions, each of which multiplies the elements by 4. One more would
+/// have more elements than the `i32` count they are drawn with.
+const MAX_SUBDIVISIONS: u32 = 12;
+
+#[derive(Default)]
+struct MeshData {
+    vertices: Vec<MeshVertex>,
+    elements: Vec<u32>,
+}
+
+impl MeshData {
+    fn vertex(&mut self, position: [f32; 3], texture_coord: [f32; 2], normal: [f32; 3]) -> u32 {
+        let index = self.vertices.len().try_into().unwrap_or(u32::MAX);
+        self.vertices.push(MeshVertex::new(position, texture_coord, normal));
+        index
+    }
+
+    /// A `columns` by `rows` sheet of quads. `surface(u, row)` gives the position and normal
+    /// for `u` in `[0, 1]` across the columns and each row from 0 to `rows` inclusive, and must
+    /// be laid out so that `cross(d/du, d/drow)` points out of the front face.
+    fn grid(
+        &mut self,
+        columns: u32,
+        rows: u32,
+        surface: impl Fn(f32, u32) -> ([f32; 3], [f32; 3]),
+    ) {
+        let first = self.vertices.len().try_into().unwrap_or(u32::MAX);
+
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let (position, normal) = surface(u, row);
+                self.vertex(position, [u, row as f32 / rows as f32], normal);
+            }
+        }
+
+        let width = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * width + column;
+                let b = a + 1;
+                let c = b + width;
+                let d = a + width;
+                self.elements.extend([a, b, c, a, c, d]);
+            }
+        }
+    }
+
+    /// A flat disc at height `y` facing up or down, split into `sectors` triangles
+    fn disc(&mut self, radius: f32, y: f32, sectors: u32, facing_up: bool) {
+        let (normal, flip_v): ([f32; 3], f32) = if facing_up {
+            ([0.0, 1.0, 0.0], -0.5)
+        } else {
+            ([0.0, -1.0, 0.0], 0.5)
+        };
+
+        let centre = self.vertex([0.0, y, 0.0], [0.5, 0.5], normal);
+        for sector in 0..=sectors {
+            let (sin, cos) = (sector as f32 / sectors as f32 * TAU).sin_cos();
+            self.vertex(
+                [radius * sin, y, radius * cos],
+                [0.5_f32.mul_add(sin, 0.5), flip_v.mul_add(cos, 0.5)],
+                normal,
+            );
+        }
+
+        for sector in 0..sectors {
+            let (a, b) = (centre + 1 + sector, centre + 2 + sector);
+            if facing_up {
+                self.elements.extend([centre, a, b]);
+            } else {
+                self.elements.extend([centre, b, a]);
+            }
+        }
+    }
+
+    fn build(mut self, material: Material) -> Result<Builder> {
+        MeshVertex::generate_tangents(&mut self.vertices, &mut self.elements)?;
+        let vertex_array = VertexArray::builder()
+            .vertices(&self.vertices)
+            .element_buffer(self.elements)
+            .build()?;
+
+        Ok(Model::builder().mesh(Mesh::new(vertex_array, material)))
+    }
+}
+
+fn check_segments(name: &str, segments: u32, minimum: u32) -> Result<()> {
+    if segments < minimum {
+        return Err(VertexArrayErr(error_fmt!(
+            primitives,
+            "{name} must be at least {minimum}, not {segments}"
+        )));
+    }
+    Ok(())
+}
+
+/// Spherical coordinates with `latitude` from `-PI / 2` at the bottom to `PI / 2` at the top
+fn on_sphere(longitude: f32, latitude: f32) -> [f32; 3] {
+    let (sin_longitude, cos_longitude) = longitude.sin_cos();
+    let (sin_latitude, cos_latitude) = latitude.sin_cos();
+    [
+        cos_latitude * sin_longitude,
+        sin_latitude,
+        cos_latitude * cos_longitude,
+    ]
+}
+
+fn scaled(vector: [f32; 3], scale: f32) -> [f32; 3] {
+    vector.map(|component| component * scale)
+}
+
+/// A sphere of `sectors` slices around the y axis and `stacks` from pole to pole, with
+/// texture coordinates wrapped like a map of the globe
+/// # Errors
+/// Returns an error if `sectors` is less than 3 or `stacks` less than 2
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32, material: Material) -> Result<Builder> {
+    check_segments("sectors", sectors, 3)?;
+    check_segments("stacks", stacks, 2)?;
+
+    let mut mesh = MeshData::default();
+    mesh.grid(sectors, stacks, |u, row| {
+        let normal = on_sphere(
+            u * TAU,
+            (row as f32 / stacks as f32).mul_add(PI, -FRAC_PI_2),
+        );
+        (scaled(normal, radius), normal)
+    });
+    mesh.build(material)
+}
+
+/// A sphere made by splitting each face of an icosahedron into four `subdivisions` times, so the
+/// triangles are close to equal in size. Texture coordinates are wrapped as for `uv_sphere`.
+/// # Errors
+/// Returns an error if `subdivisions` is more than 12, beyond which there are too many
+/// elements to draw
+pub fn icosphere(radius: f32, subdivisions: u32, material: Material) -> Result<Builder> {
+    if subdivisions > MAX_SUBDIVISIONS {
+        return Err(VertexArrayErr(error_fmt!(
+            primitives,
+            "{subdivisions} subdivisions is too many, at most {MAX_SUBDIVISIONS} are supported"
+        )));
+    }
+
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut points: Vec<[f32; 3]> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .map(normalised)
+    .to_vec();
+
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let [pa, pb] = [points[a as usize], points[b as usize]];
+                points.push(normalised([0, 1, 2].map(|i| pa[i] + pb[i])));
+                u32::try_from(points.len() - 1).unwrap_or(u32::MAX)
+            })
+        };
+
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut mesh = MeshData::default();
+    for point in &points {
+        let u = 0.5 + point[0].atan2(point[2]) / TAU;
+        let v = 0.5 + point[1].clamp(-1.0, 1.0).asin() / PI;
+        mesh.vertex(scaled(*point, radius), [u, v], *point);
+    }
+
+    // Triangles straddling the seam get copies of their low `u` vertices shifted past 1, so the
+    // texture does not run backwards across the whole sphere
+    let mut seam_copies = HashMap::new();
+    for triangle in &mut triangles {
+        let us = triangle.map(|index| mesh.vertices[index as usize].texture_coord[0]);
+        if us.iter().copied().fold(f32::MIN, f32::max) - us.iter().copied().fold(f32::MAX, f32::min)
+            <= 0.5
+        {
+            continue;
+        }
+
+        for index in triangle.iter_mut() {
+            let i = *index as usize;
+            let [u, v] = mesh.vertices[i].texture_coord;
+            if u < 0.5 {
+                *index = *seam_copies.entry(*index).or_insert_with(|| {
+                    let point = points[i];
+                    mesh.vertex(scaled(point, radius), [u + 1.0, v], point)
+                });
+            }
+        }
+    }
+
+    mesh.elements = triangles.into_iter().flatten().collect();
+    mesh.build(material)
+}
+
+fn normalised(vector: [f32; 3]) -> [f32; 3] {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    scaled(vector, 1.0 / length)
+}
+
+/// A flat rectangle on the xz plane facing up, centred on the origin and split into
+/// `x_segments` by `z_segments` quads. `v` increases towards -z.
+/// # Errors
+/// Returns an error if either segment count is 0
+pub fn plane(
+    width: f32,
+    depth: f32,
+    x_segments: u32,
+    z_segments: u32,
+    material: Material,
+) -> Result<Builder> {
+    check_segments("x_segments", x_segments, 1)?;
+    check_segments("z_segments", z_segments, 1)?;
+
+    let mut mesh = MeshData::default();
+    mesh.grid(x_segments, z_segments, |u, row| {
+        let v = row as f32 / z_segments as f32;
+        ([(u - 0.5) * width, 0.0, (0.5 - v) * depth], [0.0, 1.0, 0.0])
+    });
+    mesh.build(material)
+}
+
+/// A capped cylinder along the y axis, centred on the origin
+/// # Errors
+/// Returns an error if `sectors` is less than 3
+pub fn cylinder(radius: f32, height: f32, sectors: u32, material: Material) -> Result<Builder> {
+    check_segments("sectors", sectors, 3)?;
+
+    let mut mesh = MeshData::default();
+    mesh.grid(sectors, 1, |u, row| {
+        let (sin, cos) = (u * TAU).sin_cos();
+        let y = (row as f32 - 0.5) * height;
+        ([radius * sin, y, radius * cos], [sin, 0.0, cos])
+    });
+    mesh.disc(radius, 0.5 * height, sectors, true);
+    mesh.disc(radius, -0.5 * height, sectors, false);
+    mesh.build(material)
+}
+
+/// A cone along the y axis with its base at `-height / 2` and its point at `height / 2`
+/// # Errors
+/// Returns an error if `sectors` is less than 3
+pub fn cone(radius: f32, height: f32, sectors: u32, material: Material) -> Result<Builder> {
+    check_segments("sectors", sectors, 3)?;
+
+    let slope = normalised([height, radius, 0.0]);
+
+    let mut mesh = MeshData::default();
+    mesh.grid(sectors, 1, |u, row| {
+        let (sin, cos) = (u * TAU).sin_cos();
+        // Collapses to the point on the top row, keeping the normal of each side
+        let ring = radius * (1 - row) as f32;
+        let y = (row as f32 - 0.5) * height;
+        (
+            [ring * sin, y, ring * cos],
+            [slope[0] * sin, slope[1], slope[0] * cos],
+        )
+    });
+    mesh.disc(radius, -0.5 * height, sectors, false);
+    mesh.build(material)
+}
+
+/// A ring around the y axis. `major_radius` is from the centre to the middle of the tube and
+/// `minor_radius` that of the tube itself.
+/// # Errors
+/// Returns an error if either segment count is less than 3
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+    material: Material,
+) -> Result<Builder> {
+    check_segments("major_segments", major_segments, 3)?;
+    check_segments("minor_segments", minor_segments, 3)?;
+
+    let mut mesh = MeshData::default();
+    mesh.grid(major_segments, minor_segments, |u, row| {
+        let (sin_major, cos_major) = (u * TAU).sin_cos();
+        let (sin_minor, cos_minor) = (row as f32 / minor_segments as f32 * TAU).sin_cos();
+        let ring = minor_radius.mul_add(cos_minor, major_radius);
+        (
+            [ring * sin_major, minor_radius * sin_minor, ring * cos_major],
+            [cos_minor * sin_major, sin_minor, cos_minor * cos_major],
+        )
+    });
+    mesh.build(material)
+}
+
+/// A cylinder along the y axis with hemispherical ends. `height` is that of the cylinder
+/// between the centres of the two hemispheres, each of which has `stacks` rows.
+/// # Errors
+/// Returns an error if `sectors` is less than 3 or `stacks` is 0
+pub fn capsule(
+    radius: f32,
+    height: f32,
+    sectors: u32,
+    stacks: u32,
+    material: Material,
+) -> Result<Builder> {
+    check_segments("sectors", sectors, 3)?;
+    check_segments("stacks", stacks, 1)?;
+
+    // The bottom hemisphere takes rows 0 to `stacks`, the top one the rest, and the cylinder is
+    // the quads between them
+    let mut mesh = MeshData::default();
+    mesh.grid(sectors, 2 * stacks + 1, |u, row| {
+        let (latitude, centre) = if row <= stacks {
+            (
+                (row as f32 / stacks as f32 - 1.0) * FRAC_PI_2,
+                -0.5 * height,
+            )
+        } else {
+            (
+                (row - stacks - 1) as f32 / stacks as f32 * FRAC_PI_2,
+                0.5 * height,
+            )
+        };
+        let normal = on_sphere(u * TAU, latitude);
+        let position = scaled(normal, radius);
+        ([position[0], position[1] + centre, position[2]], normal)
+    });
+    mesh.build(material)
+}
+
+/// A flat rectangle facing +z.
+///
+/// Covers the whole screen in normalised device coordinates unless given a `rect`.
+#[derive(Clone, Debug)]
+pub struct QuadBuilder {
+    min: [f32; 2],
+    max: [f32; 2],
+    depth: f32,
+    material: Material,
+}
+
+impl Default for QuadBuilder {
+    fn default() -> Self {
+        Self {
+            min: [-1.0, -1.0],
+            max: [1.0, 1.0],
+            depth: 0.0,
+            material: Material::blank(),
+        }
+    }
+}
+
+impl QuadBuilder {
+    /// Cover the whole screen when drawn without a camera, as by `quad_vert.vert`
+    #[must_use]
+    #[inline]
+    pub fn whole_screen(self) -> Self {
+        self.rect([-1.0, -1.0], [1.0, 1.0], 0.0)
+    }
+
+    /// Span from `min` to `max` on the xy plane at z = `depth`
+    #[must_use]
+    #[inline]
+    pub const fn rect(mut self, min: [f32; 2], max: [f32; 2], depth: f32) -> Self {
+        self.min = min;
+        self.max = max;
+        self.depth = depth;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub(crate) fn vertex_array(&self) -> Result<VertexArray> {
+        let ([x0, y0], [x1, y1], z) = (self.min, self.max, self.depth);
+
+        let normal = [0.0, 0.0, 1.0];
+        let mut vertices = vec![
+            MeshVertex::new([x0, y0, z], [0.0, 0.0], normal),
+            MeshVertex::new([x1, y0, z], [1.0, 0.0], normal),
+            MeshVertex::new([x1, y1, z], [1.0, 1.0], normal),
+            MeshVertex::new([x0, y1, z], [0.0, 1.0], normal),
+        ];
+        let mut elements = vec![0, 1, 2, 0, 2, 3];
+        MeshVertex::generate_tangents(&mut vertices, &mut elements)?;
+
+        VertexArray::builder()
+            .vertices(&vertices)
+            .element_buffer(elements)
+            .build()
+    }
+
+    /// # Errors
+    /// Returns an error if the vertex array cannot be built
+    #[inline]
+    pub fn build(self) -> Result<Builder> {
+        let vertex_array = self.vertex_array()?;
+        Ok(Model::builder().mesh(Mesh::new(vertex_array, self.material)))
+    }
+}
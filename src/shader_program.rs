@@ -1,8 +1,14 @@
-use std::{ffi::CString, fs, ptr};
+use std::{
+    ffi::CString,
+    fs, io,
+    path::Path,
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::linear_algebra::matrix::Matrix;
 use crate::EngineError::ShaderErr;
-use crate::{error_fmt, Result};
+use crate::{capabilities::GlCapabilities, error_fmt, gl_state, Result};
 use crate::{material::Material, texture::Texture};
 
 struct Shader {
@@ -11,7 +17,7 @@ struct Shader {
 
 impl Shader {
     fn new_generic_shader(type_: u32, source: &str) -> Self {
-        let shader_source = CString::new(fs::read_to_string(source).unwrap()).unwrap();
+        let shader_source = CString::new(read_source(Path::new(source)).unwrap()).unwrap();
         unsafe {
             let shader_id = gl::CreateShader(type_);
             gl::ShaderSource(shader_id, 1, &shader_source.as_ptr(), ptr::null());
@@ -21,14 +27,47 @@ impl Shader {
     }
 }
 
+/// The source at `path` with every `#include "file"` line replaced by the source of `file`,
+/// found beside it, as GLSL has no includes of its own
+fn read_source(path: &Path) -> io::Result<String> {
+    let lines = fs::read_to_string(path)?
+        .lines()
+        .map(|line| {
+            line.trim().strip_prefix("#include").map_or_else(
+                || Ok(line.to_owned()),
+                |file| read_source(&path.with_file_name(file.trim().trim_matches('"'))),
+            )
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(lines.join("\n"))
+}
+
 impl Drop for Shader {
     fn drop(&mut self) {
         unsafe { gl::DeleteShader(self.id) }
     }
 }
 
+/// A vertex shader input, as reported by the linked program
+#[derive(Clone, Debug)]
+pub(crate) struct ActiveAttribute {
+    pub name: String,
+    pub location: u32,
+    /// Of each location, the rows of a matrix
+    pub components: i32,
+    /// Consecutive locations from `location`, one per column of a matrix
+    pub locations: u32,
+    pub integer: bool,
+}
+
+/// Numbers every program ever linked, from 1, as OpenGL reuses the ids of deleted programs
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
 pub struct ShaderProgram {
     id: u32,
+    /// Unlike `id`, never shared with a program linked later
+    generation: u64,
+    attributes: Vec<ActiveAttribute>,
 }
 
 impl ShaderProgram {
@@ -40,9 +79,7 @@ impl ShaderProgram {
 
     #[inline]
     pub fn use_program(&self) {
-        unsafe {
-            gl::UseProgram(self.id);
-        }
+        gl_state::use_program(self.id);
     }
 
     /// # Errors
@@ -58,27 +95,51 @@ impl ShaderProgram {
         }
     }
 
+    /// Bind each texture to the unit of its position in the list and point its sampler uniform
+    /// at it. The highest few units are left alone for the textures the engine keeps bound.
     /// # Errors
+    /// Returns an error if the list would reach those units, which start 4 below
+    /// `GlCapabilities::max_texture_units`
     #[inline]
     pub fn bind_textures(&self, texture_list: Vec<(&Texture, &str)>) -> Result<()> {
-        for (index, (tex, name)) in texture_list.into_iter().enumerate() {
-            tex.bind_to(index.try_into().expect("Texture index cannot exceed u32"))?;
-            
-            self.set_uniform_iv(
-                name,
-                [index.try_into().expect("Texture index cannot exceed u32")],
-            )?;
+        let units = GlCapabilities::unreserved_texture_units();
+        if texture_list.len() > units as usize {
+            return Err(ShaderErr(error_fmt!(
+                shader_program::ShaderProgram,
+                "Cannot bind {} textures, only units below {units} are free for them",
+                texture_list.len()
+            )));
+        }
 
+        for (unit, (texture, name)) in (0..).zip(texture_list) {
+            self.bind_texture_to_unit(texture, name, unit)?;
         }
         Ok(())
     }
 
+    /// Bind a single texture to a specific unit, for textures that must stay bound while
+    /// `bind_textures` reuses the low units for each material
+    /// # Errors
+    #[inline]
+    pub fn bind_texture_to_unit(&self, texture: &Texture, name: &str, unit: u32) -> Result<()> {
+        texture.bind_to(unit)?;
+        self.set_uniform_iv(
+            name,
+            [unit.try_into().map_err(|_| {
+                ShaderErr(error_fmt!(
+                    shader_program::ShaderProgram,
+                    "Texture unit {unit} exceeds i32"
+                ))
+            })?],
+        )
+    }
+
     /// # Errors
     #[inline]
     pub fn set_uniform_iv<const N: usize>(&self, name: &str, value: [i32; N]) -> Result<()> {
         let uniform_location = self.get_uniform_location(name)?;
+        self.use_program();
         unsafe {
-            gl::UseProgram(self.id);
             match N {
                 1 => gl::Uniform1i(uniform_location, value[0]),
                 2 => gl::Uniform2i(uniform_location, value[0], value[1]),
@@ -99,8 +160,8 @@ impl ShaderProgram {
     #[inline]
     pub fn set_uniform_mat4f(&self, name: &str, value: Matrix<4, 4>) -> Result<()> {
         let uniform_location = self.get_uniform_location(name)?;
+        self.use_program();
         unsafe {
-            gl::UseProgram(self.id);
             gl::UniformMatrix4fv(
                 uniform_location,
                 1,
@@ -115,8 +176,8 @@ impl ShaderProgram {
     #[inline]
     pub fn set_uniform_fv<const N: usize>(&self, name: &str, value: [f32; N]) -> Result<()> {
         let uniform_location = self.get_uniform_location(name)?;
+        self.use_program();
         unsafe {
-            gl::UseProgram(self.id);
             match N {
                 1 => gl::Uniform1f(uniform_location, value[0]),
                 2 => gl::Uniform2f(uniform_location, value[0], value[1]),
@@ -133,10 +194,51 @@ impl ShaderProgram {
         Ok(())
     }
 
+    /// The current value of the first `N` components of a float uniform, zeros if the program
+    /// has no uniform `name`
+    /// # Errors
+    pub(crate) fn uniform_fv<const N: usize>(&self, name: &str) -> Result<[f32; N]> {
+        let uniform_location = self.get_uniform_location(name)?;
+        // Room for the largest uniform, a 4x4 matrix, whatever its actual type
+        let mut value = [0.0; 16];
+        if uniform_location != -1 {
+            unsafe { gl::GetUniformfv(self.id, uniform_location, value.as_mut_ptr()) }
+        }
+        Ok(std::array::from_fn(|i| {
+            value.get(i).copied().unwrap_or_default()
+        }))
+    }
+
+    /// The current value of the first `N` components of an integer uniform, zeros if the
+    /// program has no uniform `name`
+    /// # Errors
+    pub(crate) fn uniform_iv<const N: usize>(&self, name: &str) -> Result<[i32; N]> {
+        let uniform_location = self.get_uniform_location(name)?;
+        let mut value = [0; 16];
+        if uniform_location != -1 {
+            unsafe { gl::GetUniformiv(self.id, uniform_location, value.as_mut_ptr()) }
+        }
+        Ok(std::array::from_fn(|i| {
+            value.get(i).copied().unwrap_or_default()
+        }))
+    }
+
+    /// The current value of a matrix uniform, zeros if the program has no uniform `name`
+    /// # Errors
+    pub(crate) fn uniform_mat4f(&self, name: &str) -> Result<Matrix<4, 4>> {
+        let value = self.uniform_fv::<16>(name)?;
+        Ok(Matrix::from_col_major(
+            std::array::from_fn::<[f32; 4], 4, _>(|column| {
+                std::array::from_fn(|row| value[column * 4 + row])
+            }),
+        ))
+    }
+
     /// # Errors
     #[inline]
     pub fn bind_material(&self, material: &Material) -> Result<()> {
         // unsafe {gl::UseProgram(self.id); }
+        gl_state::forget_materials(self.id);
 
         self.set_uniform_fv("material.shininess", [32.0])?;
         self.bind_textures(vec![
@@ -154,6 +256,82 @@ impl ShaderProgram {
     pub const fn id(&self) -> u32 {
         self.id
     }
+
+    /// Identifies this program among every program linked, unlike `id` which OpenGL reuses
+    pub(crate) const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The vertex shader inputs actually used by the program, excluding built-ins
+    pub(crate) fn active_attributes(&self) -> &[ActiveAttribute] {
+        &self.attributes
+    }
+}
+
+/// Query every active vertex shader input of a linked program
+fn query_attributes(program_id: u32) -> Vec<ActiveAttribute> {
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program_id, gl::ACTIVE_ATTRIBUTES, &mut count);
+        gl::GetProgramiv(program_id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+    }
+
+    let mut name_buffer = vec![0u8; max_length.max(1).unsigned_abs() as usize];
+
+    (0..count.unsigned_abs())
+        .filter_map(|index| {
+            let mut length = 0;
+            let mut size = 0;
+            let mut type_ = 0;
+            unsafe {
+                gl::GetActiveAttrib(
+                    program_id,
+                    index,
+                    max_length,
+                    &mut length,
+                    &mut size,
+                    &mut type_,
+                    name_buffer.as_mut_ptr().cast(),
+                );
+            }
+
+            let name = String::from_utf8_lossy(&name_buffer[..length.unsigned_abs() as usize])
+                .into_owned();
+            if name.starts_with("gl_") {
+                return None;
+            }
+
+            let location = unsafe {
+                let c_name = CString::new(name.as_str()).ok()?;
+                gl::GetAttribLocation(program_id, c_name.as_ptr().cast())
+            };
+
+            let (components, locations, integer) = match type_ {
+                gl::FLOAT => (1, 1, false),
+                gl::FLOAT_VEC2 => (2, 1, false),
+                gl::FLOAT_VEC3 => (3, 1, false),
+                gl::FLOAT_VEC4 => (4, 1, false),
+                gl::FLOAT_MAT2 => (2, 2, false),
+                gl::FLOAT_MAT3 => (3, 3, false),
+                gl::FLOAT_MAT4 => (4, 4, false),
+                gl::INT | gl::UNSIGNED_INT => (1, 1, true),
+                gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (2, 1, true),
+                gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (3, 1, true),
+                gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => (4, 1, true),
+                // Non-square matrices and doubles are not checked
+                _ => return None,
+            };
+
+            Some(ActiveAttribute {
+                name,
+                location: location.try_into().ok()?,
+                components,
+                locations,
+                integer,
+            })
+        })
+        .collect()
 }
 
 impl Drop for ShaderProgram {
@@ -162,6 +340,7 @@ impl Drop for ShaderProgram {
         unsafe {
             gl::DeleteProgram(self.id);
         }
+        gl_state::forget_program(self.id);
     }
 }
 
@@ -216,7 +395,39 @@ impl Builder {
             );
 
             gl::LinkProgram(program_id);
-            Ok(ShaderProgram { id: program_id })
+            Ok(ShaderProgram {
+                id: program_id,
+                generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+                attributes: query_attributes(program_id),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_replaced_by_their_source() {
+        let directory = std::env::temp_dir().join("shader_program_includes");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("main.frag"), "#version 330 core\n#include \"a.glsl\"\nmain\n")
+            .unwrap();
+        fs::write(directory.join("a.glsl"), "a\n  #include \"b.glsl\"\n").unwrap();
+        fs::write(directory.join("b.glsl"), "b").unwrap();
+
+        let source = read_source(&directory.join("main.frag")).unwrap();
+        assert_eq!(source, "#version 330 core\na\nb\nmain");
+        assert!(read_source(&directory.join("missing.frag")).is_err());
+    }
+
+    #[test]
+    fn bundled_shaders_include_the_lod_dither() {
+        for shader in ["fragment_shader.frag", "pbr.frag", "ssao_geometry.frag"] {
+            let source = read_source(&Path::new("src/shaders").join(shader)).unwrap();
+            assert_eq!(source.matches("bool lod_discard()").count(), 1, "{shader}");
+            assert!(!source.contains("#include"), "{shader}");
         }
     }
 }
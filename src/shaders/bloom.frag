@@ -0,0 +1,96 @@
+#version 330 core
+
+in vec2 texture_coord;
+
+out vec4 frag_colour;
+
+const int PREFILTER = 0;
+const int DOWNSAMPLE = 1;
+const int UPSAMPLE = 2;
+const int COMPOSITE = 3;
+
+uniform int stage;
+
+uniform sampler2D source;
+uniform sampler2D emission;
+uniform sampler2D bloom;
+
+uniform float threshold;
+uniform float soft_knee;
+uniform float radius;
+uniform float intensity;
+
+vec3 downsample(sampler2D tex);
+vec3 upsample(sampler2D tex);
+vec3 bright_pass(vec3 colour);
+
+void main() {
+    if (stage == PREFILTER) {
+        vec3 bright = bright_pass(downsample(source)) + texture(emission, texture_coord).rgb;
+        frag_colour = vec4(bright, 1.0);
+    } else if (stage == DOWNSAMPLE) {
+        frag_colour = vec4(downsample(source), 1.0);
+    } else if (stage == UPSAMPLE) {
+        frag_colour = vec4(upsample(source), 1.0);
+    } else {
+        vec4 scene = texture(source, texture_coord);
+        frag_colour = vec4(scene.rgb + texture(bloom, texture_coord).rgb * intensity, scene.a);
+    }
+}
+
+// 13 tap filter from "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+vec3 downsample(sampler2D tex) {
+    vec2 texel = 1.0 / vec2(textureSize(tex, 0));
+    vec2 uv = texture_coord;
+
+    vec3 a = texture(tex, uv + texel * vec2(-2.0,  2.0)).rgb;
+    vec3 b = texture(tex, uv + texel * vec2( 0.0,  2.0)).rgb;
+    vec3 c = texture(tex, uv + texel * vec2( 2.0,  2.0)).rgb;
+    vec3 d = texture(tex, uv + texel * vec2(-2.0,  0.0)).rgb;
+    vec3 e = texture(tex, uv).rgb;
+    vec3 f = texture(tex, uv + texel * vec2( 2.0,  0.0)).rgb;
+    vec3 g = texture(tex, uv + texel * vec2(-2.0, -2.0)).rgb;
+    vec3 h = texture(tex, uv + texel * vec2( 0.0, -2.0)).rgb;
+    vec3 i = texture(tex, uv + texel * vec2( 2.0, -2.0)).rgb;
+    vec3 j = texture(tex, uv + texel * vec2(-1.0,  1.0)).rgb;
+    vec3 k = texture(tex, uv + texel * vec2( 1.0,  1.0)).rgb;
+    vec3 l = texture(tex, uv + texel * vec2(-1.0, -1.0)).rgb;
+    vec3 m = texture(tex, uv + texel * vec2( 1.0, -1.0)).rgb;
+
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// 3x3 tent filter, `radius` is measured in texels of `tex`
+vec3 upsample(sampler2D tex) {
+    vec2 offset = radius / vec2(textureSize(tex, 0));
+    vec2 uv = texture_coord;
+
+    vec3 sum = texture(tex, uv).rgb * 4.0;
+    sum += (
+        texture(tex, uv + vec2( 0.0,       offset.y)).rgb
+        + texture(tex, uv + vec2(-offset.x, 0.0     )).rgb
+        + texture(tex, uv + vec2( offset.x, 0.0     )).rgb
+        + texture(tex, uv + vec2( 0.0,     -offset.y)).rgb
+    ) * 2.0;
+    sum += texture(tex, uv + vec2(-offset.x,  offset.y)).rgb
+        + texture(tex, uv + vec2( offset.x,  offset.y)).rgb
+        + texture(tex, uv + vec2(-offset.x, -offset.y)).rgb
+        + texture(tex, uv + vec2( offset.x, -offset.y)).rgb;
+
+    return sum / 16.0;
+}
+
+// Keep only the part of `colour` above `threshold`, easing in over the soft knee
+vec3 bright_pass(vec3 colour) {
+    float brightness = max(colour.r, max(colour.g, colour.b));
+    float knee = threshold * soft_knee + 0.00001;
+
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+
+    float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+    return colour * contribution;
+}
@@ -0,0 +1,77 @@
+#version 330 core
+
+in vec2 texture_coord;
+
+out vec2 frag_colour;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+vec2 hammersley(uint i, uint count);
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness);
+float geometry_schlick_ggx(float n_dot_x, float roughness);
+
+// Split-sum scale (red) and bias (green) applied to F0, indexed by n·v and roughness
+void main() {
+    float n_dot_v = max(texture_coord.x, 0.0001);
+    float roughness = texture_coord.y;
+
+    vec3 view_dir = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    vec3 normal = vec3(0.0, 0.0, 1.0);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 halfway = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), normal, roughness);
+        vec3 light_dir = normalize(2.0 * dot(view_dir, halfway) * halfway - view_dir);
+
+        float n_dot_l = max(light_dir.z, 0.0);
+        float n_dot_h = max(halfway.z, 0.0);
+        float v_dot_h = max(dot(view_dir, halfway), 0.0);
+
+        if (n_dot_l > 0.0) {
+            float geometry = geometry_schlick_ggx(n_dot_v, roughness)
+                * geometry_schlick_ggx(n_dot_l, roughness);
+            float visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            float fresnel = pow(1.0 - v_dot_h, 5.0);
+
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    frag_colour = vec2(scale, bias) / float(SAMPLE_COUNT);
+}
+
+vec2 hammersley(uint i, uint count) {
+    uint bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness) {
+    float a = roughness * roughness;
+
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 halfway = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+
+    return normalize(tangent * halfway.x + bitangent * halfway.y + normal * halfway.z);
+}
+
+// Image based lighting uses k = roughness² / 2 rather than the (roughness + 1)² / 8 of
+// direct lighting
+float geometry_schlick_ggx(float n_dot_x, float roughness) {
+    float k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+
+layout (location = 0) in vec3 in_position;
+
+out vec3 local_position;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+void main() {
+    local_position = in_position;
+    gl_Position = projection * view * vec4(in_position, 1.0);
+}
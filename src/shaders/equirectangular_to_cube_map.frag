@@ -0,0 +1,16 @@
+#version 330 core
+
+in vec3 local_position;
+
+out vec4 frag_colour;
+
+uniform sampler2D equirectangular;
+
+const vec2 INV_ATAN = vec2(0.1591, 0.3183);
+
+void main() {
+    vec3 direction = normalize(local_position);
+    vec2 uv = vec2(atan(direction.z, direction.x), asin(direction.y)) * INV_ATAN + 0.5;
+
+    frag_colour = vec4(texture(equirectangular, uv).rgb, 1.0);
+}
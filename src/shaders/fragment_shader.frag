@@ -3,8 +3,11 @@
 in vec2 texture_coord;
 in vec3 frag_normal;
 in vec3 frag_position;
+in vec4 frag_tangent;
+in vec4 frag_tint; // per-instance, white otherwise
 
-out vec4 frag_colour;
+layout (location = 0) out vec4 frag_colour;
+layout (location = 1) out vec4 emission_colour;
 
 struct LightingProperties {
     vec3 normal;
@@ -63,8 +66,13 @@ struct Material {
     sampler2D specular_map; // specular map is a mask 
     sampler2D emission; // emission is a texture and so needs a mask
     sampler2D emission_map;
+    sampler2D metallic_roughness; // unused, PBR only
+    sampler2D normal_map; // tangent space
+    sampler2D ambient_occlusion; // unused, PBR only
+    sampler2D height_map; // white is the surface
 
     float shininess;
+    float height_scale;
 };
 
 uniform Material material;
@@ -75,7 +83,22 @@ uniform SpotLight torch;
 uniform float time;
 uniform vec3 camera_position;
 
-LightingProperties lighting_properties();
+uniform sampler2D ssao;
+uniform bool use_ssao;
+
+#include "lod_dither.glsl"
+
+const int MAX_PARALLAX_LAYERS = 32;
+
+// Scales every light's ambient term, 1.0 when screen-space ambient occlusion is off
+float ambient_occlusion = 1.0;
+
+// Texture coordinates after parallax occlusion mapping
+vec2 uv;
+
+mat3 tangent_frame();
+vec2 parallax_occlusion(vec2, vec3);
+LightingProperties lighting_properties(mat3);
 GenericOutput generic_light(GenericLight, LightingProperties);
 float attenuation(vec3);
 vec4 PointLight_illuminate(PointLight, LightingProperties);
@@ -83,8 +106,21 @@ vec4 FarLight_illuminate(FarLight, LightingProperties);
 vec4 SpotLight_illuminate(SpotLight, LightingProperties);
 
 void main() {
-    
-    LightingProperties prop = lighting_properties();
+    if (lod_discard()) {
+        discard;
+    }
+
+    if (use_ssao) {
+        ambient_occlusion = texture(ssao, gl_FragCoord.xy / vec2(textureSize(ssao, 0))).r;
+    }
+
+    mat3 tbn = tangent_frame();
+    uv = parallax_occlusion(
+        texture_coord,
+        normalize(transpose(tbn) * (camera_position - frag_position))
+    );
+
+    LightingProperties prop = lighting_properties(tbn);
 
     // Illumination
     vec4 illumination = 
@@ -94,18 +130,22 @@ void main() {
     ;
     
     // Emission
-    vec4 emission = texture(material.emission, texture_coord + vec2(0, 0.5 * time))
-        * texture(material.emission_map, texture_coord);
+    vec4 emission = texture(material.emission, uv + vec2(0, 0.5 * time))
+        * texture(material.emission_map, uv);
     
     frag_colour = illumination + emission;
     
     if (frag_colour.a < 0.01) {
         discard;
     }
+
+    // Picked up by bloom when the framebuffer has a second colour attachment
+    emission_colour = vec4(emission.rgb * emission.a, 1.0);
 }
 
-LightingProperties lighting_properties() {
-    vec3 normal = normalize(frag_normal);
+LightingProperties lighting_properties(mat3 tbn) {
+    vec3 mapped = texture(material.normal_map, uv).xyz * 2.0 - 1.0;
+    vec3 normal = normalize(tbn * mapped);
     vec3 frag_to_camera = camera_position - frag_position;
     vec3 view_dir = normalize(frag_to_camera);
 
@@ -116,18 +156,76 @@ LightingProperties lighting_properties() {
     );
 }
 
+// Tangent to world space, from the mesh's tangents when it has them and from screen space
+// derivatives otherwise
+mat3 tangent_frame() {
+    vec3 normal = normalize(frag_normal);
+
+    if (dot(frag_tangent.xyz, frag_tangent.xyz) > 0.000001) {
+        vec3 tangent = normalize(frag_tangent.xyz - normal * dot(normal, frag_tangent.xyz));
+        vec3 bitangent = frag_tangent.w * cross(normal, tangent);
+        return mat3(tangent, bitangent, normal);
+    }
+
+    vec3 dp1 = dFdx(frag_position);
+    vec3 dp2 = dFdy(frag_position);
+    vec2 duv1 = dFdx(texture_coord);
+    vec2 duv2 = dFdy(texture_coord);
+
+    vec3 dp2_perp = cross(dp2, normal);
+    vec3 dp1_perp = cross(normal, dp1);
+    vec3 tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    vec3 bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+
+    float inverse_max = inversesqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12));
+    return mat3(tangent * inverse_max, bitangent * inverse_max, normal);
+}
+
+// Step into the height map along the tangent-space view direction until the ray passes below
+// the surface, then interpolate between the last two steps
+vec2 parallax_occlusion(vec2 start_uv, vec3 view_tangent) {
+    vec2 uv_dx = dFdx(start_uv);
+    vec2 uv_dy = dFdy(start_uv);
+
+    float depth = 1.0 - textureGrad(material.height_map, start_uv, uv_dx, uv_dy).r;
+    if (material.height_scale <= 0.0 || depth <= 0.0) {
+        return start_uv;
+    }
+
+    // More layers at grazing angles, where the offset is largest
+    float layers = mix(float(MAX_PARALLAX_LAYERS), 8.0, abs(view_tangent.z));
+    float layer_depth = 1.0 / layers;
+    vec2 delta = view_tangent.xy / max(view_tangent.z, 0.05) * material.height_scale / layers;
+
+    vec2 current_uv = start_uv;
+    float current_layer = 0.0;
+    for (int i = 0; i < MAX_PARALLAX_LAYERS && current_layer < depth; ++i) {
+        current_uv -= delta;
+        depth = 1.0 - textureGrad(material.height_map, current_uv, uv_dx, uv_dy).r;
+        current_layer += layer_depth;
+    }
+
+    vec2 previous_uv = current_uv + delta;
+    float after = depth - current_layer;
+    float before = 1.0 - textureGrad(material.height_map, previous_uv, uv_dx, uv_dy).r
+        - current_layer + layer_depth;
+    float weight = after / min(after - before, -0.00001);
+
+    return mix(current_uv, previous_uv, weight);
+}
+
 GenericOutput generic_light(GenericLight light, LightingProperties prop) {
-    vec4 diffuse_map = texture(material.diffuse, texture_coord);
+    vec4 diffuse_map = texture(material.diffuse, uv) * frag_tint;
     
     // Ambient
-    vec4 ambient = vec4(light.ambient, 1.0) * diffuse_map;
+    vec4 ambient = vec4(light.ambient * ambient_occlusion, 1.0) * diffuse_map;
     
     // Diffuse
     float diffuse_intensity = max(0.0, dot(prop.normal, light.light_dir));
     vec4 diffuse = vec4(light.diffuse, 1.0) * diffuse_intensity * diffuse_map;
 
     // Specular
-    vec4 specular_map = texture(material.specular_map, texture_coord);
+    vec4 specular_map = texture(material.specular_map, uv);
 
     vec3 reflect_dir = reflect(-light.light_dir, prop.normal);
     float cos_reflect_angle = max(dot(prop.view_dir, reflect_dir), 0.0);
@@ -209,4 +307,4 @@ vec4 SpotLight_illuminate(SpotLight light, LightingProperties prop) {
 
     // Return
     return gen_out.ambient + (gen_out.diffuse + gen_out.specular) * light_attenuation * intensity;
-}
\ No newline at end of file
+}
@@ -0,0 +1,32 @@
+#version 330 core
+
+in vec3 local_position;
+
+out vec4 frag_colour;
+
+uniform samplerCube environment;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+// Cosine weighted average of the environment over the hemisphere about each normal
+void main() {
+    vec3 normal = normalize(local_position);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, normal));
+    up = normalize(cross(normal, right));
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangent.x * right + tangent.y * up + tangent.z * normal;
+
+            irradiance += texture(environment, direction).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    frag_colour = vec4(PI * irradiance / samples, 1.0);
+}
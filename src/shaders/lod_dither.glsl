@@ -0,0 +1,19 @@
+// Cross-fade between levels of detail, negative while fading out, positive while fading in
+uniform float lod_fade;
+
+// Ordered dithering so that the fading in and fading out levels cover complementary pixels
+bool lod_discard() {
+    if (lod_fade == 0.0) {
+        return false;
+    }
+
+    const float bayer[16] = float[](
+         0.0,  8.0,  2.0, 10.0,
+        12.0,  4.0, 14.0,  6.0,
+         3.0, 11.0,  1.0,  9.0,
+        15.0,  7.0, 13.0,  5.0
+    );
+    ivec2 cell = ivec2(gl_FragCoord.xy) % 4;
+    float threshold = (bayer[cell.y * 4 + cell.x] + 0.5) / 16.0;
+    return (lod_fade > 0.0) != (threshold < abs(lod_fade));
+}
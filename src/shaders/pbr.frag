@@ -0,0 +1,273 @@
+#version 330 core
+
+in vec2 texture_coord;
+in vec3 frag_normal;
+in vec3 frag_position;
+in vec4 frag_tangent;
+in vec4 frag_tint; // per-instance, white otherwise
+
+layout (location = 0) out vec4 frag_colour;
+layout (location = 1) out vec4 emission_colour;
+
+struct PointLight {
+    vec3 position;
+
+    vec3 attenuation;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct FarLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    vec3 attenuation;
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    float cos_cut_off;
+    float outer_cut_off;
+    float cos_outer_cut_off;
+};
+
+struct Material {
+    sampler2D diffuse; // albedo
+    sampler2D specular_map; // unused, Phong only
+    sampler2D emission;
+    sampler2D emission_map;
+    sampler2D metallic_roughness; // roughness in green, metallic in blue
+    sampler2D normal_map; // tangent space
+    sampler2D ambient_occlusion;
+    sampler2D height_map; // white is the surface
+
+    float shininess; // unused, Phong only
+    float height_scale;
+};
+
+// Everything the BRDF needs to know about the fragment
+struct Surface {
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    vec3 normal;
+    vec3 view_dir;
+    vec3 f0;
+};
+
+uniform Material material;
+uniform PointLight light;
+uniform FarLight sun;
+uniform SpotLight torch;
+
+uniform vec3 camera_position;
+
+uniform sampler2D ssao;
+uniform bool use_ssao;
+
+uniform samplerCube irradiance_map;
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform bool use_ibl;
+uniform float max_reflection_lod;
+uniform float ibl_intensity;
+
+#include "lod_dither.glsl"
+
+const float PI = 3.14159265359;
+const int MAX_PARALLAX_LAYERS = 32;
+
+mat3 tangent_frame();
+vec2 parallax_occlusion(vec2, vec3);
+vec3 cook_torrance(Surface, vec3, vec3);
+vec3 fresnel_schlick(float, vec3);
+vec3 fresnel_schlick_roughness(float, vec3, float);
+float attenuation(vec3, float);
+
+void main() {
+    if (lod_discard()) {
+        discard;
+    }
+
+    vec3 view_dir = normalize(camera_position - frag_position);
+    mat3 tbn = tangent_frame();
+    vec2 uv = parallax_occlusion(texture_coord, normalize(transpose(tbn) * view_dir));
+
+    vec4 albedo = texture(material.diffuse, uv) * frag_tint;
+    if (albedo.a < 0.01) {
+        discard;
+    }
+
+    vec4 metallic_roughness = texture(material.metallic_roughness, uv);
+
+    Surface surface;
+    surface.albedo = albedo.rgb;
+    surface.metallic = clamp(metallic_roughness.b, 0.0, 1.0);
+    surface.roughness = clamp(metallic_roughness.g, 0.04, 1.0);
+    surface.normal = normalize(tbn * (texture(material.normal_map, uv).xyz * 2.0 - 1.0));
+    surface.view_dir = view_dir;
+    surface.f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+
+    float occlusion = texture(material.ambient_occlusion, uv).r;
+    if (use_ssao) {
+        occlusion *= texture(ssao, gl_FragCoord.xy / vec2(textureSize(ssao, 0))).r;
+    }
+
+    // Direct lighting, only the diffuse colour of each light is used as its radiance
+    vec3 frag_to_light = light.position - frag_position;
+    vec3 direct = cook_torrance(surface, normalize(frag_to_light), light.diffuse)
+        * attenuation(light.attenuation, length(frag_to_light));
+
+    direct += cook_torrance(surface, normalize(-sun.direction), sun.diffuse);
+
+    vec3 frag_to_torch = torch.position - frag_position;
+    vec3 torch_dir = normalize(frag_to_torch);
+    float theta = dot(torch_dir, normalize(-torch.direction));
+    float epsilon = torch.cos_cut_off - torch.cos_outer_cut_off;
+    float intensity = clamp((theta - torch.cos_outer_cut_off) / epsilon, 0.0, 1.0);
+    direct += cook_torrance(surface, torch_dir, torch.diffuse)
+        * attenuation(torch.attenuation, length(frag_to_torch)) * intensity;
+
+    // Ambient lighting, from the environment when there is one
+    vec3 ambient;
+    if (use_ibl) {
+        float n_dot_v = max(dot(surface.normal, surface.view_dir), 0.0);
+        vec3 fresnel = fresnel_schlick_roughness(n_dot_v, surface.f0, surface.roughness);
+        vec3 diffuse_weight = (1.0 - fresnel) * (1.0 - surface.metallic);
+
+        vec3 diffuse = texture(irradiance_map, surface.normal).rgb * surface.albedo;
+
+        vec3 reflected = reflect(-surface.view_dir, surface.normal);
+        vec3 prefiltered = textureLod(
+            prefiltered_map, reflected, surface.roughness * max_reflection_lod
+        ).rgb;
+        vec2 brdf = texture(brdf_lut, vec2(n_dot_v, surface.roughness)).rg;
+        vec3 specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+        ambient = (diffuse_weight * diffuse + specular) * ibl_intensity;
+    } else {
+        ambient = (light.ambient + sun.ambient + torch.ambient) * surface.albedo;
+    }
+
+    // Emission
+    vec4 emission = texture(material.emission, uv) * texture(material.emission_map, uv);
+    vec3 emitted = emission.rgb * emission.a;
+
+    frag_colour = vec4(ambient * occlusion + direct + emitted, albedo.a);
+
+    // Picked up by bloom when the framebuffer has a second colour attachment
+    emission_colour = vec4(emitted, 1.0);
+}
+
+// Tangent to world space, from the mesh's tangents when it has them and from screen space
+// derivatives otherwise
+mat3 tangent_frame() {
+    vec3 normal = normalize(frag_normal);
+
+    if (dot(frag_tangent.xyz, frag_tangent.xyz) > 0.000001) {
+        vec3 tangent = normalize(frag_tangent.xyz - normal * dot(normal, frag_tangent.xyz));
+        vec3 bitangent = frag_tangent.w * cross(normal, tangent);
+        return mat3(tangent, bitangent, normal);
+    }
+
+    vec3 dp1 = dFdx(frag_position);
+    vec3 dp2 = dFdy(frag_position);
+    vec2 duv1 = dFdx(texture_coord);
+    vec2 duv2 = dFdy(texture_coord);
+
+    vec3 dp2_perp = cross(dp2, normal);
+    vec3 dp1_perp = cross(normal, dp1);
+    vec3 tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    vec3 bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+
+    float inverse_max = inversesqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12));
+    return mat3(tangent * inverse_max, bitangent * inverse_max, normal);
+}
+
+// Step into the height map along the tangent-space view direction until the ray passes below
+// the surface, then interpolate between the last two steps
+vec2 parallax_occlusion(vec2 start_uv, vec3 view_tangent) {
+    vec2 uv_dx = dFdx(start_uv);
+    vec2 uv_dy = dFdy(start_uv);
+
+    float depth = 1.0 - textureGrad(material.height_map, start_uv, uv_dx, uv_dy).r;
+    if (material.height_scale <= 0.0 || depth <= 0.0) {
+        return start_uv;
+    }
+
+    // More layers at grazing angles, where the offset is largest
+    float layers = mix(float(MAX_PARALLAX_LAYERS), 8.0, abs(view_tangent.z));
+    float layer_depth = 1.0 / layers;
+    vec2 delta = view_tangent.xy / max(view_tangent.z, 0.05) * material.height_scale / layers;
+
+    vec2 current_uv = start_uv;
+    float current_layer = 0.0;
+    for (int i = 0; i < MAX_PARALLAX_LAYERS && current_layer < depth; ++i) {
+        current_uv -= delta;
+        depth = 1.0 - textureGrad(material.height_map, current_uv, uv_dx, uv_dy).r;
+        current_layer += layer_depth;
+    }
+
+    vec2 previous_uv = current_uv + delta;
+    float after = depth - current_layer;
+    float before = 1.0 - textureGrad(material.height_map, previous_uv, uv_dx, uv_dy).r
+        - current_layer + layer_depth;
+    float weight = after / min(after - before, -0.00001);
+
+    return mix(current_uv, previous_uv, weight);
+}
+
+// Outgoing radiance towards the camera from a light of `radiance` in direction `light_dir`
+vec3 cook_torrance(Surface surface, vec3 light_dir, vec3 radiance) {
+    vec3 halfway = normalize(surface.view_dir + light_dir);
+
+    float n_dot_l = max(dot(surface.normal, light_dir), 0.0);
+    float n_dot_v = max(dot(surface.normal, surface.view_dir), 0.0);
+    float n_dot_h = max(dot(surface.normal, halfway), 0.0);
+
+    // Trowbridge-Reitz GGX normal distribution
+    float a = surface.roughness * surface.roughness;
+    float a2 = a * a;
+    float denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    float distribution = a2 / (PI * denominator * denominator);
+
+    // Smith's method with Schlick-GGX
+    float k = (surface.roughness + 1.0) * (surface.roughness + 1.0) / 8.0;
+    float geometry = (n_dot_v / (n_dot_v * (1.0 - k) + k))
+        * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+
+    vec3 fresnel = fresnel_schlick(max(dot(halfway, surface.view_dir), 0.0), surface.f0);
+
+    vec3 specular = distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l + 0.0001);
+    vec3 diffuse_weight = (1.0 - fresnel) * (1.0 - surface.metallic);
+
+    return (diffuse_weight * surface.albedo / PI + specular) * radiance * n_dot_l;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Rough surfaces reflect less at grazing angles, used for the ambient term where there is no
+// single halfway vector
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+float attenuation(vec3 factors, float light_dist) {
+    return 1.0 / (
+        factors.x
+        + (factors.y * light_dist)
+        + (factors.z * light_dist * light_dist)
+    );
+}
@@ -0,0 +1,115 @@
+#version 330 core
+
+in vec2 texture_coord;
+
+out vec4 frag_colour;
+
+const int COPY = 0;
+const int GREYSCALE = 1;
+const int INVERT = 2;
+const int KERNEL = 3;
+const int VIGNETTE = 4;
+const int COLOUR_GRADE = 5;
+
+uniform sampler2D screen;
+uniform sampler2D lut;
+
+uniform int effect;
+
+uniform float kernel[9];
+uniform float kernel_offset;
+
+uniform float vignette_radius;
+uniform float vignette_softness;
+uniform float vignette_strength;
+
+uniform float lut_size;
+
+vec4 greyscale(vec4 colour);
+vec4 invert(vec4 colour);
+vec4 convolve(vec4 colour);
+vec4 vignette(vec4 colour);
+vec4 colour_grade(vec4 colour);
+
+void main() {
+    vec4 colour = texture(screen, texture_coord);
+
+    switch (effect) {
+        case GREYSCALE:
+            frag_colour = greyscale(colour);
+            break;
+        case INVERT:
+            frag_colour = invert(colour);
+            break;
+        case KERNEL:
+            frag_colour = convolve(colour);
+            break;
+        case VIGNETTE:
+            frag_colour = vignette(colour);
+            break;
+        case COLOUR_GRADE:
+            frag_colour = colour_grade(colour);
+            break;
+        default:
+            frag_colour = colour;
+            break;
+    }
+}
+
+vec4 greyscale(vec4 colour) {
+    float average = 0.2126 * colour.r + 0.7152 * colour.g + 0.0722 * colour.b;
+    return vec4(vec3(average), colour.a);
+}
+
+vec4 invert(vec4 colour) {
+    return vec4(vec3(1.0) - colour.rgb, colour.a);
+}
+
+vec4 convolve(vec4 colour) {
+    vec2 texel = kernel_offset / vec2(textureSize(screen, 0));
+
+    vec2 offsets[9] = vec2[](
+        vec2(-texel.x,  texel.y), // top-left
+        vec2( 0.0,      texel.y), // top-center
+        vec2( texel.x,  texel.y), // top-right
+        vec2(-texel.x,  0.0),     // center-left
+        vec2( 0.0,      0.0),     // center-center
+        vec2( texel.x,  0.0),     // center-right
+        vec2(-texel.x, -texel.y), // bottom-left
+        vec2( 0.0,     -texel.y), // bottom-center
+        vec2( texel.x, -texel.y)  // bottom-right
+    );
+
+    vec3 sum = vec3(0.0);
+    for (int i = 0; i < 9; i++) {
+        sum += texture(screen, texture_coord + offsets[i]).rgb * kernel[i];
+    }
+
+    return vec4(sum, colour.a);
+}
+
+vec4 vignette(vec4 colour) {
+    // 0.0 at the centre of the screen, 1.0 in the corners
+    float dist = distance(texture_coord, vec2(0.5)) * sqrt(2.0);
+    float shade = smoothstep(vignette_radius, vignette_radius - vignette_softness, dist);
+
+    return vec4(colour.rgb * mix(1.0, shade, vignette_strength), colour.a);
+}
+
+vec4 colour_grade(vec4 colour) {
+    // The LUT is `lut_size` slices of `lut_size` x `lut_size` laid left to right.
+    // Red increases to the right within a slice, green increases upwards and blue picks the slice.
+    vec3 cell = clamp(colour.rgb, 0.0, 1.0) * (lut_size - 1.0);
+
+    float slice = floor(cell.b);
+    float next_slice = min(slice + 1.0, lut_size - 1.0);
+    float blend = cell.b - slice;
+
+    float width = lut_size * lut_size;
+    float v = (cell.g + 0.5) / lut_size;
+
+    vec3 lower = texture(lut, vec2((slice * lut_size + cell.r + 0.5) / width, v)).rgb;
+    vec3 upper = texture(lut, vec2((next_slice * lut_size + cell.r + 0.5) / width, v)).rgb;
+
+    return vec4(mix(lower, upper, blend), colour.a);
+}
@@ -0,0 +1,61 @@
+#version 330 core
+
+in vec3 local_position;
+
+out vec4 frag_colour;
+
+uniform samplerCube environment;
+uniform float roughness;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+vec2 hammersley(uint i, uint count);
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness);
+
+// Convolve the environment with the GGX lobe for `roughness`, assuming view = normal
+void main() {
+    vec3 normal = normalize(local_position);
+
+    vec3 colour = vec3(0.0);
+    float total_weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 halfway = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), normal, roughness);
+        vec3 light_dir = normalize(2.0 * dot(normal, halfway) * halfway - normal);
+
+        float n_dot_l = dot(normal, light_dir);
+        if (n_dot_l > 0.0) {
+            colour += texture(environment, light_dir).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    frag_colour = vec4(colour / max(total_weight, 0.0001), 1.0);
+}
+
+// Low discrepancy point `i` of `count`, from the Van der Corput radical inverse
+vec2 hammersley(uint i, uint count) {
+    uint bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness) {
+    float a = roughness * roughness;
+
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 halfway = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+
+    return normalize(tangent * halfway.x + bitangent * halfway.y + normal * halfway.z);
+}
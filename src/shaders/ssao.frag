@@ -0,0 +1,58 @@
+#version 330 core
+
+in vec2 texture_coord;
+
+out vec4 frag_colour;
+
+const int MAX_KERNEL_SIZE = 64;
+
+uniform sampler2D position;
+uniform sampler2D normal;
+uniform sampler2D noise;
+
+uniform vec3 samples[MAX_KERNEL_SIZE];
+uniform int kernel_size;
+uniform float radius;
+uniform float bias;
+uniform float power;
+
+uniform mat4 projection;
+
+void main() {
+    vec4 normal_sample = texture(normal, texture_coord);
+    if (normal_sample.a == 0.0) {
+        // Background, nothing to occlude
+        frag_colour = vec4(1.0);
+        return;
+    }
+
+    vec3 frag_position = texture(position, texture_coord).xyz;
+    vec3 frag_normal = normalize(normal_sample.xyz);
+
+    // Rotate the kernel about the normal by a tiling random vector
+    vec2 noise_scale = vec2(textureSize(position, 0)) / vec2(textureSize(noise, 0));
+    vec3 random = normalize(texture(noise, texture_coord * noise_scale).xyz);
+
+    vec3 tangent = normalize(random - frag_normal * dot(random, frag_normal));
+    vec3 bitangent = cross(frag_normal, tangent);
+    mat3 tbn = mat3(tangent, bitangent, frag_normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < kernel_size; i++) {
+        vec3 sample_position = frag_position + tbn * samples[i] * radius;
+
+        vec4 offset = projection * vec4(sample_position, 1.0);
+        offset.xy = (offset.xy / offset.w) * 0.5 + 0.5;
+
+        if (texture(normal, offset.xy).a == 0.0) {
+            continue;
+        }
+
+        float sample_depth = texture(position, offset.xy).z;
+        float range_check = smoothstep(0.0, 1.0, radius / abs(frag_position.z - sample_depth));
+        occlusion += (sample_depth >= sample_position.z + bias ? 1.0 : 0.0) * range_check;
+    }
+
+    occlusion = 1.0 - occlusion / float(kernel_size);
+    frag_colour = vec4(vec3(pow(occlusion, power)), 1.0);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+in vec2 texture_coord;
+
+out vec4 frag_colour;
+
+uniform sampler2D occlusion;
+uniform int blur_size;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(occlusion, 0));
+
+    float sum = 0.0;
+    for (int x = -blur_size; x <= blur_size; x++) {
+        for (int y = -blur_size; y <= blur_size; y++) {
+            sum += texture(occlusion, texture_coord + vec2(x, y) * texel).r;
+        }
+    }
+
+    float width = float(2 * blur_size + 1);
+    frag_colour = vec4(vec3(sum / (width * width)), 1.0);
+}
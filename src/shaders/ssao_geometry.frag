@@ -0,0 +1,19 @@
+#version 330 core
+
+in vec3 view_position;
+in vec3 view_normal;
+
+layout (location = 0) out vec4 position;
+layout (location = 1) out vec4 normal;
+
+#include "lod_dither.glsl"
+
+void main() {
+    if (lod_discard()) {
+        discard;
+    }
+
+    position = vec4(view_position, 1.0);
+    // alpha marks the fragment as geometry, the cleared background has alpha 0.0
+    normal = vec4(normalize(view_normal), 1.0);
+}
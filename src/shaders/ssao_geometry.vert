@@ -0,0 +1,23 @@
+#version 330 core
+
+layout (location = 0) in vec3 in_position;
+layout (location = 2) in vec3 in_normal;
+layout (location = 11) in mat4 in_instance_model; // as in vertex_shader.vert
+
+out vec3 view_position;
+out vec3 view_normal;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+uniform bool instanced;
+
+void main() {
+    mat4 model_view = instanced ? view * in_instance_model * model : view * model;
+
+    vec4 position = model_view * vec4(in_position, 1.0);
+    view_position = position.xyz;
+    view_normal = mat3(transpose(inverse(model_view))) * in_normal;
+
+    gl_Position = projection * position;
+}
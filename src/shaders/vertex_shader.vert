@@ -3,23 +3,35 @@
 layout (location = 0) in vec3 in_position;
 layout (location = 1) in vec2 in_texture_coord;
 layout (location = 2) in vec3 in_normal;
+layout (location = 3) in vec4 in_tangent; // (0, 0, 0, 1) when the mesh has no tangents
+// Instance attributes take the top of the 16 locations every driver has, clear of vertex layouts
+layout (location = 11) in mat4 in_instance_model; // locations 11 to 14
+layout (location = 15) in vec4 in_instance_tint;
 
 out vec2 texture_coord;
 out vec3 frag_normal;
+out vec4 frag_tangent;
+out vec4 frag_tint;
 out vec3 frag_position;
 out vec4 screen_pos;
 
 uniform mat4 model;
 uniform mat4 projtimesview;
+uniform bool instanced;
 
 void main() {
     texture_coord = in_texture_coord;
+
+    // Instances are placed after the model's own transform
+    mat4 world = instanced ? in_instance_model * model : model;
+    frag_tint = instanced ? in_instance_tint : vec4(1.0);
     
-    mat3 normal_matrix = mat3(transpose(inverse(model)));
+    mat3 normal_matrix = mat3(transpose(inverse(world)));
     frag_normal = normal_matrix * in_normal;
     //frag_normal = in_normal;
+    frag_tangent = vec4(mat3(world) * in_tangent.xyz, in_tangent.w);
 
-    vec4 model_position = (model * vec4(in_position, 1.0));
+    vec4 model_position = (world * vec4(in_position, 1.0));
     frag_position = model_position.xyz;
     screen_pos = projtimesview * model_position;
     gl_Position = screen_pos;
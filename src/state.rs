@@ -1,22 +1,29 @@
-use std::{iter, vec};
-
 use opengl::{
+    assets::{AssetManager, Handle},
     buffers::{
         framebuffer::{BufferColourType, FrameBuffer},
-        vertex_array::VertexArray,
+        instance_buffer::{Instance, InstanceBuffer},
+        BufferUsage,
     },
     camera::Camera,
-    drawing::{draw::Draw, groups::TempListLights},
+    drawing::{
+        bloom::Bloom,
+        draw::Draw,
+        groups::TempListLights,
+        post_process::{PostEffect, PostProcessChain},
+        ssao::Ssao,
+    },
     environment::Environment,
     global_state::GlobalState,
     input::keyboard::{Key::*, Keyboard},
     input::mouse::Mouse,
     lighting::{far_light::FarLight, point_light::PointLight, spot_light::SpotLight},
-    linear_algebra::{orientation::Orientation, vector::Vector},
+    linear_algebra::{matrix::Matrix, orientation::Orientation, vector::Vector},
+    loading::Loader,
     material::Material,
     modelling::model::Model,
     shader_program::ShaderProgram,
-    texture::Texture,
+    texture::{self, sampler::Filter, Texture},
     window::Window,
     Result,
 };
@@ -30,7 +37,8 @@ pub struct State {
     speed: [f32; 3],
 
     light: Model,
-    containers: Vec<Model>,
+    container: Model,
+    container_instances: InstanceBuffer,
     player: Model,
 
     reverse_fbo: FrameBuffer,
@@ -40,10 +48,13 @@ pub struct State {
     far_light: FarLight,
     spotlight: SpotLight,
 
-    box_shader: ShaderProgram,
-    quad_shader: ShaderProgram,
+    assets: AssetManager,
+    loader: Loader,
+    box_shader: Handle<ShaderProgram>,
+    quad_shader: Handle<ShaderProgram>,
 
-    screen_quad: Model,
+    post_process: PostProcessChain,
+    ssao: Ssao,
     rear_view_quad: Model,
 }
 
@@ -57,6 +68,11 @@ impl GlobalState for State {
         default_framebuffer: &'a mut FrameBuffer,
         time: f32,
     ) -> Vec<Draw<'b>> {
+        // Each failed load is handed back once, leaving its placeholder in place
+        for error in self.loader.update() {
+            eprintln!("Failed to load an asset: {error:?}");
+        }
+
         self.controls(mouse, keyboard, frame_time, window);
         self.physics(frame_time, time);
 
@@ -84,7 +100,8 @@ impl GlobalState for State {
         let sensitivity = 0.5;
 
         let forward_fbo = FrameBuffer::builder()
-            .add_colour(BufferColourType::TexRgb)
+            .add_colour(BufferColourType::TexRgba16F)
+            .add_colour(BufferColourType::TexRgba16F)
             .add_depth()
             .add_dims(width, height)
             .build()?;
@@ -95,25 +112,32 @@ impl GlobalState for State {
             .add_dims(width, height)
             .build()?;
 
-        let container_material = {
-            let container_tex = Texture::builder().image("assets/container.png")?.build()?;
-
-            let container_specular = Texture::builder()
-                .image("assets/containerspecular.png")?
-                .build()?;
-
-            let container_emission = Texture::builder().image("assets/matrix.jpg")?.build()?;
+        let mut assets = AssetManager::new();
+        let mut loader = Loader::builder().build()?;
 
-            let container_emission_map = Texture::builder()
-                .image("assets/matrix_mask.png")?
-                .mag_filter(gl::NEAREST)
-                .build()?;
+        let container_material = {
+            let container_tex = assets.load_texture_async_with(
+                &mut loader,
+                "assets/container.png",
+                texture::Builder::srgb,
+            );
+            let container_specular =
+                assets.load_texture_async(&mut loader, "assets/containerspecular.png");
+            let container_emission = assets.load_texture_async_with(
+                &mut loader,
+                "assets/matrix.jpg",
+                texture::Builder::srgb,
+            );
+            let container_emission_map = assets
+                .load_texture_with("assets/matrix_mask.png", |builder| {
+                    builder.mag_filter(Filter::Nearest)
+                })?;
 
             Material::builder()
-                .diffuse(container_tex)
-                .specular_map(container_specular)
-                .emission(container_emission)
-                .emission_map(container_emission_map)
+                .diffuse(assets[container_tex].clone())
+                .specular_map(assets[container_specular].clone())
+                .emission(assets[container_emission].clone())
+                .emission_map(assets[container_emission_map].clone())
                 .build()
         };
 
@@ -134,10 +158,12 @@ impl GlobalState for State {
         };
 
         let player_material = {
-            let awesomeface = Texture::builder()
-                .image("assets/awesomeface.png")?
-                .build()?;
-            Material::builder().diffuse(awesomeface).build()
+            let awesomeface = assets.load_texture_async_with(
+                &mut loader,
+                "assets/awesomeface.png",
+                texture::Builder::srgb,
+            );
+            Material::builder().diffuse(assets[awesomeface].clone()).build()
         };
 
         let model_builder = Model::cube(1.0, Material::blank())?;
@@ -191,29 +217,27 @@ impl GlobalState for State {
             [-2.0, 2.0, 2.0],
         ].map(Vector::new);
 
-        let containers: Vec<Model> = iter::repeat(container_builder)
-            .zip(cube_positions)
-            .map(|(container_builder, position)| {
-                //container.set_model_matrix(Matrix::transform_translate(position));
-                container_builder
-                    .position(position)
-                    .orientation(Orientation::builder()
-                        .looking_at(position, Vector::new([2.0, 2.0, 1.0]))
-                        .build()
-                    )
-                    .build()
+        let instances: Vec<Instance> = cube_positions
+            .into_iter()
+            .map(|position| {
+                let orientation = Orientation::builder()
+                    .looking_at(position, Vector::new([2.0, 2.0, 1.0]))
+                    .build();
+                Instance::new(
+                    Matrix::transform_translate(position) * orientation.as_matrix(None)
+                )
             })
             .collect();
 
-        let box_shader = ShaderProgram::builder()
-            .add_vertex_shader("src/shaders/vertex_shader.vert")
-            .add_fragment_shader("src/shaders/fragment_shader.frag")
-            .build()?;
+        let container = container_builder.build();
+        let container_instances = InstanceBuffer::new(&instances, BufferUsage::Static)?;
 
-        let quad_shader = ShaderProgram::builder()
-            .add_vertex_shader("src/shaders/quad_vert.vert")
-            .add_fragment_shader("src/shaders/quad_frag.frag")
-            .build()?;
+        let box_shader = assets.load_shader(
+            "src/shaders/vertex_shader.vert",
+            "src/shaders/fragment_shader.frag",
+        )?;
+        let quad_shader =
+            assets.load_shader("src/shaders/quad_vert.vert", "src/shaders/quad_frag.frag")?;
 
         let light_attenuation_array = [1.0, 0.09, 0.032];
 
@@ -245,47 +269,40 @@ impl GlobalState for State {
             25f32.to_radians().cos(),
         );
 
-        let quad_texcoord = vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![1.0, 1.0], vec![0.0, 1.0]];
-
-        let screen_corners = vec![
-            vec![-1.0, -1.0, 0.0],
-            vec![1.0, -1.0, 0.0],
-            vec![1.0, 1.0, 0.0],
-            vec![-1.0, 1.0, 0.0],
-        ];
-
-        let screen_quad = Model::builder()
-            .mesh_from(
-                VertexArray::builder()
-                    .attribute("coords".into(), screen_corners)?
-                    .attribute("texcoord".into(), quad_texcoord)?
-                    .element_buffer(vec![0, 1, 2, 0, 2, 3])
-                    .build()?,
-                forward_fbo.as_material()?,
-            )
-            .build();
+        let ssao = Ssao::builder()
+            .add_dims(width, height)
+            .radius(0.5)
+            .build()?;
+
+        let bloom = Bloom::builder()
+            .add_dims(width, height)
+            .emission(forward_fbo.get_colour_attachment(1)?)
+            .threshold(1.0)
+            .intensity(0.8)
+            .build()?;
+
+        let post_process = PostProcessChain::builder()
+            .add_dims(width, height)
+            .bloom(bloom)
+            .effect(PostEffect::Vignette {
+                radius: 1.0,
+                softness: 0.6,
+                strength: 0.8,
+            })
+            .build()?;
 
         let rear_view_width = 0.5;
         let rear_view_height = 0.25;
 
-        let rear_view_corners = vec![
-            vec![-rear_view_width, 1.0 - 2.0 * rear_view_height, -1.0],
-            vec![rear_view_width, 1.0 - 2.0 * rear_view_height, -1.0],
-            vec![rear_view_width, 1.0, -1.0],
-            vec![-rear_view_width, 1.0, -1.0],
-        ];
-        let rear_view_texcoord = vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![1.0, 1.0], vec![0.0, 1.0]];
-        
-        let rear_view_quad = Model::builder()
-        .mesh_from(
-            VertexArray::builder()
-                .attribute("coords".into(), rear_view_corners)?
-                .attribute("texcoord".into(), rear_view_texcoord)?
-                .element_buffer(vec![0, 1, 2, 0, 2, 3])
-                .build()?,
-            reverse_fbo.as_material()?,
-        )
-        .build();
+        let rear_view_quad = Model::quad()
+            .rect(
+                [-rear_view_width, 1.0 - 2.0 * rear_view_height],
+                [rear_view_width, 1.0],
+                -1.0,
+            )
+            .material(reverse_fbo.as_material()?)
+            .build()?
+            .build();
         
         let mut rear_camera = camera.clone();
         rear_camera.reverse_direction();
@@ -298,16 +315,20 @@ impl GlobalState for State {
             sensitivity,
             speed,
             light,
-            containers,
+            container,
+            container_instances,
             player,
             reverse_fbo,
             forward_fbo,
             point_light,
             far_light,
             spotlight,
+            assets,
+            loader,
             box_shader,
             quad_shader,
-            screen_quad,
+            post_process,
+            ssao,
             rear_view_quad,
         })
     }
@@ -433,10 +454,13 @@ impl State {
         // Forward FBO,
         // Rear FBO,
         // default main FBO,
+        let scene = self.forward_fbo.get_colour();
+        let box_shader = &self.assets[self.box_shader];
+
         let mut out = Vec::new();
-        for (fbo, camera, shader) in [
-            (&mut self.forward_fbo, &self.camera, &self.box_shader),
-            (&mut self.reverse_fbo, &self.rear_camera, &self.box_shader),
+        for (fbo, camera, shader, ssao) in [
+            (&mut self.forward_fbo, &self.camera, box_shader, Some(&mut self.ssao)),
+            (&mut self.reverse_fbo, &self.rear_camera, box_shader, None),
         ] {
             let mut draw = Draw::new(
                 fbo,
@@ -444,10 +468,12 @@ impl State {
                 TempListLights::new(&self.point_light, &self.far_light, &self.spotlight),
             );
 
-            for model in &self.containers {
-                draw.add_model(model, shader);
+            if let Some(ssao) = ssao {
+                draw.ambient_occlusion(ssao);
             }
 
+            draw.add_instanced(&self.container, &self.container_instances, shader);
+
             draw.add_model(&self.player, shader);
             draw.add_model(&self.light, shader);
 
@@ -456,8 +482,10 @@ impl State {
 
         {
             let mut draw = Draw::new_quad(default_fb);
-            draw.add_model(&self.screen_quad, &self.quad_shader);
-            draw.add_model(&self.rear_view_quad, &self.quad_shader);
+            if let Ok(scene) = scene {
+                draw.post_process(&mut self.post_process, scene);
+            }
+            draw.add_model(&self.rear_view_quad, &self.assets[self.quad_shader]);
             out.push(draw);
         }
 
@@ -0,0 +1,16 @@
+//! Helpers shared by the unit tests
+
+/// Largest difference between two floats still taken as equal
+pub const TOLERANCE: f32 = 1.0e-5;
+
+/// Asserts `actual` has as many components as `expected`, each within [`TOLERANCE`] of it
+#[track_caller]
+pub fn assert_near(actual: impl AsRef<[f32]>, expected: impl AsRef<[f32]>) {
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    let near = actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| (actual - expected).abs() < TOLERANCE);
+    assert!(near, "{actual:?} is not {expected:?}");
+}
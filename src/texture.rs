@@ -1,27 +1,155 @@
+pub mod compressed;
+pub mod procedural;
+pub mod sampler;
+
+use std::cell::RefCell;
+use std::ffi::c_void;
 use std::ptr;
 use std::rc::Rc;
 
 use crate::buffers::framebuffer::FrameBuffer;
+use crate::capabilities::GlCapabilities;
+use crate::texture::compressed::CompressedImage;
+use crate::texture::sampler::{Filter, MipmapMode, Sampler, SamplerSettings, Wrap};
 use crate::EngineError::TextureErr;
-use crate::{error_fmt, some_builder, Result};
+use crate::{error_fmt, gl_state, Result};
+
+/// The kind of texture, which decides how shaders sample it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureTarget {
+    /// `sampler2D`
+    Texture2D,
+    /// `sampler2DArray`, layers of equally sized images
+    Texture2DArray,
+    /// `sampler3D`, a volume
+    Texture3D,
+    /// `samplerCube`, six square faces
+    CubeMap,
+}
+
+impl TextureTarget {
+    #[must_use]
+    #[inline]
+    pub const fn gl_target(self) -> u32 {
+        match self {
+            Self::Texture2D => gl::TEXTURE_2D,
+            Self::Texture2DArray => gl::TEXTURE_2D_ARRAY,
+            Self::Texture3D => gl::TEXTURE_3D,
+            Self::CubeMap => gl::TEXTURE_CUBE_MAP,
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Internal {
     id: u32,
+    target: TextureTarget,
+    /// Shared sampler object used instead of the texture's own settings
+    sampler: RefCell<Option<Sampler>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Texture(Rc<Internal>);
 
+/// Raw texels of a volume, in x then y then z order
+#[derive(Clone, Debug)]
+pub enum VolumeData {
+    R8(Vec<u8>),
+    Rgba8(Vec<u8>),
+    R32F(Vec<f32>),
+    Rgba32F(Vec<f32>),
+}
+
+impl VolumeData {
+    const fn components(&self) -> usize {
+        match self {
+            Self::R8(_) | Self::R32F(_) => 1,
+            Self::Rgba8(_) | Self::Rgba32F(_) => 4,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::R8(data) | Self::Rgba8(data) => data.len(),
+            Self::R32F(data) | Self::Rgba32F(data) => data.len(),
+        }
+    }
+
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            Self::R8(data) | Self::Rgba8(data) => data.as_ptr().cast(),
+            Self::R32F(data) | Self::Rgba32F(data) => data.as_ptr().cast(),
+        }
+    }
+
+    /// Internal format, format and type
+    const fn formats(&self) -> (u32, u32, u32) {
+        match self {
+            Self::R8(_) => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            Self::Rgba8(_) => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            Self::R32F(_) => (gl::R32F, gl::RED, gl::FLOAT),
+            Self::Rgba32F(_) => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Volume {
+    width: i32,
+    height: i32,
+    depth: i32,
+    data: VolumeData,
+}
+
+/// An image converted for upload
+enum Pixels {
+    Normalised(Vec<u8>),
+    NotNormalised(Vec<f32>),
+}
+
+impl Pixels {
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            Self::Normalised(data) => data.as_ptr().cast(),
+            Self::NotNormalised(data) => data.as_ptr().cast(),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Builder {
     image_data: Option<image::DynamicImage>,
     dims: Option<(i32, i32)>,
+    layers: Vec<image::DynamicImage>,
+    faces: Option<Box<[image::DynamicImage; 6]>>,
+    volume: Option<Volume>,
+    compressed: Option<CompressedImage>,
     not_normalised: bool,
-    wrap_s: Option<u32>,
-    wrap_t: Option<u32>,
-    mag_filter: Option<u32>,
-    min_filter: Option<u32>,
+    srgb: bool,
+    sampler: SamplerSettings,
+}
+
+fn open_image(filepath: &str) -> Result<image::DynamicImage> {
+    image::io::Reader::open(filepath)
+        .map_err(|_| {
+            TextureErr(error_fmt!(
+                texture::Builder,
+                "Opening texture at {filepath}"
+            ))
+        })?
+        .decode()
+        .map_err(|_| {
+            TextureErr(error_fmt!(
+                texture::Builder,
+                "Error parsing open texture {filepath}"
+            ))
+        })
+}
+
+fn to_i32(value: u32, what: &str) -> Result<i32> {
+    value
+        .try_into()
+        .map_err(|_| TextureErr(error_fmt!(texture::Builder, "Texture {what} exceeds i32")))
 }
 
 impl Builder {
@@ -34,35 +162,135 @@ impl Builder {
     ///
     #[inline]
     pub fn image(mut self, filepath: &str) -> Result<Self> {
-        self.image_data = Some(
-            image::io::Reader::open(filepath)
-                .map_err(|_| {
-                    TextureErr(error_fmt!(
-                        texture::Builder,
-                        "Opening texture at {filepath}"
-                    ))
-                })?
-                .decode()
-                .map_err(|_| {
-                    TextureErr(error_fmt!(
-                        texture::Builder,
-                        "Error parsing open texture {filepath}"
-                    ))
-                })?,
-        );
+        self.image_data = Some(open_image(filepath)?);
         Ok(self)
     }
 
+    /// Use already decoded image data, e.g. generated at runtime
+    #[must_use]
+    #[inline]
+    pub fn image_data(mut self, image: image::DynamicImage) -> Self {
+        self.image_data = Some(image);
+        self
+    }
+
+    /// Build a 2D array texture with a layer from each image, which must all be the same size
+    /// # Errors
+    /// Returns an error if an image cannot be opened or decoded
+    #[inline]
+    pub fn layers(mut self, filepaths: &[&str]) -> Result<Self> {
+        self.layers = filepaths
+            .iter()
+            .map(|filepath| open_image(filepath))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    /// Build a 2D array texture from already decoded images, which must all be the same size
+    #[must_use]
+    #[inline]
+    pub fn layer_data(mut self, images: Vec<image::DynamicImage>) -> Self {
+        self.layers = images;
+        self
+    }
+
+    /// Build a cube map from six square images of the same size, in the order +x, -x, +y, -y,
+    /// +z, -z. Unlike 2D textures, faces are not flipped, as cube maps start from the top left.
+    /// # Errors
+    /// Returns an error if an image cannot be opened or decoded
+    #[inline]
+    pub fn cube_faces(mut self, filepaths: [&str; 6]) -> Result<Self> {
+        let [a, b, c, d, e, f] = filepaths.map(open_image);
+        self.faces = Some(Box::new([a?, b?, c?, d?, e?, f?]));
+        Ok(self)
+    }
+
+    /// Build a cube map from already decoded faces, as for `cube_faces`
+    #[must_use]
+    #[inline]
+    pub fn cube_face_data(mut self, faces: [image::DynamicImage; 6]) -> Self {
+        self.faces = Some(Box::new(faces));
+        self
+    }
+
+    /// Build a 2D texture from a DDS or KTX2 file of BC1 to BC7 or ETC2 blocks, keeping every mip
+    /// level it holds. These are not flipped, see `compressed`.
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed
+    #[inline]
+    pub fn compressed(mut self, filepath: &str) -> Result<Self> {
+        self.compressed = Some(CompressedImage::open(filepath)?);
+        Ok(self)
+    }
+
+    /// Build a 2D texture from already parsed compressed data. Formats the driver cannot sample
+    /// are decompressed in software, to 16 bit floats for BC6H.
+    #[must_use]
+    #[inline]
+    pub fn compressed_data(mut self, image: CompressedImage) -> Self {
+        self.compressed = Some(image);
+        self
+    }
+
+    /// Build a 3D texture from raw texels, `not_normalised` has no effect
+    #[must_use]
+    #[inline]
+    pub fn volume(mut self, width: i32, height: i32, depth: i32, data: VolumeData) -> Self {
+        self.volume = Some(Volume {
+            width,
+            height,
+            depth,
+            data,
+        });
+        self
+    }
+
+    /// Unset wraps repeat, except on cube maps which clamp to their edges
     #[must_use]
     #[inline]
-    pub const fn set_wrap_s_t(mut self, wrap_s: u32, wrap_t: u32) -> Self {
-        self.wrap_s = Some(wrap_s);
-        self.wrap_t = Some(wrap_t);
+    pub const fn set_wrap_s_t(mut self, wrap_s: Wrap, wrap_t: Wrap) -> Self {
+        self.sampler = self.sampler.wrap_s_t(wrap_s, wrap_t);
         self
     }
 
-    some_builder!(min_filter: u32);
-    some_builder!(mag_filter: u32);
+    /// Wrapping along the third axis of 3D textures and cube maps
+    #[must_use]
+    #[inline]
+    pub const fn set_wrap_r(mut self, wrap_r: Wrap) -> Self {
+        self.sampler = self.sampler.wrap_r(wrap_r);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn min_filter(mut self, filter: Filter) -> Self {
+        self.sampler = self.sampler.min_filter(filter);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn mag_filter(mut self, filter: Filter) -> Self {
+        self.sampler = self.sampler.mag_filter(filter);
+        self
+    }
+
+    /// Anything but `MipmapMode::None` generates the mip chain, except for compressed images
+    /// which bring their own and use `MipmapMode::Linear` by default when they do
+    #[must_use]
+    #[inline]
+    pub const fn mipmap(mut self, mode: MipmapMode) -> Self {
+        self.sampler = self.sampler.mipmap(mode);
+        self
+    }
+
+    /// Replace every sampling setting at once
+    #[must_use]
+    #[inline]
+    pub const fn sampler(mut self, settings: SamplerSettings) -> Self {
+        self.sampler = settings;
+        self
+    }
 
     #[must_use]
     #[inline]
@@ -71,6 +299,16 @@ impl Builder {
         self
     }
 
+    /// Store colours in sRGB, as most images are authored, so that shaders sample them in linear
+    /// space. Without this the data is taken as linear already, which suits normal, specular and
+    /// other non-colour maps. Has no effect with `not_normalised` or on volumes.
+    #[must_use]
+    #[inline]
+    pub const fn srgb(mut self) -> Self {
+        self.srgb = true;
+        self
+    }
+
     #[must_use]
     #[inline]
     pub fn monochrome(mut self, colour: [f32; 4]) -> Self {
@@ -80,115 +318,397 @@ impl Builder {
         self
     }
 
+    /// The kind of texture `build` makes, from whichever contents were given
+    #[must_use]
+    #[inline]
+    pub fn target(&self) -> TextureTarget {
+        if self.volume.is_some() {
+            TextureTarget::Texture3D
+        } else if self.faces.is_some() {
+            TextureTarget::CubeMap
+        } else if !self.layers.is_empty() {
+            TextureTarget::Texture2DArray
+        } else {
+            TextureTarget::Texture2D
+        }
+    }
+
     /// # Errors
     ///
     #[inline]
     pub fn build(self) -> Result<Texture> {
-        let mut output = Internal { id: 0 };
+        let mut output = Internal {
+            id: 0,
+            target: self.target(),
+            sampler: RefCell::default(),
+        };
         unsafe {
             gl::GenTextures(1, ptr::addr_of_mut!(output.id));
-            gl::BindTexture(gl::TEXTURE_2D, output.id);
+        }
+        // Dropping `output` deletes the texture again on failure
+        self.upload(output.id)?;
 
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_S,
-                #[expect(clippy::cast_possible_wrap)]
-                (self.wrap_s.unwrap_or(gl::REPEAT) as i32),
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_T,
-                #[expect(clippy::cast_possible_wrap)]
-                (self.wrap_t.unwrap_or(gl::REPEAT) as i32),
-            );
-            gl::TexParameteri(
+        Ok(Texture(Rc::new(output)))
+    }
+
+    /// Replace the contents and parameters of `texture`, which keeps its id. Used to swap a
+    /// placeholder for the real image once it has loaded.
+    /// # Errors
+    /// Returns an error if there is no image data and no dimensions, or the targets differ
+    pub(crate) fn build_into(self, texture: &Texture) -> Result<()> {
+        if texture.0.target != self.target() {
+            return Err(TextureErr(error_fmt!(
+                texture::Builder,
+                "Cannot replace a {:?} texture with a {:?} one",
+                texture.0.target,
+                self.target()
+            )));
+        }
+        self.upload(texture.0.id)
+    }
+
+    /// Bytes that `build` will upload, roughly
+    pub(crate) fn upload_size(&self) -> usize {
+        let texel = if self.not_normalised { 16 } else { 4 };
+        let images = |images: &[image::DynamicImage]| {
+            images
+                .iter()
+                .map(|image| image.width() as usize * image.height() as usize * texel)
+                .sum()
+        };
+
+        if let Some(compressed) = &self.compressed {
+            return compressed.levels().iter().map(Vec::len).sum();
+        }
+
+        match (&self.volume, &self.faces) {
+            (Some(volume), _) => {
+                let scalar = match volume.data {
+                    VolumeData::R8(_) | VolumeData::Rgba8(_) => 1,
+                    VolumeData::R32F(_) | VolumeData::Rgba32F(_) => 4,
+                };
+                volume.data.len() * scalar
+            }
+            (None, Some(faces)) => images(faces.as_slice()),
+            (None, None) if !self.layers.is_empty() => images(&self.layers),
+            (None, None) => self.image_data.as_ref().map_or_else(
+                || {
+                    self.dims.map_or(0, |(width, height)| {
+                        width.unsigned_abs() as usize * height.unsigned_abs() as usize * 16
+                    })
+                },
+                |image| images(std::slice::from_ref(image)),
+            ),
+        }
+    }
+
+    /// Internal format and data type of every uncompressed image this builder uploads
+    const fn formats(&self) -> (u32, u32) {
+        if self.not_normalised {
+            (gl::RGBA16F, gl::FLOAT)
+        } else if self.srgb {
+            (gl::SRGB8_ALPHA8, gl::UNSIGNED_BYTE)
+        } else {
+            (gl::RGBA8, gl::UNSIGNED_BYTE)
+        }
+    }
+
+    /// Internal format and texel data of `image`, flipped so that the first row is the bottom
+    fn pixels(&self, image: &image::DynamicImage, flip: bool) -> (u32, u32, Pixels) {
+        let image = if flip { image.flipv() } else { image.clone() };
+        let (internal_format, data_type) = self.formats();
+        let pixels = if self.not_normalised {
+            Pixels::NotNormalised(image.into_rgba32f().into_raw())
+        } else {
+            Pixels::Normalised(image.into_rgba8().into_raw())
+        };
+        (internal_format, data_type, pixels)
+    }
+
+    /// Width and height shared by every image
+    fn common_size(images: &[image::DynamicImage]) -> Result<(i32, i32)> {
+        let first = images.first().ok_or_else(|| {
+            TextureErr(error_fmt!(texture::Builder, "No images given"))
+        })?;
+        if images
+            .iter()
+            .any(|image| (image.width(), image.height()) != (first.width(), first.height()))
+        {
+            return Err(TextureErr(error_fmt!(
+                texture::Builder,
+                "Every layer or face must be the same size"
+            )));
+        }
+        Ok((to_i32(first.width(), "width")?, to_i32(first.height(), "height")?))
+    }
+
+    fn upload(self, id: u32) -> Result<()> {
+        let target = self.target();
+        let gl_target = target.gl_target();
+        let default_wrap = match target {
+            TextureTarget::CubeMap => Wrap::ClampToEdge,
+            _ => Wrap::Repeat,
+        };
+        // Compressed images bring their own mip levels, which are never generated
+        let levels = self
+            .compressed
+            .as_ref()
+            .map_or(1, |compressed| compressed.levels().len());
+        let default_mipmap = if levels > 1 {
+            MipmapMode::Linear
+        } else {
+            MipmapMode::None
+        };
+
+        gl_state::bind_texture(gl_target, id);
+        self.sampler.apply(
+            default_wrap,
+            default_mipmap,
+            |parameter, value| unsafe { gl::TexParameteri(gl_target, parameter, value) },
+            |parameter, values| unsafe {
+                gl::TexParameterfv(gl_target, parameter, values.as_ptr());
+            },
+        );
+
+        unsafe {
+            if self.compressed.is_some() {
+                gl::TexParameteri(
+                    gl_target,
+                    gl::TEXTURE_MAX_LEVEL,
+                    to_i32(levels.try_into().unwrap_or(u32::MAX), "mip level count")? - 1,
+                );
+            }
+        }
+
+        let result = unsafe {
+            match target {
+                TextureTarget::Texture2D => self.upload_2d(),
+                TextureTarget::Texture2DArray => self.upload_layers(),
+                TextureTarget::Texture3D => self.upload_volume(),
+                TextureTarget::CubeMap => self.upload_faces(),
+            }
+        };
+
+        unsafe {
+            if result.is_ok()
+                && self.compressed.is_none()
+                && self.sampler.mipmap_or(default_mipmap) != MipmapMode::None
+            {
+                gl::GenerateMipmap(gl_target);
+            }
+
+            gl_state::bind_texture(gl_target, 0);
+        }
+
+        result
+    }
+
+    unsafe fn upload_2d(&self) -> Result<()> {
+        if let Some(compressed) = &self.compressed {
+            Self::upload_compressed(compressed, self.srgb || compressed.srgb())?;
+        } else if let Some(image_data) = &self.image_data {
+            let (width, height) = Self::common_size(std::slice::from_ref(image_data))?;
+            let (internal_format, data_type, pixels) = self.pixels(image_data, true);
+            gl::TexImage2D(
                 gl::TEXTURE_2D,
-                gl::TEXTURE_MIN_FILTER,
+                0,
                 #[expect(clippy::cast_possible_wrap)]
-                (self.min_filter.unwrap_or(gl::LINEAR) as i32),
+                (internal_format as i32),
+                width,
+                height,
+                0,
+                gl::RGBA,
+                data_type,
+                pixels.as_ptr(),
             );
-            gl::TexParameteri(
+        } else if let Some((width, height)) = self.dims {
+            gl::TexImage2D(
                 gl::TEXTURE_2D,
-                gl::TEXTURE_MAG_FILTER,
+                0,
                 #[expect(clippy::cast_possible_wrap)]
-                (self.mag_filter.unwrap_or(gl::LINEAR) as i32),
+                (gl::RGBA32F as i32),
+                width,
+                height,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                ptr::null(),
             );
+        } else {
+            return Err(TextureErr(error_fmt!(
+                texture::Builder,
+                "No image data or image dimensions given"
+            )));
+        }
+        Ok(())
+    }
 
-            if let Some(image_data) = self.image_data {
-                if self.not_normalised {
-                    let image = image_data.flipv().into_rgba32f().into_flat_samples();
-
-                    gl::TexImage2D(
-                        gl::TEXTURE_2D,
-                        0,
-                        #[expect(clippy::cast_possible_wrap)]
-                        (gl::RGBA16F as i32),
-                        image.layout.width.try_into().map_err(|_| {
-                            TextureErr(error_fmt!(texture::Builder, "Texture width exceeds i32"))
-                        })?,
-                        image.layout.height.try_into().map_err(|_| {
-                            TextureErr(error_fmt!(texture::Builder, "Texture height exceeds i32"))
-                        })?,
-                        0,
-                        gl::RGBA,
-                        gl::FLOAT,
-                        image.samples.as_ptr().cast(),
-                    );
-                } else {
-                    // if NOT self.not_normalised
-                    let image = image_data.flipv().into_rgba8().into_flat_samples();
-
-                    gl::TexImage2D(
-                        gl::TEXTURE_2D,
-                        0,
-                        #[expect(clippy::cast_possible_wrap)]
-                        (gl::RGBA as i32),
-                        image.layout.width.try_into().map_err(|_| {
-                            TextureErr(error_fmt!(texture::Builder, "Texture width exceeds i32"))
-                        })?,
-                        image.layout.height.try_into().map_err(|_| {
-                            TextureErr(error_fmt!(texture::Builder, "Texture height exceeds i32"))
-                        })?,
-                        0,
-                        gl::RGBA,
-                        gl::UNSIGNED_BYTE,
-                        image.samples.as_ptr().cast(),
-                    );
-                }
-            } else if let Some((width, height)) = self.dims {
-                gl::TexImage2D(
+    /// Upload every level of `compressed` as it is, or decompressed if the driver cannot
+    /// sample its format
+    unsafe fn upload_compressed(compressed: &CompressedImage, srgb: bool) -> Result<()> {
+        let format = compressed.format();
+        let supported = format.is_supported(srgb);
+
+        for (level, data) in (0_i32..).zip(compressed.levels()) {
+            let (width, height) = compressed.level_dimensions(level.unsigned_abs() as usize);
+            let (width, height) = (to_i32(width, "width")?, to_i32(height, "height")?);
+            if supported {
+                gl::CompressedTexImage2D(
                     gl::TEXTURE_2D,
+                    level,
+                    format.gl_format(srgb),
+                    width,
+                    height,
                     0,
+                    to_i32(data.len().try_into().unwrap_or(u32::MAX), "level size")?,
+                    data.as_ptr().cast(),
+                );
+            } else if format.is_hdr() {
+                let pixels = compressed.decode_hdr(level.unsigned_abs() as usize)?;
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    level,
                     #[expect(clippy::cast_possible_wrap)]
-                    (gl::RGBA32F as i32),
+                    (gl::RGBA16F as i32),
                     width,
                     height,
                     0,
                     gl::RGBA,
                     gl::FLOAT,
-                    ptr::null(),
+                    pixels.as_ptr().cast(),
                 );
             } else {
-                return Err(TextureErr(error_fmt!(
-                    texture::Builder,
-                    "No image data or image dimensions given"
-                )));
+                let pixels = compressed.decode(level.unsigned_abs() as usize)?;
+                let internal_format = if srgb {
+                    gl::SRGB8_ALPHA8
+                } else {
+                    gl::RGBA8
+                };
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    level,
+                    #[expect(clippy::cast_possible_wrap)]
+                    (internal_format as i32),
+                    width,
+                    height,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_ptr().cast(),
+                );
             }
+        }
+        Ok(())
+    }
 
-            if let Some(
-                gl::LINEAR_MIPMAP_LINEAR
-                | gl::LINEAR_MIPMAP_NEAREST
-                | gl::NEAREST_MIPMAP_LINEAR
-                | gl::NEAREST_MIPMAP_NEAREST,
-            ) = self.min_filter
-            {
-                gl::GenerateMipmap(gl::TEXTURE_2D);
-            }
+    unsafe fn upload_layers(&self) -> Result<()> {
+        let (width, height) = Self::common_size(&self.layers)?;
+        let layers = to_i32(
+            self.layers.len().try_into().unwrap_or(u32::MAX),
+            "layer count",
+        )?;
+        let (internal_format, data_type) = self.formats();
+
+        gl::TexImage3D(
+            gl::TEXTURE_2D_ARRAY,
+            0,
+            #[expect(clippy::cast_possible_wrap)]
+            (internal_format as i32),
+            width,
+            height,
+            layers,
+            0,
+            gl::RGBA,
+            data_type,
+            ptr::null(),
+        );
+        for (layer, image) in (0..).zip(&self.layers) {
+            let (_, data_type, pixels) = self.pixels(image, true);
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                0,
+                layer,
+                width,
+                height,
+                1,
+                gl::RGBA,
+                data_type,
+                pixels.as_ptr(),
+            );
+        }
+        Ok(())
+    }
 
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+    unsafe fn upload_volume(&self) -> Result<()> {
+        let Some(volume) = &self.volume else {
+            return Ok(());
+        };
+        let texels = [volume.width, volume.height, volume.depth]
+            .iter()
+            .map(|&size| size.unsigned_abs() as usize)
+            .product::<usize>();
+        if texels * volume.data.components() != volume.data.len() {
+            return Err(TextureErr(error_fmt!(
+                texture::Builder,
+                "Volume of {}x{}x{} needs {} values, found {}",
+                volume.width,
+                volume.height,
+                volume.depth,
+                texels * volume.data.components(),
+                volume.data.len()
+            )));
         }
 
-        Ok(Texture(Rc::new(output)))
+        let (internal_format, format, data_type) = volume.data.formats();
+        // Rows of single byte texels are not always a multiple of 4 bytes long
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage3D(
+            gl::TEXTURE_3D,
+            0,
+            #[expect(clippy::cast_possible_wrap)]
+            (internal_format as i32),
+            volume.width,
+            volume.height,
+            volume.depth,
+            0,
+            format,
+            data_type,
+            volume.data.as_ptr(),
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        Ok(())
+    }
+
+    unsafe fn upload_faces(&self) -> Result<()> {
+        let Some(faces) = &self.faces else {
+            return Ok(());
+        };
+        let (width, height) = Self::common_size(faces.as_slice())?;
+        if width != height {
+            return Err(TextureErr(error_fmt!(
+                texture::Builder,
+                "Cube map faces must be square, found {width}x{height}"
+            )));
+        }
+
+        for (face, image) in (0..).zip(faces.iter()) {
+            let (internal_format, data_type, pixels) = self.pixels(image, false);
+            gl::TexImage2D(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                0,
+                #[expect(clippy::cast_possible_wrap)]
+                (internal_format as i32),
+                width,
+                height,
+                0,
+                gl::RGBA,
+                data_type,
+                pixels.as_ptr(),
+            );
+        }
+        Ok(())
     }
 }
 
@@ -238,10 +758,16 @@ impl Texture {
             let mut id = 0;
 
             gl::GenTextures(1, &mut id);
-            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl_state::bind_texture(gl::TEXTURE_2D, id);
 
+            // Attachments are resampled by post processing (e.g. the bloom mip chain), so filter
+            // linearly and never wrap around the screen edge
             #[allow(clippy::cast_possible_wrap)]
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            }
 
             gl::TexImage2D(
                 gl::TEXTURE_2D,
@@ -255,12 +781,85 @@ impl Texture {
                 ptr::null(),
             );
 
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl_state::bind_texture(gl::TEXTURE_2D, 0);
+
+            id
+        };
+
+        Self(Rc::new(Internal {
+            id,
+            target: TextureTarget::Texture2D,
+            sampler: RefCell::default(),
+        }))
+    }
+
+    /// An empty square cube map for rendering into, one face at a time. With `mipmapped` the
+    /// full mip chain is allocated and sampled trilinearly.
+    pub(crate) fn cube_map_attachment(
+        internalformat: i32,
+        size: i32,
+        format: u32,
+        data_type: u32,
+        mipmapped: bool,
+    ) -> Self {
+        let id = unsafe {
+            let mut id = 0;
+
+            gl::GenTextures(1, &mut id);
+            gl_state::bind_texture(gl::TEXTURE_CUBE_MAP, id);
+
+            for face in 0..6 {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    internalformat,
+                    size,
+                    size,
+                    0,
+                    format,
+                    data_type,
+                    ptr::null(),
+                );
+            }
+
+            #[allow(clippy::cast_possible_wrap)]
+            {
+                let min_filter = if mipmapped {
+                    gl::LINEAR_MIPMAP_LINEAR
+                } else {
+                    gl::LINEAR
+                };
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            }
+
+            if mipmapped {
+                gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            }
+
+            gl_state::bind_texture(gl::TEXTURE_CUBE_MAP, 0);
 
             id
         };
 
-        Self(Rc::new(Internal { id }))
+        Self(Rc::new(Internal {
+            id,
+            target: TextureTarget::CubeMap,
+            sampler: RefCell::default(),
+        }))
+    }
+
+    /// Regenerate the mip chain after rendering into the base level
+    pub(crate) fn generate_mipmaps(&self) {
+        let target = self.0.target.gl_target();
+        gl_state::bind_texture(target, self.0.id);
+        unsafe {
+            gl::GenerateMipmap(target);
+        }
+        gl_state::bind_texture(target, 0);
     }
 
     #[must_use]
@@ -269,33 +868,148 @@ impl Texture {
         self.0.id
     }
 
-    /// # Errors
+    #[must_use]
     #[inline]
-    pub fn bind_to(&self, index: u32) -> Result<()> {
-        if index < 16 { // TODO: Programmatic replacement to 16 here
+    pub fn target(&self) -> TextureTarget {
+        self.0.target
+    }
+
+    /// Estimated bytes of GPU memory used by every mip level, layer and face
+    #[must_use]
+    #[inline]
+    pub fn memory(&self) -> usize {
+        let target = self.0.target.gl_target();
+        let (face, faces) = if self.0.target == TextureTarget::CubeMap {
+            (gl::TEXTURE_CUBE_MAP_POSITIVE_X, 6)
+        } else {
+            (target, 1)
+        };
+
+        gl_state::bind_texture(target, self.0.id);
+        let mut total = 0;
+        for level in 0.. {
+            let (mut width, mut height, mut depth, mut format) = (0, 0, 0, 0);
+            let (mut compressed, mut compressed_size) = (0, 0);
             unsafe {
-                gl::ActiveTexture(gl::TEXTURE0 + index);
-                gl::BindTexture(gl::TEXTURE_2D, self.0.id);
+                gl::GetTexLevelParameteriv(face, level, gl::TEXTURE_COMPRESSED, &mut compressed);
+                gl::GetTexLevelParameteriv(face, level, gl::TEXTURE_WIDTH, &mut width);
+                gl::GetTexLevelParameteriv(face, level, gl::TEXTURE_HEIGHT, &mut height);
+                // 1 for anything but arrays and volumes
+                gl::GetTexLevelParameteriv(face, level, gl::TEXTURE_DEPTH, &mut depth);
+                gl::GetTexLevelParameteriv(face, level, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+            }
+            if width == 0 || height == 0 {
+                break;
+            }
+            if compressed == i32::from(gl::TRUE) {
+                unsafe {
+                    gl::GetTexLevelParameteriv(
+                        face,
+                        level,
+                        gl::TEXTURE_COMPRESSED_IMAGE_SIZE,
+                        &mut compressed_size,
+                    );
+                }
+                total += compressed_size.unsigned_abs() as usize;
+                continue;
             }
+            total += [width, height, depth.max(1)]
+                .map(|size| size.unsigned_abs() as usize)
+                .iter()
+                .product::<usize>()
+                * texel_size(format.unsigned_abs());
+        }
+        gl_state::bind_texture(target, 0);
+
+        total * faces
+    }
+
+    /// Sample with a sampler object, which may be shared with other textures, in place of this
+    /// texture's own settings. `None` goes back to the texture's own settings.
+    #[inline]
+    pub fn set_sampler(&self, sampler: Option<&Sampler>) {
+        self.0.sampler.replace(sampler.cloned());
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn sampler(&self) -> Option<Sampler> {
+        self.0.sampler.borrow().clone()
+    }
+
+    /// # Errors
+    /// Returns an error if `index` is not below `GlCapabilities::max_texture_units`
+    #[inline]
+    pub fn bind_to(&self, index: u32) -> Result<()> {
+        let units = GlCapabilities::texture_units();
+        if index < units {
+            gl_state::bind_texture_to_unit(index, self.0.target.gl_target(), self.0.id);
+            let sampler = self.0.sampler.borrow().as_ref().map_or(0, Sampler::id);
+            gl_state::bind_sampler(index, sampler);
             Ok(())
         } else {
             Err(TextureErr(error_fmt!(
-                texture::Builder,
-                "Cannot bind to a texture index greater than {}", 16 - 1
+                texture::Texture,
+                "Cannot bind to texture unit {index}, the driver has {units}"
             )))
         }
     }
 }
 
+/// Bytes per texel of a sized internal format, drivers may pad some formats further
+const fn texel_size(internal_format: u32) -> usize {
+    match internal_format {
+        gl::R8 => 1,
+        gl::RG8 | gl::R16F => 2,
+        gl::RGBA16F | gl::RGB16F | gl::RG32F => 8,
+        gl::RGB32F => 12,
+        gl::RGBA32F => 16,
+        // RGBA8, RGB8 padded to four bytes, RG16F, R32F and the usual depth formats
+        _ => 4,
+    }
+}
+
 impl Drop for Internal {
     fn drop(&mut self) {
         unsafe { gl::DeleteTextures(1, &self.id) }
+        gl_state::forget_texture(self.id);
+    }
+}
+
+/// Textures are equal when they are the same OpenGL texture
+impl PartialEq for Texture {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
     }
 }
 
+impl Eq for Texture {}
+
 impl Default for Texture {
     #[inline]
     fn default() -> Self {
         Self::blank()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploaded_formats() {
+        assert_eq!(Builder::new().formats(), (gl::RGBA8, gl::UNSIGNED_BYTE));
+        assert_eq!(Builder::new().srgb().formats(), (gl::SRGB8_ALPHA8, gl::UNSIGNED_BYTE));
+        assert_eq!(Builder::new().not_normalised().formats(), (gl::RGBA16F, gl::FLOAT));
+        assert_eq!(Builder::new().srgb().not_normalised().formats(), (gl::RGBA16F, gl::FLOAT));
+    }
+
+    #[test]
+    fn layers_use_the_same_formats_as_single_images() {
+        let image = image::DynamicImage::new_rgba8(1, 1);
+        let builder = Builder::new().srgb();
+        let (internal_format, data_type, _) = builder.pixels(&image, true);
+        assert_eq!((internal_format, data_type), builder.formats());
+    }
+}
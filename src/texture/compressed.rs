@@ -0,0 +1,805 @@
+//! Block-compressed images read from DDS and KTX2 files, with every mip level they hold.
+//!
+//! Compressed data is uploaded as it is stored, so images keep the top-left origin of the
+//! files rather than being flipped like `Builder::image`. Export them flipped vertically, or
+//! flip the texture coordinates, to match.
+
+pub(crate) mod bptc;
+pub(crate) mod etc;
+
+use std::fs;
+
+use crate::{error_fmt, EngineError::TextureErr, Result};
+
+// From EXT_texture_compression_s3tc and EXT_texture_sRGB, which are not part of core OpenGL
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: u32 = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: u32 = 0x8C4F;
+
+const KTX2_IDENTIFIER: [u8; 12] = *b"\xABKTX 20\xBB\r\n\x1A\n";
+
+/// Enough for a chain down to 1x1 from the largest `u32` size
+const MAX_LEVELS: u32 = 32;
+
+/// How each 4x4 block of texels is compressed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    /// BC1 (DXT1) without alpha
+    Bc1Rgb,
+    /// BC1 (DXT1) with 1 bit alpha
+    Bc1Rgba,
+    /// BC2 (DXT3), explicit 4 bit alpha
+    Bc2,
+    /// BC3 (DXT5), interpolated alpha
+    Bc3,
+    /// BC4, a single unsigned channel
+    Bc4,
+    /// BC5, two unsigned channels, usually a normal map
+    Bc5,
+    /// BC6H, unsigned half float RGB
+    Bc6hUnsigned,
+    /// BC6H, signed half float RGB
+    Bc6hSigned,
+    /// BC7, high quality RGBA
+    Bc7,
+    Etc2Rgb,
+    /// ETC2 with 1 bit alpha
+    Etc2RgbA1,
+    /// ETC2 colour with EAC alpha
+    Etc2Rgba,
+}
+
+impl CompressedFormat {
+    /// Bytes per 4x4 block
+    #[must_use]
+    #[inline]
+    pub const fn block_size(self) -> usize {
+        match self {
+            Self::Bc1Rgb | Self::Bc1Rgba | Self::Bc4 | Self::Etc2Rgb | Self::Etc2RgbA1 => 8,
+            _ => 16,
+        }
+    }
+
+    /// The internal format to upload as. BC4, BC5 and BC6H have no sRGB variant and ignore
+    /// `srgb`.
+    #[must_use]
+    #[inline]
+    pub const fn gl_format(self, srgb: bool) -> u32 {
+        match (self, srgb) {
+            (Self::Bc1Rgb, false) => COMPRESSED_RGB_S3TC_DXT1,
+            (Self::Bc1Rgb, true) => COMPRESSED_SRGB_S3TC_DXT1,
+            (Self::Bc1Rgba, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (Self::Bc1Rgba, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (Self::Bc2, false) => COMPRESSED_RGBA_S3TC_DXT3,
+            (Self::Bc2, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            (Self::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (Self::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (Self::Bc4, _) => gl::COMPRESSED_RED_RGTC1,
+            (Self::Bc5, _) => gl::COMPRESSED_RG_RGTC2,
+            (Self::Bc6hUnsigned, _) => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            (Self::Bc6hSigned, _) => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            (Self::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (Self::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (Self::Etc2Rgb, false) => gl::COMPRESSED_RGB8_ETC2,
+            (Self::Etc2Rgb, true) => gl::COMPRESSED_SRGB8_ETC2,
+            (Self::Etc2RgbA1, false) => gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (Self::Etc2RgbA1, true) => gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (Self::Etc2Rgba, false) => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            (Self::Etc2Rgba, true) => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        }
+    }
+
+    /// Whether the driver can sample this format as a 2D texture
+    #[must_use]
+    #[inline]
+    pub fn is_supported(self, srgb: bool) -> bool {
+        let mut supported = 0;
+        unsafe {
+            gl::GetInternalformativ(
+                gl::TEXTURE_2D,
+                self.gl_format(srgb),
+                gl::INTERNALFORMAT_SUPPORTED,
+                1,
+                &mut supported,
+            );
+        }
+        supported == i32::from(gl::TRUE)
+    }
+
+    /// Whether the format holds values outside `[0.0, 1.0]`, which `CompressedImage::decode`
+    /// clamps and `CompressedImage::decode_hdr` keeps
+    #[must_use]
+    #[inline]
+    pub const fn is_hdr(self) -> bool {
+        matches!(self, Self::Bc6hUnsigned | Self::Bc6hSigned)
+    }
+
+    /// Bytes of one level of this size, `None` if that exceeds `usize`
+    const fn level_size(self, width: u32, height: u32) -> Option<usize> {
+        let blocks_wide = (width as usize).div_ceil(4);
+        let blocks_high = (height as usize).div_ceil(4);
+        match blocks_wide.checked_mul(blocks_high) {
+            Some(blocks) => blocks.checked_mul(self.block_size()),
+            None => None,
+        }
+    }
+}
+
+/// One side of mip `level` of an image `size` texels across
+fn level_dimension(size: u32, level: usize) -> u32 {
+    u32::try_from(level)
+        .ok()
+        .and_then(|level| size.checked_shr(level))
+        .unwrap_or(0)
+        .max(1)
+}
+
+fn check_level_count(level_count: u32) -> Result<()> {
+    if level_count > MAX_LEVELS {
+        return Err(TextureErr(error_fmt!(
+            texture::compressed,
+            "{level_count} mip levels is more than the {MAX_LEVELS} any image can have"
+        )));
+    }
+    Ok(())
+}
+
+/// A 2D block-compressed image and its mip levels, largest first
+#[derive(Clone, Debug)]
+pub struct CompressedImage {
+    format: CompressedFormat,
+    srgb: bool,
+    width: u32,
+    height: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| {
+            TextureErr(error_fmt!(
+                texture::compressed,
+                "File ends before its header does"
+            ))
+        })
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<usize> {
+    let low = read_u32(bytes, offset)?;
+    let high = read_u32(bytes, offset + 4)?;
+    (u64::from(high) << 32 | u64::from(low))
+        .try_into()
+        .map_err(|_| TextureErr(error_fmt!(texture::compressed, "Offset exceeds usize")))
+}
+
+impl CompressedImage {
+    /// Read a DDS or KTX2 file, told apart by their first bytes
+    /// # Errors
+    /// Returns an error if the file cannot be read, is neither format or holds anything but a
+    /// single 2D image in a supported format
+    #[inline]
+    pub fn open(filepath: &str) -> Result<Self> {
+        let bytes = fs::read(filepath).map_err(|_| {
+            TextureErr(error_fmt!(
+                texture::compressed,
+                "Opening texture at {filepath}"
+            ))
+        })?;
+
+        if bytes.starts_with(b"DDS ") {
+            Self::from_dds(&bytes)
+        } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+            Self::from_ktx2(&bytes)
+        } else {
+            Err(TextureErr(error_fmt!(
+                texture::compressed,
+                "{filepath} is neither a DDS nor a KTX2 file"
+            )))
+        }
+    }
+
+    /// # Errors
+    /// Returns an error if the data is not a DDS file of a single compressed 2D image
+    #[inline]
+    pub fn from_dds(bytes: &[u8]) -> Result<Self> {
+        const MIPMAP_COUNT_FLAG: u32 = 0x2_0000;
+        const CUBE_MAP_FLAG: u32 = 0x200;
+        const FOURCC_FLAG: u32 = 0x4;
+
+        if !bytes.starts_with(b"DDS ") || read_u32(bytes, 4)? != 124 {
+            return Err(TextureErr(error_fmt!(
+                texture::compressed,
+                "Not a DDS file"
+            )));
+        }
+
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let level_count = if flags & MIPMAP_COUNT_FLAG == 0 {
+            1
+        } else {
+            read_u32(bytes, 28)?.max(1)
+        };
+        check_level_count(level_count)?;
+        let pixel_flags = read_u32(bytes, 80)?;
+        let four_cc = bytes.get(84..88).unwrap_or_default();
+        let unsupported = || {
+            TextureErr(error_fmt!(
+                texture::compressed,
+                "DDS files must hold a single BC1 to BC7 compressed 2D image"
+            ))
+        };
+
+        if read_u32(bytes, 112)? & CUBE_MAP_FLAG != 0 || pixel_flags & FOURCC_FLAG == 0 {
+            return Err(unsupported());
+        }
+
+        let ((format, srgb), data_start) = if four_cc == b"DX10" {
+            // Extended header after the usual one
+            let dxgi_format = read_u32(bytes, 128)?;
+            let array_size = read_u32(bytes, 140)?;
+            if read_u32(bytes, 132)? != 3 || read_u32(bytes, 136)? & 0x4 != 0 || array_size > 1 {
+                return Err(unsupported());
+            }
+            let format = match dxgi_format {
+                70 | 71 => (CompressedFormat::Bc1Rgba, false),
+                72 => (CompressedFormat::Bc1Rgba, true),
+                73 | 74 => (CompressedFormat::Bc2, false),
+                75 => (CompressedFormat::Bc2, true),
+                76 | 77 => (CompressedFormat::Bc3, false),
+                78 => (CompressedFormat::Bc3, true),
+                79 | 80 => (CompressedFormat::Bc4, false),
+                82 | 83 => (CompressedFormat::Bc5, false),
+                94 | 95 => (CompressedFormat::Bc6hUnsigned, false),
+                96 => (CompressedFormat::Bc6hSigned, false),
+                97 | 98 => (CompressedFormat::Bc7, false),
+                99 => (CompressedFormat::Bc7, true),
+                _ => return Err(unsupported()),
+            };
+            (format, 148)
+        } else {
+            let format = match four_cc {
+                b"DXT1" => CompressedFormat::Bc1Rgba,
+                b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+                b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+                b"ATI1" | b"BC4U" => CompressedFormat::Bc4,
+                b"ATI2" | b"BC5U" => CompressedFormat::Bc5,
+                _ => return Err(unsupported()),
+            };
+            ((format, false), 128)
+        };
+
+        // Levels follow one another, largest first
+        let mut offset: usize = data_start;
+        let levels = (0..level_count)
+            .map(|level| {
+                let data = format
+                    .level_size(
+                        level_dimension(width, level as usize),
+                        level_dimension(height, level as usize),
+                    )
+                    .and_then(|size| bytes.get(offset..offset.checked_add(size)?))
+                    .ok_or_else(|| {
+                        TextureErr(error_fmt!(
+                            texture::compressed,
+                            "DDS file ends within mip level {level}"
+                        ))
+                    })?;
+                let size = data.len();
+                offset += size;
+                Ok(data.to_vec())
+            })
+            .collect::<Result<_>>()?;
+
+        Self::new(format, srgb, width, height, levels)
+    }
+
+    /// # Errors
+    /// Returns an error if the data is not a KTX2 file of a single compressed 2D image without
+    /// supercompression
+    #[inline]
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(TextureErr(error_fmt!(
+                texture::compressed,
+                "Not a KTX2 file"
+            )));
+        }
+
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let layers = read_u32(bytes, 32)?;
+        let faces = read_u32(bytes, 36)?;
+        let level_count = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+        check_level_count(level_count)?;
+
+        if depth > 0 || layers > 1 || faces != 1 || supercompression != 0 {
+            return Err(TextureErr(error_fmt!(
+                texture::compressed,
+                "KTX2 files must hold a single 2D image without supercompression"
+            )));
+        }
+
+        let (format, srgb) = match vk_format {
+            131 => (CompressedFormat::Bc1Rgb, false),
+            132 => (CompressedFormat::Bc1Rgb, true),
+            133 => (CompressedFormat::Bc1Rgba, false),
+            134 => (CompressedFormat::Bc1Rgba, true),
+            135 => (CompressedFormat::Bc2, false),
+            136 => (CompressedFormat::Bc2, true),
+            137 => (CompressedFormat::Bc3, false),
+            138 => (CompressedFormat::Bc3, true),
+            139 => (CompressedFormat::Bc4, false),
+            141 => (CompressedFormat::Bc5, false),
+            143 => (CompressedFormat::Bc6hUnsigned, false),
+            144 => (CompressedFormat::Bc6hSigned, false),
+            145 => (CompressedFormat::Bc7, false),
+            146 => (CompressedFormat::Bc7, true),
+            147 => (CompressedFormat::Etc2Rgb, false),
+            148 => (CompressedFormat::Etc2Rgb, true),
+            149 => (CompressedFormat::Etc2RgbA1, false),
+            150 => (CompressedFormat::Etc2RgbA1, true),
+            151 => (CompressedFormat::Etc2Rgba, false),
+            152 => (CompressedFormat::Etc2Rgba, true),
+            _ => {
+                return Err(TextureErr(error_fmt!(
+                    texture::compressed,
+                    "Unsupported KTX2 format {vk_format}"
+                )))
+            }
+        };
+
+        // The level index follows the 80 byte header, largest level first
+        let levels = (0..level_count as usize)
+            .map(|level| {
+                let entry = 80 + level * 24;
+                let offset = read_u64(bytes, entry)?;
+                let length = read_u64(bytes, entry + 8)?;
+                offset
+                    .checked_add(length)
+                    .and_then(|end| bytes.get(offset..end))
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| {
+                        TextureErr(error_fmt!(
+                            texture::compressed,
+                            "KTX2 file ends within mip level {level}"
+                        ))
+                    })
+            })
+            .collect::<Result<_>>()?;
+
+        Self::new(format, srgb, width, height, levels)
+    }
+
+    /// # Errors
+    /// Returns an error if there are more than 32 levels or a level holds the wrong number of
+    /// bytes for its size
+    #[inline]
+    pub fn new(
+        format: CompressedFormat,
+        srgb: bool,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 || levels.is_empty() {
+            return Err(TextureErr(error_fmt!(
+                texture::compressed,
+                "Compressed images need a size and at least one level"
+            )));
+        }
+
+        check_level_count(u32::try_from(levels.len()).unwrap_or(u32::MAX))?;
+
+        for (level, data) in levels.iter().enumerate() {
+            let expected = format.level_size(
+                level_dimension(width, level),
+                level_dimension(height, level),
+            );
+            if expected != Some(data.len()) {
+                return Err(TextureErr(error_fmt!(
+                    texture::compressed,
+                    "Mip level {level} is the wrong size, found {} bytes",
+                    data.len()
+                )));
+            }
+        }
+
+        Ok(Self {
+            format,
+            srgb,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn format(&self) -> CompressedFormat {
+        self.format
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn srgb(&self) -> bool {
+        self.srgb
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Compressed bytes of each level, largest first
+    #[must_use]
+    #[inline]
+    pub fn levels(&self) -> &[Vec<u8>] {
+        &self.levels
+    }
+
+    /// Size of `level`
+    pub(crate) fn level_dimensions(&self, level: usize) -> (u32, u32) {
+        (
+            level_dimension(self.width, level),
+            level_dimension(self.height, level),
+        )
+    }
+
+    /// Decompress `level` to RGBA8, for drivers without support for the format. BC6H values are
+    /// clamped to `[0.0, 1.0]`, see `decode_hdr`.
+    /// # Errors
+    /// Returns an error if there is no such level
+    #[inline]
+    pub fn decode(&self, level: usize) -> Result<Vec<u8>> {
+        let decode_block: fn(&[u8]) -> [[u8; 4]; 16] = match self.format {
+            CompressedFormat::Bc1Rgb => |block| bc1_block(block, false, true),
+            CompressedFormat::Bc1Rgba => |block| bc1_block(block, true, true),
+            CompressedFormat::Bc2 => bc2_block,
+            CompressedFormat::Bc3 => bc3_block,
+            CompressedFormat::Bc4 => bc4_block,
+            CompressedFormat::Bc5 => bc5_block,
+            CompressedFormat::Bc6hUnsigned | CompressedFormat::Bc6hSigned => {
+                return Ok(self
+                    .decode_hdr(level)?
+                    .into_iter()
+                    .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect())
+            }
+            CompressedFormat::Bc7 => bptc::bc7_block,
+            CompressedFormat::Etc2Rgb => |block| etc::etc2_block(block, false),
+            CompressedFormat::Etc2RgbA1 => |block| etc::etc2_block(block, true),
+            CompressedFormat::Etc2Rgba => etc::etc2_rgba_block,
+        };
+        self.decode_with(level, decode_block)
+    }
+
+    /// Decompress `level` to RGBA32F, keeping the full range of BC6H. Other formats are scaled
+    /// to `[0.0, 1.0]`.
+    /// # Errors
+    /// Returns an error if there is no such level
+    #[inline]
+    pub fn decode_hdr(&self, level: usize) -> Result<Vec<f32>> {
+        let signed = match self.format {
+            CompressedFormat::Bc6hUnsigned => false,
+            CompressedFormat::Bc6hSigned => true,
+            _ => {
+                return Ok(self
+                    .decode(level)?
+                    .into_iter()
+                    .map(|value| f32::from(value) / 255.0)
+                    .collect())
+            }
+        };
+
+        self.decode_with(level, |block| {
+            bptc::bc6h_block(block, signed).map(|[r, g, b]| [r, g, b, 1.0])
+        })
+    }
+
+    /// Decode every block of `level` with `decode_block` and lay the texels out in rows
+    fn decode_with<T: Copy + Default>(
+        &self,
+        level: usize,
+        decode_block: impl Fn(&[u8]) -> [[T; 4]; 16],
+    ) -> Result<Vec<T>> {
+        let data = self.levels.get(level).ok_or_else(|| {
+            TextureErr(error_fmt!(
+                texture::compressed,
+                "No mip level {level}, there are {}",
+                self.levels.len()
+            ))
+        })?;
+
+        let (width, height) = self.level_dimensions(level);
+        let (width, height) = (width as usize, height as usize);
+        let blocks_wide = width.div_ceil(4);
+        let mut pixels = vec![T::default(); width * height * 4];
+
+        for (index, block) in data.chunks_exact(self.format.block_size()).enumerate() {
+            let (block_x, block_y) = (index % blocks_wide * 4, index / blocks_wide * 4);
+            for (texel, colour) in decode_block(block).into_iter().enumerate() {
+                let (x, y) = (block_x + texel % 4, block_y + texel / 4);
+                // Blocks overhang images whose sides are not multiples of 4
+                if x < width && y < height {
+                    let start = (y * width + x) * 4;
+                    pixels[start..start + 4].copy_from_slice(&colour);
+                }
+            }
+        }
+
+        Ok(pixels)
+    }
+}
+
+fn rgb565(colour: u16) -> [u8; 3] {
+    let expand = |value: u16, bits: u32| {
+        let max = (1 << bits) - 1;
+        (u32::from(value) * 255 / max) as u8
+    };
+    [
+        expand(colour >> 11, 5),
+        expand((colour >> 5) & 0x3F, 6),
+        expand(colour & 0x1F, 5),
+    ]
+}
+
+/// Without `three_colour_allowed` blocks always use four colours, as BC2 and BC3 colour blocks do
+fn bc1_block(block: &[u8], alpha: bool, three_colour_allowed: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |weight_a: u32, weight_b: u32, total: u32| {
+        [0, 1, 2].map(|i| ((u32::from(a[i]) * weight_a + u32::from(b[i]) * weight_b) / total) as u8)
+    };
+
+    let palette: [[u8; 4]; 4] = if c0 > c1 || !three_colour_allowed {
+        let (third, two_thirds) = (mix(2, 1, 3), mix(1, 2, 3));
+        [
+            [a[0], a[1], a[2], 255],
+            [b[0], b[1], b[2], 255],
+            [third[0], third[1], third[2], 255],
+            [two_thirds[0], two_thirds[1], two_thirds[2], 255],
+        ]
+    } else {
+        let half = mix(1, 1, 2);
+        [
+            [a[0], a[1], a[2], 255],
+            [b[0], b[1], b[2], 255],
+            [half[0], half[1], half[2], 255],
+            [0, 0, 0, if alpha { 0 } else { 255 }],
+        ]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        *colour = palette[(indices >> (texel * 2)) as usize & 0b11];
+    }
+    texels
+}
+
+fn bc2_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = bc1_block(&block[8..], false, false);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap_or_default());
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        colour[3] = ((alpha >> (texel * 4)) & 0xF) as u8 * 17;
+    }
+    texels
+}
+
+/// A BC3 alpha or BC4 channel block, 8 bytes for 16 values
+fn bc4_channel(block: &[u8]) -> [u8; 16] {
+    let (a, b) = (u32::from(block[0]), u32::from(block[1]));
+    let mut palette = [0; 8];
+    palette[0] = a;
+    palette[1] = b;
+    if a > b {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as u32) * a + i as u32 * b) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as u32) * a + i as u32 * b) / 5;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut index_bytes = [0; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+    let mut values = [0; 16];
+    for (texel, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (texel * 3)) & 0b111) as usize] as u8;
+    }
+    values
+}
+
+fn bc3_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = bc1_block(&block[8..], false, false);
+    for (colour, alpha) in texels.iter_mut().zip(bc4_channel(&block[..8])) {
+        colour[3] = alpha;
+    }
+    texels
+}
+
+fn bc4_block(block: &[u8]) -> [[u8; 4]; 16] {
+    bc4_channel(block).map(|red| [red, 0, 0, 255])
+}
+
+fn bc5_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let (red, green) = (bc4_channel(&block[..8]), bc4_channel(&block[8..]));
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        colour[0] = red[texel];
+        colour[1] = green[texel];
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A KTX2 header for a BC1 image with the given level index entries
+    fn ktx2(width: u32, height: u32, level_count: u32, index: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for field in [131, 1, width, height, 0, 0, 1, level_count, 0] {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.resize(80, 0);
+        for (offset, length) in index {
+            bytes.extend(offset.to_le_bytes());
+            bytes.extend(length.to_le_bytes());
+            bytes.extend(0_u64.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn ktx2_single_level() {
+        let mut bytes = ktx2(4, 4, 1, &[(104, 8)]);
+        bytes.extend([0xAB; 8]);
+        let image = CompressedImage::from_ktx2(&bytes).unwrap();
+        assert_eq!(image.levels(), [vec![0xAB; 8]]);
+    }
+
+    #[test]
+    fn too_many_levels() {
+        assert!(CompressedImage::from_ktx2(&ktx2(4, 4, 33, &[])).is_err());
+        assert!(CompressedImage::new(
+            CompressedFormat::Bc1Rgb,
+            false,
+            4,
+            4,
+            vec![vec![0; 8]; 33]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn level_range_overflow() {
+        let bytes = ktx2(4, 4, 1, &[(u64::MAX - 2, 8)]);
+        assert!(CompressedImage::from_ktx2(&bytes).is_err());
+    }
+
+    #[test]
+    fn bc1_four_colours() {
+        let texels = bc1_block(&[0xFF, 0xFF, 0, 0, 0xE4, 0, 0, 0], true, true);
+        assert_eq!(texels[0], [255, 255, 255, 255]);
+        assert_eq!(texels[1], [0, 0, 0, 255]);
+        assert_eq!(texels[2], [170, 170, 170, 255]);
+        assert_eq!(texels[3], [85, 85, 85, 255]);
+    }
+
+    #[test]
+    fn bc1_three_colours() {
+        let block = [0, 0, 0xFF, 0xFF, 0xE4, 0, 0, 0];
+        let texels = bc1_block(&block, true, true);
+        assert_eq!(texels[2], [127, 127, 127, 255]);
+        assert_eq!(texels[3], [0; 4]);
+        assert_eq!(bc1_block(&block, false, true)[3], [0, 0, 0, 255]);
+        // BC2 and BC3 colour blocks always have four colours
+        assert_eq!(bc1_block(&block, false, false)[3], [170, 170, 170, 255]);
+    }
+
+    #[test]
+    fn bc2_alpha() {
+        let mut block = [0xF5, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF].to_vec();
+        block.extend([0xFF, 0xFF, 0, 0, 0, 0, 0, 0]);
+        let texels = bc2_block(&block);
+        assert_eq!(texels[0], [255, 255, 255, 85]);
+        assert_eq!(texels[1], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bc4_palettes() {
+        let red = |block: [u8; 8]| bc4_block(&block).map(|[red, ..]| red);
+
+        let eight = red([255, 0, 0x0A, 0, 0, 0, 0, 0]);
+        assert_eq!(eight[..3], [218, 0, 255]);
+
+        // Index 6 and 7 are 0 and 255 when the first endpoint is not greater
+        let six = red([0, 255, 0x32, 0x0E, 0, 0, 0, 0]);
+        assert_eq!(six[..4], [51, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc5_channels() {
+        let mut block = [200, 200, 0, 0, 0, 0, 0, 0].to_vec();
+        block.extend([100, 100, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bc5_block(&block), [[200, 100, 0, 255]; 16]);
+    }
+
+    #[test]
+    fn decode_crops_overhanging_blocks() {
+        let image = CompressedImage::new(
+            CompressedFormat::Bc1Rgb,
+            false,
+            2,
+            2,
+            vec![vec![0xFF, 0xFF, 0, 0, 0x44, 0x44, 0, 0]],
+        )
+        .unwrap();
+        assert_eq!(
+            image.decode(0).unwrap(),
+            [[255; 4], [0, 0, 0, 255], [255; 4], [0, 0, 0, 255]].concat()
+        );
+        assert!(image.decode(1).is_err());
+    }
+
+    #[test]
+    fn decode_hdr_keeps_range() {
+        // A BC6H mode 11 block of 65504 everywhere
+        let mut block = 0b00011_u128;
+        for shift in [5, 15, 25] {
+            block |= 1023 << shift;
+        }
+        let image = CompressedImage::new(
+            CompressedFormat::Bc6hUnsigned,
+            false,
+            4,
+            4,
+            vec![block.to_le_bytes().to_vec()],
+        )
+        .unwrap();
+
+        assert_eq!(image.decode_hdr(0).unwrap()[..4], [65504.0, 65504.0, 65504.0, 1.0]);
+        assert_eq!(image.decode(0).unwrap()[..4], [255; 4]);
+    }
+
+    #[test]
+    fn huge_dimensions() {
+        assert_eq!(level_dimension(u32::MAX, 31), 1);
+        assert_eq!(level_dimension(u32::MAX, 40), 1);
+        assert_eq!(level_dimension(8, 1), 4);
+        assert!(CompressedImage::new(
+            CompressedFormat::Bc7,
+            false,
+            u32::MAX,
+            u32::MAX,
+            vec![vec![0; 16]]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,694 @@
+//! BC6H and BC7 blocks, which share their partitions and bit packing.
+
+/// Reads fields from a block least significant bit first
+struct Bits {
+    value: u128,
+    position: u32,
+}
+
+impl Bits {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            value: u128::from_le_bytes(block[..16].try_into().unwrap_or_default()),
+            position: 0,
+        }
+    }
+
+    #[expect(clippy::cast_possible_truncation)]
+    fn take(&mut self, count: u32) -> u32 {
+        let field = (self.value >> self.position) as u32 & ((1 << count) - 1);
+        self.position += count;
+        field
+    }
+
+    /// Read `count` bits into `target` starting at bit `shift`
+    fn take_into(&mut self, target: &mut u32, shift: u32, count: u32) {
+        *target |= self.take(count) << shift;
+    }
+}
+
+/// The subset of each texel for two subset partitions, one bit per texel
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// The subset of each texel for three subset partitions, two bits per texel
+const PARTITIONS_3: [u32; 64] = [
+    0xAA68_5050, 0x6A5A_5040, 0x5A5A_4200, 0x5450_A0A8, 0xA5A5_0000, 0xA0A0_5050, 0x5555_A0A0,
+    0x5A5A_5050, 0xAA55_0000, 0xAA55_5500, 0xAAAA_5500, 0x9090_9090, 0x9494_9494, 0xA4A4_A4A4,
+    0xA9A5_9450, 0x2A0A_4250, 0xA594_5040, 0x0A42_5054, 0xA5A5_A500, 0x55A0_A0A0, 0xA8A8_5454,
+    0x6A6A_4040, 0xA4A4_5000, 0x1A1A_0500, 0x0050_A4A4, 0xAAA5_9090, 0x1469_6914, 0x6969_1400,
+    0xA085_85A0, 0xAA82_1414, 0x50A4_A450, 0x6A5A_0200, 0xA9A5_8000, 0x5090_A0A8, 0xA8A0_9050,
+    0x2424_2424, 0x00AA_5500, 0x2492_4924, 0x2449_9224, 0x50A5_0A50, 0x500A_A550, 0xAAAA_4444,
+    0x6666_0000, 0xA5A0_A5A0, 0x50A0_50A0, 0x6928_6928, 0x44AA_AA44, 0x6666_6600, 0xAA44_4444,
+    0x54A8_54A8, 0x9580_9580, 0x9696_9600, 0xA854_54A8, 0x8095_9580, 0xAA14_1414, 0x9696_0000,
+    0xAAAA_1414, 0xA050_50A0, 0xA0A5_A5A0, 0x9600_0000, 0x4080_4080, 0xA9A8_A9A8, 0xAAAA_AA44,
+    0x2A4A_5254,
+];
+
+/// The texel of the second subset stored with one bit less index
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+/// The subset of `texel` in `partition` of a block with `subsets` subsets
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        2 => usize::from((PARTITIONS_2[partition] >> texel) & 1 == 1),
+        3 => ((PARTITIONS_3[partition] >> (texel * 2)) & 0b11) as usize,
+        _ => 0,
+    }
+}
+
+/// Whether `texel` has its index stored with one bit less
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            2 => texel == ANCHORS_2[partition].into(),
+            3 => {
+                texel == ANCHORS_3_SECOND[partition].into()
+                    || texel == ANCHORS_3_THIRD[partition].into()
+            }
+            _ => false,
+        }
+}
+
+/// Read an index for each texel, anchors having one bit less
+fn read_indices(bits: &mut Bits, index_bits: u32, anchor: impl Fn(usize) -> bool) -> [u32; 16] {
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = bits.take(index_bits - u32::from(anchor(texel)));
+    }
+    indices
+}
+
+#[expect(clippy::cast_possible_truncation)]
+fn interpolate(a: u32, b: u32, weight: u32) -> u8 {
+    (((64 - weight) * a + weight * b + 32) >> 6) as u8
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    colour_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    (rotation_bits, index_selection_bits): (u32, u32),
+    (colour_bits, alpha_bits): (u32, u32),
+    (endpoint_p_bits, shared_p_bits): (bool, bool),
+    (index_bits, secondary_index_bits): (u32, u32),
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        colour_bits,
+        alpha_bits,
+        endpoint_p_bits,
+        shared_p_bits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, (0, 0), (4, 0), (true, false), (3, 0)),
+    bc7_mode(2, 6, (0, 0), (6, 0), (false, true), (3, 0)),
+    bc7_mode(3, 6, (0, 0), (5, 0), (false, false), (2, 0)),
+    bc7_mode(2, 6, (0, 0), (7, 0), (true, false), (2, 0)),
+    bc7_mode(1, 0, (2, 1), (5, 6), (false, false), (2, 3)),
+    bc7_mode(1, 0, (2, 0), (7, 8), (false, false), (2, 2)),
+    bc7_mode(1, 0, (0, 0), (7, 7), (true, false), (4, 0)),
+    bc7_mode(2, 6, (0, 0), (5, 5), (true, false), (2, 0)),
+];
+
+/// Scale a `bits` wide value to 8 bits, repeating its high bits in the low ones
+const fn expand(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+pub fn bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut bits = Bits::new(block);
+    let Some(mode_number) = (0..8).find(|_| bits.take(1) == 1) else {
+        // Reserved mode, decoded as transparent black
+        return [[0; 4]; 16];
+    };
+    let mode = &BC7_MODES[mode_number];
+
+    let partition = bits.take(mode.partition_bits) as usize;
+    let rotation = bits.take(mode.rotation_bits);
+    let index_selection = bits.take(mode.index_selection_bits) == 1;
+
+    // endpoints[subset * 2 + end][channel]
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0_u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.take(mode.colour_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = bits.take(mode.alpha_bits);
+    }
+
+    let (colour_bits, alpha_bits) = if mode.endpoint_p_bits || mode.shared_p_bits {
+        let p_bits: Vec<u32> = if mode.endpoint_p_bits {
+            (0..endpoint_count).map(|_| bits.take(1)).collect()
+        } else {
+            (0..mode.subsets)
+                .flat_map(|_| {
+                    let p = bits.take(1);
+                    [p, p]
+                })
+                .collect()
+        };
+        for (endpoint, p) in endpoints.iter_mut().zip(p_bits) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                if channel < 3 || mode.alpha_bits > 0 {
+                    *value = *value << 1 | p;
+                }
+            }
+        }
+        (
+            mode.colour_bits + 1,
+            mode.alpha_bits + u32::from(mode.alpha_bits > 0),
+        )
+    } else {
+        (mode.colour_bits, mode.alpha_bits)
+    };
+
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for value in &mut endpoint[..3] {
+            *value = expand(*value, colour_bits);
+        }
+        endpoint[3] = if alpha_bits == 0 {
+            255
+        } else {
+            expand(endpoint[3], alpha_bits)
+        };
+    }
+
+    let primary = read_indices(&mut bits, mode.index_bits, |texel| {
+        is_anchor(mode.subsets, partition, texel)
+    });
+    let secondary = if mode.secondary_index_bits > 0 {
+        Some(read_indices(&mut bits, mode.secondary_index_bits, |texel| {
+            texel == 0
+        }))
+    } else {
+        None
+    };
+
+    let mut texels = [[0; 4]; 16];
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        let subset = subset(mode.subsets, partition, texel);
+        let (a, b) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let (colour_index, colour_weights, alpha_index, alpha_weights) = match secondary {
+            Some(secondary) if index_selection => (
+                secondary[texel],
+                weights(mode.secondary_index_bits),
+                primary[texel],
+                weights(mode.index_bits),
+            ),
+            Some(secondary) => (
+                primary[texel],
+                weights(mode.index_bits),
+                secondary[texel],
+                weights(mode.secondary_index_bits),
+            ),
+            None => (
+                primary[texel],
+                weights(mode.index_bits),
+                primary[texel],
+                weights(mode.index_bits),
+            ),
+        };
+
+        for channel in 0..3 {
+            colour[channel] =
+                interpolate(a[channel], b[channel], colour_weights[colour_index as usize]);
+        }
+        colour[3] = interpolate(a[3], b[3], alpha_weights[alpha_index as usize]);
+
+        if rotation > 0 {
+            colour.swap(3, rotation as usize - 1);
+        }
+    }
+    texels
+}
+
+/// Where each endpoint field of a BC6H mode is stored, in the order the bits appear after the
+/// mode. `(field, first bit, count)`, with fields numbered `endpoint * 3 + channel`.
+type Bc6hLayout = &'static [(u8, u8, u8)];
+
+struct Bc6hMode {
+    /// The value of the mode bits
+    id: u32,
+    /// Whether there are two subsets
+    partitioned: bool,
+    /// Whether endpoints other than the first are stored as deltas from it
+    transformed: bool,
+    /// Precision of the first endpoint
+    base_bits: u32,
+    /// Precision of each channel of the other endpoints
+    delta_bits: [u32; 3],
+    layout: Bc6hLayout,
+    /// Whether the high bits of the first endpoint are stored in reverse order
+    reversed: bool,
+}
+
+// Fields, numbered `endpoint * 3 + channel`
+const R0: u8 = 0;
+const G0: u8 = 1;
+const B0: u8 = 2;
+const R1: u8 = 3;
+const G1: u8 = 4;
+const B1: u8 = 5;
+const R2: u8 = 6;
+const G2: u8 = 7;
+const B2: u8 = 8;
+const R3: u8 = 9;
+const G3: u8 = 10;
+const B3: u8 = 11;
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { id: 0b00, partitioned: true, transformed: true, base_bits: 10, delta_bits: [5, 5, 5], reversed: false, layout: &[
+        (G2, 4, 1), (B2, 4, 1), (B3, 4, 1), (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 5),
+        (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1),
+        (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b01, partitioned: true, transformed: true, base_bits: 7, delta_bits: [6, 6, 6], reversed: false, layout: &[
+        (G2, 5, 1), (G3, 4, 1), (G3, 5, 1), (R0, 0, 7), (B3, 0, 1), (B3, 1, 1), (B2, 4, 1),
+        (G0, 0, 7), (B2, 5, 1), (B3, 2, 1), (G2, 4, 1), (B0, 0, 7), (B3, 3, 1), (B3, 5, 1),
+        (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6), (G3, 0, 4), (B1, 0, 6), (B2, 0, 4),
+        (R2, 0, 6), (R3, 0, 6),
+    ] },
+    Bc6hMode { id: 0b00010, partitioned: true, transformed: true, base_bits: 11, delta_bits: [5, 4, 4], reversed: false, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 5), (R0, 10, 1), (G2, 0, 4), (G1, 0, 4),
+        (G0, 10, 1), (B3, 0, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1), (B3, 1, 1), (B2, 0, 4),
+        (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b00110, partitioned: true, transformed: true, base_bits: 11, delta_bits: [4, 5, 4], reversed: false, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (G3, 4, 1), (G2, 0, 4),
+        (G1, 0, 5), (G0, 10, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1), (B3, 1, 1), (B2, 0, 4),
+        (R2, 0, 4), (B3, 0, 1), (B3, 2, 1), (R3, 0, 4), (G2, 4, 1), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b01010, partitioned: true, transformed: true, base_bits: 11, delta_bits: [4, 4, 5], reversed: false, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (B2, 4, 1), (G2, 0, 4),
+        (G1, 0, 4), (G0, 10, 1), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B0, 10, 1), (B2, 0, 4),
+        (R2, 0, 4), (B3, 1, 1), (B3, 2, 1), (R3, 0, 4), (B3, 4, 1), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b01110, partitioned: true, transformed: true, base_bits: 9, delta_bits: [5, 5, 5], reversed: false, layout: &[
+        (R0, 0, 9), (B2, 4, 1), (G0, 0, 9), (G2, 4, 1), (B0, 0, 9), (B3, 4, 1), (R1, 0, 5),
+        (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1),
+        (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b10010, partitioned: true, transformed: true, base_bits: 8, delta_bits: [6, 5, 5], reversed: false, layout: &[
+        (R0, 0, 8), (G3, 4, 1), (B2, 4, 1), (G0, 0, 8), (B3, 2, 1), (G2, 4, 1), (B0, 0, 8),
+        (B3, 3, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4),
+        (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 6), (R3, 0, 6),
+    ] },
+    Bc6hMode { id: 0b10110, partitioned: true, transformed: true, base_bits: 8, delta_bits: [5, 6, 5], reversed: false, layout: &[
+        (R0, 0, 8), (B3, 0, 1), (B2, 4, 1), (G0, 0, 8), (G2, 5, 1), (G2, 4, 1), (B0, 0, 8),
+        (G3, 5, 1), (B3, 4, 1), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 6), (G3, 0, 4),
+        (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b11010, partitioned: true, transformed: true, base_bits: 8, delta_bits: [5, 5, 6], reversed: false, layout: &[
+        (R0, 0, 8), (B3, 1, 1), (B2, 4, 1), (G0, 0, 8), (B2, 5, 1), (G2, 4, 1), (B0, 0, 8),
+        (B3, 5, 1), (B3, 4, 1), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1),
+        (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+    ] },
+    Bc6hMode { id: 0b11110, partitioned: true, transformed: false, base_bits: 6, delta_bits: [6, 6, 6], reversed: false, layout: &[
+        (R0, 0, 6), (G3, 4, 1), (B3, 0, 1), (B3, 1, 1), (B2, 4, 1), (G0, 0, 6), (G2, 5, 1),
+        (B2, 5, 1), (B3, 2, 1), (G2, 4, 1), (B0, 0, 6), (G3, 5, 1), (B3, 3, 1), (B3, 5, 1),
+        (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6), (G3, 0, 4), (B1, 0, 6), (B2, 0, 4),
+        (R2, 0, 6), (R3, 0, 6),
+    ] },
+    Bc6hMode { id: 0b00011, partitioned: false, transformed: false, base_bits: 10, delta_bits: [10, 10, 10], reversed: false, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 10), (G1, 0, 10), (B1, 0, 10),
+    ] },
+    Bc6hMode { id: 0b00111, partitioned: false, transformed: true, base_bits: 11, delta_bits: [9, 9, 9], reversed: false, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 9), (R0, 10, 1), (G1, 0, 9), (G0, 10, 1),
+        (B1, 0, 9), (B0, 10, 1),
+    ] },
+    Bc6hMode { id: 0b01011, partitioned: false, transformed: true, base_bits: 12, delta_bits: [8, 8, 8], reversed: true, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 8), (R0, 10, 2), (G1, 0, 8), (G0, 10, 2),
+        (B1, 0, 8), (B0, 10, 2),
+    ] },
+    Bc6hMode { id: 0b01111, partitioned: false, transformed: true, base_bits: 16, delta_bits: [4, 4, 4], reversed: true, layout: &[
+        (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 6), (G1, 0, 4), (G0, 10, 6),
+        (B1, 0, 4), (B0, 10, 6),
+    ] },
+];
+
+#[expect(clippy::cast_possible_wrap)]
+const fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// Widen an endpoint to 16 bits before interpolating
+fn unquantise(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantised = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantised
+        } else {
+            unquantised
+        }
+    } else if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scale an interpolated value to the bits of a half float
+#[expect(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn finish_unquantise(value: i32, signed: bool) -> u16 {
+    if signed {
+        if value < 0 {
+            0x8000 | ((-value * 31) >> 5) as u16
+        } else {
+            ((value * 31) >> 5) as u16
+        }
+    } else {
+        ((value * 31) >> 6) as u16
+    }
+}
+
+/// IEEE 754 half precision bits as a float
+pub fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from(half >> 10 & 0x1F);
+    let mantissa = f32::from(half & 0x03FF);
+    match exponent {
+        0 => sign * mantissa * 2.0_f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2.0_f32.powi(exponent - 15),
+    }
+}
+
+/// Decode to linear RGB, `signed` for `CompressedFormat::Bc6hSigned`
+pub fn bc6h_block(block: &[u8], signed: bool) -> [[f32; 3]; 16] {
+    let mut bits = Bits::new(block);
+    let mut id = bits.take(2);
+    if id > 1 {
+        id |= bits.take(3) << 2;
+    }
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.id == id) else {
+        // Reserved mode, decoded as black
+        return [[0.0; 3]; 16];
+    };
+
+    let mut fields = [0_u32; 12];
+    for &(field, shift, count) in mode.layout {
+        let field = &mut fields[field as usize];
+        if mode.reversed && shift == 10 {
+            let high = bits.take(count.into());
+            let reversed = high.reverse_bits() >> (32 - u32::from(count));
+            *field |= reversed << 10;
+        } else {
+            bits.take_into(field, shift.into(), count.into());
+        }
+    }
+
+    let endpoint_count = if mode.partitioned { 4 } else { 2 };
+    let mut endpoints = [[0_i32; 3]; 4];
+    for channel in 0..3 {
+        let base = fields[channel];
+        endpoints[0][channel] = if signed {
+            sign_extend(base, mode.base_bits)
+        } else {
+            base.cast_signed()
+        };
+
+        for endpoint in 1..endpoint_count {
+            let value = fields[endpoint * 3 + channel];
+            endpoints[endpoint][channel] = if mode.transformed {
+                let delta = sign_extend(value, mode.delta_bits[channel]).cast_unsigned();
+                let sum = base.wrapping_add(delta) & ((1 << mode.base_bits) - 1);
+                if signed {
+                    sign_extend(sum, mode.base_bits)
+                } else {
+                    sum.cast_signed()
+                }
+            } else if signed {
+                sign_extend(value, mode.delta_bits[channel])
+            } else {
+                value.cast_signed()
+            };
+        }
+    }
+
+    let partition = if mode.partitioned {
+        bits.take(5) as usize
+    } else {
+        0
+    };
+    let (subsets, index_bits) = if mode.partitioned { (2, 3) } else { (1, 4) };
+    let indices = read_indices(&mut bits, index_bits, |texel| {
+        is_anchor(subsets, partition, texel)
+    });
+    let weights = weights(index_bits);
+
+    let mut texels = [[0.0; 3]; 16];
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        let subset = subset(subsets, partition, texel);
+        let weight = weights[indices[texel] as usize].cast_signed();
+        for (channel, value) in colour.iter_mut().enumerate() {
+            let [a, b] = [subset * 2, subset * 2 + 1]
+                .map(|endpoint| unquantise(endpoints[endpoint][channel], mode.base_bits, signed));
+            let interpolated = ((64 - weight) * a + weight * b + 32) >> 6;
+            *value = half_to_f32(finish_unquantise(interpolated, signed));
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack `(value, bits)` fields least significant bit first
+    fn pack(fields: &[(u32, u32)]) -> [u8; 16] {
+        let mut block = 0_u128;
+        let mut position = 0;
+        for &(value, bits) in fields {
+            block |= u128::from(value) << position;
+            position += bits;
+        }
+        assert_eq!(position, 128);
+        block.to_le_bytes()
+    }
+
+    /// Indices for every texel, with `bits` less one for the anchors at `anchors`
+    fn indices(values: [u32; 16], bits: u32, anchors: &[usize]) -> Vec<(u32, u32)> {
+        (0..16)
+            .map(|texel| (values[texel], bits - u32::from(anchors.contains(&texel))))
+            .collect()
+    }
+
+    #[test]
+    fn partitions_start_in_the_first_subset_and_anchor_the_others() {
+        for partition in 0..64 {
+            assert_eq!(subset(2, partition, 0), 0);
+            assert_eq!(subset(3, partition, 0), 0);
+            assert_eq!(subset(2, partition, ANCHORS_2[partition].into()), 1);
+            assert_eq!(subset(3, partition, ANCHORS_3_SECOND[partition].into()), 1);
+            assert_eq!(subset(3, partition, ANCHORS_3_THIRD[partition].into()), 2);
+        }
+    }
+
+    #[test]
+    fn bc7_mode_6() {
+        let mut fields = vec![
+            (1 << 6, 7),
+            (0, 7),
+            (127, 7),
+            (0, 7),
+            (127, 7),
+            (0, 7),
+            (127, 7),
+            (127, 7),
+            (127, 7),
+            (0, 1),
+            (1, 1),
+        ];
+        let mut values = [0; 16];
+        values[8] = 8;
+        values[15] = 15;
+        fields.extend(indices(values, 4, &[0]));
+
+        let texels = bc7_block(&pack(&fields));
+        assert_eq!(texels[0], [0, 0, 0, 254]);
+        assert_eq!(texels[8], [135, 135, 135, 255]);
+        assert_eq!(texels[15], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bc7_mode_1_partitions() {
+        // Partition 0 puts the two right columns in the second subset
+        let mut fields = vec![(0b10, 2), (0, 6)];
+        for channel in [[63, 63, 1, 1], [0, 0, 1, 1], [0, 0, 63, 63]] {
+            fields.extend(channel.map(|value| (value, 6)));
+        }
+        fields.extend([(0, 1), (1, 1)]);
+        fields.extend(indices([0; 16], 3, &[0, 15]));
+
+        let texels = bc7_block(&pack(&fields));
+        for (texel, colour) in texels.into_iter().enumerate() {
+            if texel % 4 < 2 {
+                assert_eq!(colour, [253, 0, 0, 255]);
+            } else {
+                assert_eq!(colour, [6, 6, 255, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc7_mode_5_rotation() {
+        // Rotation 1 swaps red and alpha after interpolating
+        let mut fields = vec![(1 << 5, 6), (1, 2)];
+        fields.extend([(127, 7), (127, 7), (0, 7), (0, 7), (0, 7), (0, 7)]);
+        fields.extend([(64, 8), (64, 8)]);
+        fields.extend(indices([0; 16], 2, &[0]));
+        fields.extend(indices([0; 16], 2, &[0]));
+
+        assert_eq!(bc7_block(&pack(&fields)), [[64, 0, 0, 255]; 16]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode() {
+        assert_eq!(bc7_block(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(half_to_f32(0x3C00), 1.0);
+        assert_eq!(half_to_f32(0xC000), -2.0);
+        assert_eq!(half_to_f32(0x7BFF), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2.0_f32.powi(-24));
+        assert_eq!(half_to_f32(0x7C00), f32::INFINITY);
+        assert!(half_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn bc6h_untransformed() {
+        let mut fields = vec![(0b00011, 5)];
+        fields.extend([(0, 10), (0, 10), (0, 10), (1023, 10), (512, 10), (0, 10)]);
+        let mut values = [0; 16];
+        values[15] = 15;
+        fields.extend(indices(values, 4, &[0]));
+
+        let texels = bc6h_block(&pack(&fields), false);
+        assert_eq!(texels[0], [0.0; 3]);
+        assert_eq!(texels[15], [65504.0, half_to_f32(0x3E0F), 0.0]);
+    }
+
+    #[test]
+    fn bc6h_signed() {
+        let mut fields = vec![(0b00011, 5)];
+        fields.extend([(0x3FF, 10), (0, 10), (1, 10), (0, 10), (0, 10), (0, 10)]);
+        fields.extend(indices([0; 16], 4, &[0]));
+
+        let [red, green, blue] = bc6h_block(&pack(&fields), true)[0];
+        assert!(red < 0.0);
+        assert_eq!(green, 0.0);
+        assert!(blue > 0.0);
+        assert_eq!(red, -blue);
+    }
+
+    #[test]
+    fn bc6h_transformed() {
+        // An 11 bit base of 1024 with a delta of -1 to 1023
+        let mut fields = vec![(0b00111, 5)];
+        fields.extend([(0, 10), (0, 10), (0, 10), (0x1FF, 9), (1, 1)]);
+        fields.extend([(0, 9), (0, 1), (0, 9), (0, 1)]);
+        let mut values = [0; 16];
+        values[15] = 15;
+        fields.extend(indices(values, 4, &[0]));
+
+        let texels = bc6h_block(&pack(&fields), false);
+        assert_eq!(texels[0][0], half_to_f32(15879));
+        assert_eq!(texels[15][0], half_to_f32(15864));
+        assert_eq!(texels[0][1], 0.0);
+    }
+
+    #[test]
+    fn bc6h_reversed_high_bits() {
+        // Mode 14 stores bits 10 to 15 of the base from the highest down
+        let mut fields = vec![(0b01111, 5)];
+        fields.extend([(0, 10), (0, 10), (0, 10)]);
+        fields.extend([(0, 4), (1, 6), (0, 4), (0, 6), (0, 4), (0, 6)]);
+        fields.extend(indices([0; 16], 4, &[0]));
+
+        assert_eq!(bc6h_block(&pack(&fields), false), [[1.5, 0.0, 0.0]; 16]);
+    }
+
+    #[test]
+    fn bc6h_reserved_mode() {
+        let block = pack(&[(0b10011, 5), (0, 123)]);
+        assert_eq!(bc6h_block(&block, false), [[0.0; 3]; 16]);
+    }
+}
@@ -0,0 +1,329 @@
+//! ETC2 colour blocks and EAC alpha blocks. Both are big endian, and number their texels down
+//! each column rather than along each row.
+
+/// Intensity modifiers of the individual and differential modes, `[small, large]`
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// Distances between the paint colours of the T and H modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const ALPHA_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// `count` bits of `word` from bit `first` upwards
+#[expect(clippy::cast_possible_truncation)]
+const fn bits(word: u64, first: u32, count: u32) -> i32 {
+    ((word >> first) & ((1 << count) - 1)) as i32
+}
+
+/// Scale a `bits` wide value to 8 bits, repeating its high bits in the low ones
+const fn expand(value: i32, bits: u32) -> i32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+fn clamp(value: i32) -> u8 {
+    u8::try_from(value.clamp(0, 255)).unwrap_or(u8::MAX)
+}
+
+fn offset(colour: [i32; 3], amount: i32) -> [u8; 4] {
+    let [r, g, b] = colour.map(|channel| clamp(channel + amount));
+    [r, g, b, 255]
+}
+
+/// Texel `y * 4 + x` for the index of each texel down the columns
+fn by_row(by_column: impl Fn(usize) -> [u8; 4]) -> [[u8; 4]; 16] {
+    let mut texels = [[0; 4]; 16];
+    for (texel, colour) in texels.iter_mut().enumerate() {
+        *colour = by_column(texel % 4 * 4 + texel / 4);
+    }
+    texels
+}
+
+/// A 2 bit index for the texel numbered `index` down the columns
+const fn texel_index(word: u64, index: usize) -> usize {
+    ((word >> (index + 16) & 1) << 1 | word >> index & 1) as usize
+}
+
+/// An RGB block, with `punchthrough` for `CompressedFormat::Etc2RgbA1` where the differential
+/// bit instead marks blocks without transparent texels
+pub fn etc2_block(block: &[u8], punchthrough: bool) -> [[u8; 4]; 16] {
+    let word = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
+    let flag = bits(word, 33, 1) == 1;
+    let (differential, opaque) = if punchthrough {
+        (true, flag)
+    } else {
+        (flag, true)
+    };
+    let transparent = |index: usize| !opaque && index == 2;
+
+    if !differential {
+        let base = |shift: u32| [60, 52, 44].map(|first| expand(bits(word, first - shift, 4), 4));
+        let colours = [base(0), base(4)];
+        let tables = [bits(word, 37, 3), bits(word, 34, 3)];
+        return individual(word, colours, tables, |_| false);
+    }
+
+    let channel = |first: u32| {
+        let value = bits(word, first, 5);
+        let delta = bits(word, first - 3, 3) << 29 >> 29;
+        (value, value + delta)
+    };
+    let (red, red_2) = channel(59);
+    let (green, green_2) = channel(51);
+    let (blue, blue_2) = channel(43);
+
+    if !(0..32).contains(&red_2) {
+        // T mode
+        let first = [
+            bits(word, 59, 2) << 2 | bits(word, 56, 2),
+            bits(word, 52, 4),
+            bits(word, 48, 4),
+        ]
+        .map(|value| expand(value, 4));
+        let second = [bits(word, 44, 4), bits(word, 40, 4), bits(word, 36, 4)]
+            .map(|value| expand(value, 4));
+        let distance = DISTANCES[(bits(word, 34, 2) << 1 | bits(word, 32, 1)) as usize];
+        let paint = [
+            offset(first, 0),
+            offset(second, distance),
+            offset(second, 0),
+            offset(second, -distance),
+        ];
+        return by_row(|index| {
+            let index = texel_index(word, index);
+            if transparent(index) {
+                [0; 4]
+            } else {
+                paint[index]
+            }
+        });
+    }
+
+    if !(0..32).contains(&green_2) {
+        // H mode
+        let first = [
+            bits(word, 59, 4),
+            bits(word, 56, 3) << 1 | bits(word, 52, 1),
+            bits(word, 51, 1) << 3 | bits(word, 47, 3),
+        ];
+        let second = [bits(word, 43, 4), bits(word, 39, 4), bits(word, 35, 4)];
+        let packed = |[r, g, b]: [i32; 3]| r << 8 | g << 4 | b;
+        let distance = DISTANCES[(bits(word, 34, 1) << 2
+            | bits(word, 32, 1) << 1
+            | i32::from(packed(first) >= packed(second)))
+            as usize];
+        let (first, second) = (first.map(|v| expand(v, 4)), second.map(|v| expand(v, 4)));
+        let paint = [
+            offset(first, distance),
+            offset(first, -distance),
+            offset(second, distance),
+            offset(second, -distance),
+        ];
+        return by_row(|index| {
+            let index = texel_index(word, index);
+            if transparent(index) {
+                [0; 4]
+            } else {
+                paint[index]
+            }
+        });
+    }
+
+    if !(0..32).contains(&blue_2) {
+        return planar(word);
+    }
+
+    let colours = [
+        [red, green, blue].map(|value| expand(value, 5)),
+        [red_2, green_2, blue_2].map(|value| expand(value, 5)),
+    ];
+    let tables = [bits(word, 37, 3), bits(word, 34, 3)];
+    individual(word, colours, tables, transparent)
+}
+
+/// Two half blocks of a base colour each, with an intensity modifier per texel
+fn individual(
+    word: u64,
+    colours: [[i32; 3]; 2],
+    tables: [i32; 2],
+    transparent: impl Fn(usize) -> bool,
+) -> [[u8; 4]; 16] {
+    let flipped = bits(word, 32, 1) == 1;
+    by_row(|index| {
+        let (x, y) = (index / 4, index % 4);
+        let half = usize::from(if flipped { y >= 2 } else { x >= 2 });
+        let texel = texel_index(word, index);
+        if transparent(texel) {
+            return [0; 4];
+        }
+
+        let [small, large] = MODIFIERS[tables[half] as usize];
+        let modifier = match texel {
+            // Punchthrough blocks with transparency lose the small modifier
+            0 if transparent(2) => 0,
+            0 => small,
+            1 => large,
+            2 => -small,
+            _ => -large,
+        };
+        offset(colours[half], modifier)
+    })
+}
+
+/// A gradient from the origin colour along the horizontal and vertical colours
+fn planar(word: u64) -> [[u8; 4]; 16] {
+    let origin = [
+        expand(bits(word, 57, 6), 6),
+        expand(bits(word, 56, 1) << 6 | bits(word, 49, 6), 7),
+        expand(
+            bits(word, 48, 1) << 5 | bits(word, 43, 2) << 3 | bits(word, 39, 3),
+            6,
+        ),
+    ];
+    let horizontal = [
+        expand(bits(word, 34, 5) << 1 | bits(word, 32, 1), 6),
+        expand(bits(word, 25, 7), 7),
+        expand(bits(word, 19, 6), 6),
+    ];
+    let vertical = [
+        expand(bits(word, 13, 6), 6),
+        expand(bits(word, 6, 7), 7),
+        expand(bits(word, 0, 6), 6),
+    ];
+
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, colour) in (0_i32..).zip(texels.iter_mut()) {
+        let (x, y) = (texel % 4, texel / 4);
+        for channel in 0..3 {
+            let (o, h, v) = (origin[channel], horizontal[channel], vertical[channel]);
+            colour[channel] = clamp((x * (h - o) + y * (v - o) + 4 * o + 2) >> 2);
+        }
+    }
+    texels
+}
+
+/// An EAC alpha block, 8 bytes for 16 values in row order
+pub fn eac_alpha(block: &[u8]) -> [u8; 16] {
+    let word = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
+    let base = bits(word, 56, 8);
+    let multiplier = bits(word, 52, 4);
+    let modifiers = ALPHA_MODIFIERS[bits(word, 48, 4) as usize];
+
+    let mut values = [0; 16];
+    for (texel, value) in values.iter_mut().enumerate() {
+        let index = texel % 4 * 4 + texel / 4;
+        let modifier = modifiers[bits(word, 45 - 3 * index as u32, 3) as usize];
+        *value = clamp(base + modifier * multiplier);
+    }
+    values
+}
+
+pub fn etc2_rgba_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = etc2_block(&block[8..], false);
+    for (colour, alpha) in texels.iter_mut().zip(eac_alpha(&block[..8])) {
+        colour[3] = alpha;
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The texel at `x`, `y` of a block decoded in row order
+    fn at(texels: &[[u8; 4]; 16], x: usize, y: usize) -> [u8; 4] {
+        texels[y * 4 + x]
+    }
+
+    #[test]
+    fn individual_mode() {
+        assert_eq!(etc2_block(&[0; 8], false), [[2, 2, 2, 255]; 16]);
+
+        // Red in the left half only
+        let texels = etc2_block(&[0xF0, 0, 0, 0, 0, 0, 0, 0], false);
+        for y in 0..4 {
+            assert_eq!(at(&texels, 1, y), [255, 2, 2, 255]);
+            assert_eq!(at(&texels, 2, y), [2, 2, 2, 255]);
+        }
+    }
+
+    #[test]
+    fn differential_mode_indices() {
+        // The top left texel takes the large negative modifier
+        let texels = etc2_block(&[0, 0, 0, 0x02, 0, 0x01, 0, 0x01], false);
+        assert_eq!(at(&texels, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(at(&texels, 1, 0), [2, 2, 2, 255]);
+        assert_eq!(at(&texels, 0, 1), [2, 2, 2, 255]);
+    }
+
+    #[test]
+    fn t_mode() {
+        let texels = etc2_block(&[0x04, 0, 0x88, 0x82, 0, 0, 0, 0x01], false);
+        assert_eq!(at(&texels, 0, 0), [139, 139, 139, 255]);
+        assert_eq!(at(&texels, 1, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn h_mode() {
+        let texels = etc2_block(&[0, 0x04, 0x78, 0x02, 0, 0x01, 0, 0], false);
+        assert_eq!(at(&texels, 0, 0), [255, 3, 3, 255]);
+        assert_eq!(at(&texels, 3, 3), [3, 3, 3, 255]);
+    }
+
+    #[test]
+    fn planar_mode() {
+        let texels = etc2_block(&[0x7E, 0, 0x04, 0x02, 0, 0, 0, 0], false);
+        assert_eq!(at(&texels, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(at(&texels, 1, 0), [191, 0, 0, 255]);
+        assert_eq!(at(&texels, 0, 1), [191, 0, 0, 255]);
+        assert_eq!(at(&texels, 3, 3), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn punchthrough_alpha() {
+        let texels = etc2_block(&[0, 0, 0, 0, 0, 0x01, 0, 0], true);
+        assert_eq!(at(&texels, 0, 0), [0; 4]);
+        assert_eq!(at(&texels, 1, 0), [0, 0, 0, 255]);
+
+        // With the opaque bit set index 2 is a colour again
+        let texels = etc2_block(&[0, 0, 0, 0x02, 0, 0x01, 0, 0], true);
+        assert_eq!(at(&texels, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(at(&texels, 1, 0), [2, 2, 2, 255]);
+    }
+
+    #[test]
+    fn eac_alpha_values() {
+        assert_eq!(eac_alpha(&[0x80, 0x10, 0, 0, 0, 0, 0, 0]), [125; 16]);
+        assert_eq!(eac_alpha(&[0x80, 0x20, 0, 0, 0, 0, 0, 0]), [122; 16]);
+
+        let values = eac_alpha(&[0x80, 0x10, 0xE0, 0, 0, 0, 0, 0]);
+        assert_eq!(values[0], 142);
+        assert_eq!(values[1], 125);
+    }
+}
@@ -0,0 +1,478 @@
+//! Textures generated on the CPU, for test scenes and placeholders without asset files.
+//!
+//! Each generator returns a `texture::Builder` holding the image, so the stored format and
+//! sampling are chosen on it as for any other image: `srgb` for colour, `not_normalised` to
+//! keep floating point precision for data such as heights. Images are generated top row first,
+//! with `v` increasing upwards once uploaded.
+
+use image::{DynamicImage, Rgba, Rgba32FImage};
+
+use crate::{error_fmt, texture::Builder, EngineError::TextureErr, Result};
+
+/// Colour of the grid lines in `Procedural::uv_grid`
+const GRID_LINE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Direction colours change in `Procedural::gradient`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Gradient {
+    /// From left to right
+    Horizontal,
+    /// From bottom to top
+    Vertical,
+    /// From the centre out to the middle of each edge
+    Radial,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NoiseKind {
+    /// Smooth gradient noise on a square lattice
+    Perlin,
+    /// Gradient noise on a triangular lattice, with fewer axis-aligned artefacts than Perlin.
+    /// Unlike the others it does not tile.
+    Simplex,
+    /// Distance to the nearest of randomly scattered points, giving a cellular look
+    Worley,
+}
+
+/// Fractal noise, summing `octaves` layers of `kind` that each double the frequency and halve
+/// the amplitude of the last. Values are in `[0.0, 1.0]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Noise {
+    kind: NoiseKind,
+    frequency: u32,
+    octaves: u32,
+    seed: u32,
+}
+
+impl Noise {
+    /// A single octave of `frequency` cells across the texture, which is whole so that the
+    /// texture tiles
+    #[must_use]
+    #[inline]
+    pub const fn new(kind: NoiseKind, frequency: u32) -> Self {
+        Self {
+            kind,
+            frequency,
+            octaves: 1,
+            seed: 0,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// Different seeds give unrelated noise of the same character
+    #[must_use]
+    #[inline]
+    pub const fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The noise at `(x, y)`, both in `[0.0, 1.0)` across the texture
+    #[must_use]
+    #[inline]
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitudes = 0.0;
+        let mut period = self.frequency.max(1);
+
+        for octave in 0..self.octaves.max(1) {
+            let seed = self.seed.wrapping_add(octave.wrapping_mul(0x9E37_79B9));
+            #[expect(clippy::cast_precision_loss)]
+            let (x, y) = (x * period as f32, y * period as f32);
+            let value = match self.kind {
+                NoiseKind::Perlin => perlin(x, y, period, seed).mul_add(0.5, 0.5),
+                NoiseKind::Simplex => simplex(x, y, seed).mul_add(0.5, 0.5),
+                NoiseKind::Worley => worley(x, y, period, seed),
+            };
+            total += value.clamp(0.0, 1.0) * amplitude;
+            amplitudes += amplitude;
+            amplitude *= 0.5;
+            period = period.saturating_mul(2);
+        }
+
+        total / amplitudes
+    }
+}
+
+/// Generates textures of one resolution
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Procedural {
+    width: u32,
+    height: u32,
+}
+
+impl Procedural {
+    /// # Errors
+    /// Returns an error if either side is 0
+    #[inline]
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(TextureErr(error_fmt!(
+                texture::Procedural,
+                "Cannot generate a {width}x{height} texture"
+            )));
+        }
+        Ok(Self { width, height })
+    }
+
+    /// The colour of each texel from its `(x, y)` in `[0.0, 1.0)`, measured from the top left
+    #[must_use]
+    #[inline]
+    pub fn from_fn(&self, colour: impl Fn(f32, f32) -> [f32; 4]) -> Builder {
+        #[expect(clippy::cast_precision_loss)]
+        let (width, height) = (self.width as f32, self.height as f32);
+        let image = Rgba32FImage::from_fn(self.width, self.height, |x, y| {
+            #[expect(clippy::cast_precision_loss)]
+            Rgba(colour(x as f32 / width, y as f32 / height))
+        });
+        Builder::new().image_data(DynamicImage::ImageRgba32F(image))
+    }
+
+    /// `cells` squares along each side, alternating between `first` and `second`
+    #[must_use]
+    #[inline]
+    pub fn checkerboard(&self, cells: u32, first: [f32; 4], second: [f32; 4]) -> Builder {
+        #[expect(clippy::cast_precision_loss)]
+        let cells = cells.max(1) as f32;
+        self.from_fn(|x, y| {
+            #[expect(clippy::cast_possible_truncation)]
+            let parity = ((x * cells).floor() + (y * cells).floor()) as i64 % 2;
+            if parity == 0 {
+                first
+            } else {
+                second
+            }
+        })
+    }
+
+    /// A linear blend from `from` to `to`
+    #[must_use]
+    #[inline]
+    pub fn gradient(&self, from: [f32; 4], to: [f32; 4], direction: Gradient) -> Builder {
+        self.from_fn(|x, y| {
+            let amount = match direction {
+                Gradient::Horizontal => x,
+                Gradient::Vertical => 1.0 - y,
+                Gradient::Radial => ((x - 0.5).hypot(y - 0.5) * 2.0).min(1.0),
+            };
+            [0, 1, 2, 3].map(|i| (to[i] - from[i]).mul_add(amount, from[i]))
+        })
+    }
+
+    /// Greyscale noise, opaque
+    #[must_use]
+    #[inline]
+    pub fn noise(&self, noise: Noise) -> Builder {
+        self.from_fn(|x, y| {
+            let value = noise.sample(x, y);
+            [value, value, value, 1.0]
+        })
+    }
+
+    /// Noise as heights for `normal_map`, top row first
+    #[must_use]
+    #[inline]
+    pub fn height_field(&self, noise: Noise) -> Vec<f32> {
+        #[expect(clippy::cast_precision_loss)]
+        let (width, height) = (self.width as f32, self.height as f32);
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                #[expect(clippy::cast_precision_loss)]
+                noise.sample(x as f32 / width, y as f32 / height)
+            })
+            .collect()
+    }
+
+    /// A tangent-space normal map of `heights`, one per texel with the top row first, such as
+    /// from `height_field`. `strength` scales the slopes, where a height difference of 1 across
+    /// a texel is 45 degrees. Edges wrap so that tiling heights give a tiling map.
+    /// # Errors
+    /// Returns an error if there is not one height per texel
+    #[inline]
+    pub fn normal_map(&self, heights: &[f32], strength: f32) -> Result<Builder> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        if heights.len() != width * height {
+            return Err(TextureErr(error_fmt!(
+                texture::Procedural,
+                "Expected {} heights for a {width}x{height} normal map, found {}",
+                width * height,
+                heights.len()
+            )));
+        }
+
+        let at = |x: usize, y: usize| heights[(y % height) * width + x % width];
+        let image = Rgba32FImage::from_fn(self.width, self.height, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            let slope_x = (at(x + 1, y) - at(x + width - 1, y)) * 0.5 * strength;
+            // Rows run downwards but `v` upwards
+            let slope_v = (at(x, y + 1) - at(x, y + height - 1)) * 0.5 * strength;
+            let length = slope_x
+                .mul_add(slope_x, slope_v.mul_add(slope_v, 1.0))
+                .sqrt();
+            let normal = [-slope_x / length, slope_v / length, 1.0 / length];
+            Rgba([
+                normal[0].mul_add(0.5, 0.5),
+                normal[1].mul_add(0.5, 0.5),
+                normal[2].mul_add(0.5, 0.5),
+                1.0,
+            ])
+        });
+        Ok(Builder::new().image_data(DynamicImage::ImageRgba32F(image)))
+    }
+
+    /// `cells` squares along each side with white lines between them, each coloured by the `u`
+    /// and `v` of its centre in red and green, for checking texture coordinates
+    #[must_use]
+    #[inline]
+    pub fn uv_grid(&self, cells: u32) -> Builder {
+        let cells = cells.max(1);
+        #[expect(clippy::cast_precision_loss)]
+        let (cells_f, width, height) = (cells as f32, self.width as f32, self.height as f32);
+        // Lines are about a texel wide however many cells there are
+        let line = cells_f / width.min(height);
+        self.from_fn(|x, y| {
+            let v = 1.0 - y;
+            let (cell_u, cell_v) = (x * cells_f, v * cells_f);
+            let on_line = |position: f32| {
+                let fraction = position.fract();
+                fraction < line * 0.5 || fraction > line.mul_add(-0.5, 1.0)
+            };
+            if on_line(cell_u) || on_line(cell_v) {
+                GRID_LINE
+            } else {
+                [
+                    (cell_u.floor() + 0.5) / cells_f,
+                    (cell_v.floor() + 0.5) / cells_f,
+                    0.25,
+                    1.0,
+                ]
+            }
+        })
+    }
+}
+
+/// Well mixed bits from a lattice point and seed
+const fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut hash =
+        seed ^ (x as u32).wrapping_mul(0x27D4_EB2D) ^ (y as u32).wrapping_mul(0x1656_67B1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2_AE35);
+    hash ^ (hash >> 16)
+}
+
+/// Uniform in `[0.0, 1.0)`
+fn hash_unit(x: i32, y: i32, seed: u32) -> f32 {
+    #[expect(clippy::cast_precision_loss)]
+    ((hash(x, y, seed) >> 8) as f32 / (1 << 24) as f32)
+}
+
+/// One of eight unit gradients, dotted with `(x, y)`
+fn gradient_dot(hash: u32, x: f32, y: f32) -> f32 {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    let (gradient_x, gradient_y) = match hash & 7 {
+        0 => (1.0, 0.0),
+        1 => (-1.0, 0.0),
+        2 => (0.0, 1.0),
+        3 => (0.0, -1.0),
+        4 => (DIAGONAL, DIAGONAL),
+        5 => (-DIAGONAL, DIAGONAL),
+        6 => (DIAGONAL, -DIAGONAL),
+        _ => (-DIAGONAL, -DIAGONAL),
+    };
+    gradient_x.mul_add(x, gradient_y * y)
+}
+
+/// Lattice coordinates of `position`, wrapped to `period`, and the offset within the cell
+fn lattice(position: f32, period: u32) -> (i32, i32, f32) {
+    let floor = position.floor();
+    let period = i64::from(period);
+    #[expect(clippy::cast_possible_truncation)]
+    let wrap = |cell: i64| cell.rem_euclid(period) as i32;
+    #[expect(clippy::cast_possible_truncation)]
+    let cell = floor as i64;
+    (wrap(cell), wrap(cell + 1), position - floor)
+}
+
+/// Perlin noise in about `[-1.0, 1.0]`, repeating every `period` cells
+fn perlin(x: f32, y: f32, period: u32, seed: u32) -> f32 {
+    let fade = |t: f32| t * t * t * t.mul_add(t.mul_add(6.0, -15.0), 10.0);
+    let (x0, x1, dx) = lattice(x, period);
+    let (y0, y1, dy) = lattice(y, period);
+
+    let bottom = gradient_dot(hash(x0, y0, seed), dx, dy);
+    let bottom =
+        (gradient_dot(hash(x1, y0, seed), dx - 1.0, dy) - bottom).mul_add(fade(dx), bottom);
+    let top = gradient_dot(hash(x0, y1, seed), dx, dy - 1.0);
+    let top = (gradient_dot(hash(x1, y1, seed), dx - 1.0, dy - 1.0) - top).mul_add(fade(dx), top);
+
+    ((top - bottom).mul_add(fade(dy), bottom) * std::f32::consts::SQRT_2).clamp(-1.0, 1.0)
+}
+
+/// Simplex noise in about `[-1.0, 1.0]`
+fn simplex(x: f32, y: f32, seed: u32) -> f32 {
+    // Skew to a square lattice of triangle pairs, and back
+    const SKEW: f32 = 0.366_025_42;
+    const UNSKEW: f32 = 0.211_324_87;
+
+    let skew = (x + y) * SKEW;
+    let (cell_x, cell_y) = ((x + skew).floor(), (y + skew).floor());
+    let unskew = (cell_x + cell_y) * UNSKEW;
+    let (x0, y0) = (x - cell_x + unskew, y - cell_y + unskew);
+    // The lower or upper triangle of the cell
+    let (step_x, step_y) = if x0 > y0 { (1.0, 0.0) } else { (0.0, 1.0) };
+
+    #[expect(clippy::cast_possible_truncation)]
+    let (cell_x, cell_y) = (cell_x as i32, cell_y as i32);
+    #[expect(clippy::cast_possible_truncation)]
+    let corners = [
+        (0, 0, x0, y0),
+        (
+            step_x as i32,
+            step_y as i32,
+            x0 - step_x + UNSKEW,
+            y0 - step_y + UNSKEW,
+        ),
+        (
+            1,
+            1,
+            2.0f32.mul_add(UNSKEW, x0 - 1.0),
+            2.0f32.mul_add(UNSKEW, y0 - 1.0),
+        ),
+    ];
+
+    let total: f32 = corners
+        .iter()
+        .map(|&(offset_x, offset_y, dx, dy)| {
+            let falloff = dy.mul_add(-dy, dx.mul_add(-dx, 0.5));
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            let corner_hash = hash(
+                cell_x.wrapping_add(offset_x),
+                cell_y.wrapping_add(offset_y),
+                seed,
+            );
+            falloff.powi(4) * gradient_dot(corner_hash, dx, dy)
+        })
+        .sum();
+
+    (total * 70.0).clamp(-1.0, 1.0)
+}
+
+/// Distance to the nearest feature point, one per cell, repeating every `period` cells
+fn worley(x: f32, y: f32, period: u32, seed: u32) -> f32 {
+    let (cell_x, _, dx) = lattice(x, period);
+    let (cell_y, _, dy) = lattice(y, period);
+    let period = i64::from(period);
+
+    let mut nearest = f32::MAX;
+    for offset_y in -1..=1 {
+        for offset_x in -1..=1 {
+            #[expect(clippy::cast_possible_truncation)]
+            let wrap = |cell: i32, offset: i32| {
+                (i64::from(cell) + i64::from(offset)).rem_euclid(period) as i32
+            };
+            let (neighbour_x, neighbour_y) = (wrap(cell_x, offset_x), wrap(cell_y, offset_y));
+            #[expect(clippy::cast_precision_loss)]
+            let point_x = offset_x as f32 + hash_unit(neighbour_x, neighbour_y, seed);
+            #[expect(clippy::cast_precision_loss)]
+            let point_y = offset_y as f32 + hash_unit(neighbour_x, neighbour_y, seed ^ 0x5BD1_E995);
+            nearest = nearest.min((point_x - dx).hypot(point_y - dy));
+        }
+    }
+
+    nearest.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_near, TOLERANCE};
+
+    fn texels(builder: Builder) -> Rgba32FImage {
+        builder.image_data.unwrap().into_rgba32f()
+    }
+
+    fn grid(steps: u8) -> impl Iterator<Item = (f32, f32)> {
+        let step = move |index: u8| f32::from(index) / f32::from(steps);
+        (0..steps).flat_map(move |y| (0..steps).map(move |x| (step(x), step(y))))
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Worley] {
+            let settings = [(1, 1, 0), (4, 1, 7), (3, 5, 12345), (16, 3, u32::MAX)];
+            for (frequency, octaves, seed) in settings {
+                let noise = Noise::new(kind, frequency).octaves(octaves).seed(seed);
+                for (x, y) in grid(64) {
+                    let value = noise.sample(x, y);
+                    assert!((0.0..=1.0).contains(&value), "{noise:?} gave {value} at {x}, {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn noise_tiles() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Worley] {
+            let noise = Noise::new(kind, 4).octaves(3).seed(99);
+            for (_, along) in grid(32) {
+                let across = (noise.sample(0.0, along) - noise.sample(1.0, along)).abs();
+                let down = (noise.sample(along, 0.0) - noise.sample(along, 1.0)).abs();
+                let tiles = across < TOLERANCE && down < TOLERANCE;
+                assert!(tiles, "{kind:?} does not tile at {along}");
+            }
+        }
+    }
+
+    #[test]
+    fn noise_varies_with_seed() {
+        let first = Noise::new(NoiseKind::Perlin, 4);
+        let second = first.seed(1);
+        assert!(grid(8).any(|(x, y)| (first.sample(x, y) - second.sample(x, y)).abs() > 1.0e-3));
+    }
+
+    #[test]
+    fn checkerboard_parity() {
+        let (first, second) = ([1.0; 4], [0.0, 0.0, 0.0, 1.0]);
+        let image = texels(Procedural::new(8, 8).unwrap().checkerboard(4, first, second));
+
+        for (x, y, texel) in image.enumerate_pixels() {
+            let expected = if (x / 2 + y / 2) % 2 == 0 { first } else { second };
+            assert_eq!(texel.0, expected, "texel {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn normal_map_faces_away_from_the_slope() {
+        let procedural = Procedural::new(4, 4).unwrap();
+        let encoded = |normal: [f32; 3]| normal.map(|component| component.mul_add(0.5, 0.5));
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+
+        // Rising by 1 a texel to the right is 45 degrees, leaning the normal left
+        let rising_right: Vec<f32> = (0..16_u8).map(|texel| f32::from(texel % 4)).collect();
+        let image = texels(procedural.normal_map(&rising_right, 1.0).unwrap());
+        assert_near(&image.get_pixel(1, 1).0[..3], encoded([-diagonal, 0.0, diagonal]));
+
+        // Rising down the image is falling towards increasing `v`, leaning the normal up
+        let rising_down: Vec<f32> = (0..16_u8).map(|texel| f32::from(texel / 4) * 0.5).collect();
+        let image = texels(procedural.normal_map(&rising_down, 2.0).unwrap());
+        assert_near(&image.get_pixel(2, 2).0[..3], encoded([0.0, diagonal, diagonal]));
+
+        // Flat heights point straight out
+        let image = texels(procedural.normal_map(&[0.25; 16], 5.0).unwrap());
+        assert!(image.pixels().all(|texel| texel.0 == [0.5, 0.5, 1.0, 1.0]));
+
+        assert!(procedural.normal_map(&[0.0; 15], 1.0).is_err());
+    }
+}
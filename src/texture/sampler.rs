@@ -0,0 +1,282 @@
+//! How textures are sampled: wrapping, filtering, anisotropy, LOD bias and border colour.
+//!
+//! `SamplerSettings` can be given to a `texture::Builder`, which stores them in the texture, or
+//! built into a `Sampler` object that overrides the settings of any texture it is set on.
+
+use std::{ptr, rc::Rc};
+
+use crate::{capabilities::GlCapabilities, error_fmt, gl_state, EngineError::TextureErr, Result};
+
+// From ARB_texture_filter_anisotropic, core only since OpenGL 4.6
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+
+/// What happens to texture coordinates outside `[0.0, 1.0]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Outside the texture is the border colour
+    ClampToBorder,
+    /// Mirrored once, then clamped
+    MirrorClampToEdge,
+}
+
+impl Wrap {
+    #[must_use]
+    #[inline]
+    pub const fn gl_enum(self) -> u32 {
+        match self {
+            Self::Repeat => gl::REPEAT,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Self::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Self::ClampToBorder => gl::CLAMP_TO_BORDER,
+            Self::MirrorClampToEdge => gl::MIRROR_CLAMP_TO_EDGE,
+        }
+    }
+}
+
+/// How texels are filtered within a mip level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// The closest texel, for a pixelated look or data that must not be blended
+    Nearest,
+    #[default]
+    Linear,
+}
+
+/// How mip levels are chosen when minifying
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipmapMode {
+    /// Only the base level is sampled
+    None,
+    /// The closest level
+    Nearest,
+    /// A blend of the two closest levels
+    Linear,
+}
+
+/// Everything about how a texture is sampled. Unset wraps and mipmap modes are decided by the
+/// texture, see `texture::Builder`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SamplerSettings {
+    wrap_s: Option<Wrap>,
+    wrap_t: Option<Wrap>,
+    wrap_r: Option<Wrap>,
+    min_filter: Filter,
+    mag_filter: Filter,
+    mipmap: Option<MipmapMode>,
+    anisotropy: Option<f32>,
+    lod_bias: f32,
+    border_colour: [f32; 4],
+}
+
+impl SamplerSettings {
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap along every axis
+    #[must_use]
+    #[inline]
+    pub const fn wrap(self, wrap: Wrap) -> Self {
+        self.wrap_s_t(wrap, wrap).wrap_r(wrap)
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn wrap_s_t(mut self, wrap_s: Wrap, wrap_t: Wrap) -> Self {
+        self.wrap_s = Some(wrap_s);
+        self.wrap_t = Some(wrap_t);
+        self
+    }
+
+    /// Wrapping along the third axis of 3D textures and cube maps
+    #[must_use]
+    #[inline]
+    pub const fn wrap_r(mut self, wrap_r: Wrap) -> Self {
+        self.wrap_r = Some(wrap_r);
+        self
+    }
+
+    /// Filter used when the texture is drawn smaller than its size, `Linear` by default
+    #[must_use]
+    #[inline]
+    pub const fn min_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    /// Filter used when the texture is drawn larger than its size, `Linear` by default
+    #[must_use]
+    #[inline]
+    pub const fn mag_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    /// Both the min and mag filter
+    #[must_use]
+    #[inline]
+    pub const fn filter(self, filter: Filter) -> Self {
+        self.min_filter(filter).mag_filter(filter)
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn mipmap(mut self, mode: MipmapMode) -> Self {
+        self.mipmap = Some(mode);
+        self
+    }
+
+    /// Samples taken along the steepest axis of a surface at a glancing angle, clamped to
+    /// between 1, which is off, and `GlCapabilities::max_anisotropy`. Ignored by drivers
+    /// without anisotropic filtering.
+    #[must_use]
+    #[inline]
+    pub const fn anisotropy(mut self, level: f32) -> Self {
+        self.anisotropy = Some(level);
+        self
+    }
+
+    /// Added to the mip level chosen, positive values blur and negative values sharpen
+    #[must_use]
+    #[inline]
+    pub const fn lod_bias(mut self, bias: f32) -> Self {
+        self.lod_bias = bias;
+        self
+    }
+
+    /// Colour outside `Wrap::ClampToBorder` textures, transparent black by default
+    #[must_use]
+    #[inline]
+    pub const fn border_colour(mut self, colour: [f32; 4]) -> Self {
+        self.border_colour = colour;
+        self
+    }
+
+    /// The mipmap mode set, or `default`
+    pub(crate) fn mipmap_or(&self, default: MipmapMode) -> MipmapMode {
+        self.mipmap.unwrap_or(default)
+    }
+
+    /// Set every parameter with `int` and `float`, e.g. `gl::TexParameteri` and
+    /// `gl::TexParameterfv`, with any unset wrap and mipmap mode taken from the defaults
+    pub(crate) fn apply(
+        &self,
+        default_wrap: Wrap,
+        default_mipmap: MipmapMode,
+        mut int: impl FnMut(u32, i32),
+        mut float: impl FnMut(u32, &[f32]),
+    ) {
+        let min_filter = match (self.min_filter, self.mipmap_or(default_mipmap)) {
+            (Filter::Nearest, MipmapMode::None) => gl::NEAREST,
+            (Filter::Linear, MipmapMode::None) => gl::LINEAR,
+            (Filter::Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+
+        #[expect(clippy::cast_possible_wrap)]
+        let as_int = |value: u32| value as i32;
+        int(
+            gl::TEXTURE_WRAP_S,
+            as_int(self.wrap_s.unwrap_or(default_wrap).gl_enum()),
+        );
+        int(
+            gl::TEXTURE_WRAP_T,
+            as_int(self.wrap_t.unwrap_or(default_wrap).gl_enum()),
+        );
+        int(
+            gl::TEXTURE_WRAP_R,
+            as_int(self.wrap_r.unwrap_or(default_wrap).gl_enum()),
+        );
+        int(gl::TEXTURE_MIN_FILTER, as_int(min_filter));
+        int(gl::TEXTURE_MAG_FILTER, as_int(mag_filter));
+        float(gl::TEXTURE_LOD_BIAS, &[self.lod_bias]);
+        float(gl::TEXTURE_BORDER_COLOR, &self.border_colour);
+        let max_anisotropy = GlCapabilities::get().map_or(1.0, GlCapabilities::max_anisotropy);
+        if let Some(level) = self.anisotropy.filter(|_| max_anisotropy > 1.0) {
+            float(TEXTURE_MAX_ANISOTROPY, &[level.clamp(1.0, max_anisotropy)]);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Internal {
+    id: u32,
+    settings: SamplerSettings,
+}
+
+/// An OpenGL sampler object, which can be shared by any number of textures through
+/// `Texture::set_sampler` and takes the place of their own settings
+#[derive(Clone, Debug)]
+pub struct Sampler(Rc<Internal>);
+
+impl Sampler {
+    /// Unset wraps repeat, and an unset mipmap mode is `MipmapMode::Linear`, so textures
+    /// without mip levels should set `MipmapMode::None`
+    /// # Errors
+    /// Returns an error if the driver cannot create a sampler
+    #[inline]
+    pub fn new(settings: SamplerSettings) -> Result<Self> {
+        let mut id = 0;
+        unsafe {
+            gl::GenSamplers(1, ptr::addr_of_mut!(id));
+        }
+        if id == 0 {
+            return Err(TextureErr(error_fmt!(
+                texture::Sampler,
+                "Failed to create a sampler object"
+            )));
+        }
+
+        settings.apply(
+            Wrap::Repeat,
+            MipmapMode::Linear,
+            |parameter, value| unsafe { gl::SamplerParameteri(id, parameter, value) },
+            |parameter, values| unsafe {
+                gl::SamplerParameterfv(id, parameter, values.as_ptr());
+            },
+        );
+
+        Ok(Self(Rc::new(Internal { id, settings })))
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn settings(&self) -> SamplerSettings {
+        self.0.settings
+    }
+}
+
+impl PartialEq for Sampler {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for Sampler {}
+
+impl Drop for Internal {
+    fn drop(&mut self) {
+        gl_state::forget_sampler(self.id);
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+    }
+}